@@ -0,0 +1,283 @@
+// src/audit.rs
+
+//! Promotion audit log.
+//!
+//! Every deployment that reaches HubSpot (`promote`, `rollback` and the
+//! runtime's promote endpoints) appends one JSON line to
+//! `.hsemulator/audit.jsonl`, or to `HSEMULATE_AUDIT_LOG`: what was deployed
+//! where, when, by whom, from which commit and with which test result.
+//!
+//! Entries form a chain: each holds the `hash` of the one before (`prev`)
+//! and its own `hash`, the SHA-256 of the entry without `hash` and `sig`
+//! (serialised with sorted keys). With `HSEMULATE_AUDIT_KEY` set, entries
+//! are also signed with HMAC-SHA256 (`sig`), so an edited log cannot be
+//! re-hashed into a valid one without the key.
+//!
+//! `hsemulate audit verify` checks sequence numbers, the chain, the hashes
+//! and (with the key) the signatures.
+
+use crate::history::Kind;
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+const DEFAULT_LOG: &str = ".hsemulator/audit.jsonl";
+const LOG_ENV: &str = "HSEMULATE_AUDIT_LOG";
+const KEY_ENV: &str = "HSEMULATE_AUDIT_KEY";
+
+/// Serialises appends from concurrent runtime requests.
+static APPEND_LOCK: Mutex<()> = Mutex::new(());
+
+/// Where a deployment was started from.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    Cli,
+    Http,
+}
+
+/// One action changed by an audited deployment.
+#[derive(Debug, Serialize)]
+pub struct AuditAction {
+    pub action_index: usize,
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+}
+
+/// A deployment to audit.
+pub struct Event<'a> {
+    pub origin: Origin,
+    pub kind: Kind,
+    /// cicd.yaml target, or `project/target` of the runtime registry
+    pub target: Option<&'a str>,
+    pub workflow_id: &'a str,
+    pub actions: Vec<AuditAction>,
+    pub revision_id_before: JsonValue,
+    pub revision_id_after: JsonValue,
+    pub force: bool,
+    /// Summary of `.hsemulator/last-test.json`, when there is one
+    pub tests: Option<JsonValue>,
+    /// Who deployed: the authenticated API key for HTTP, else the local
+    /// git/OS user when `None`
+    pub user: Option<&'a str>,
+    /// Actor named in an HTTP request body; unverified, kept apart from `user`
+    pub claimed_actor: Option<&'a str>,
+    /// Promotion history record (CLI only)
+    pub history_id: Option<&'a str>,
+}
+
+/* ---------------- recording ---------------- */
+
+/// Append an entry for `event`.
+pub fn record(event: &Event<'_>) -> Result<()> {
+    let _guard = APPEND_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let path = log_path();
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {:?}", parent))?;
+        }
+    }
+
+    let (seq, prev) = match last_entry(&path)? {
+        Some(last) => (
+            last.get("seq").and_then(|v| v.as_u64()).unwrap_or(0) + 1,
+            last.get("hash").cloned().unwrap_or(JsonValue::Null),
+        ),
+        None => (1, JsonValue::Null),
+    };
+
+    let (git_commit, git_dirty) = git_state();
+    let mut entry = json!({
+        "seq": seq,
+        "prev": prev,
+        "at": Utc::now().to_rfc3339(),
+        "origin": event.origin,
+        "kind": event.kind,
+        "target": event.target,
+        "workflow_id": event.workflow_id,
+        "actions": event.actions,
+        "revision_id_before": event.revision_id_before,
+        "revision_id_after": event.revision_id_after,
+        "force": event.force,
+        "tests": event.tests,
+        "user": match event.origin {
+            Origin::Cli => event.user.map(|u| u.to_string()).or_else(local_user),
+            Origin::Http => event.user.map(|u| u.to_string()),
+        },
+        "claimed_actor": event.claimed_actor,
+        "git_commit": git_commit,
+        "git_dirty": git_dirty,
+        "history_id": event.history_id,
+    });
+
+    let body = serde_json::to_string(&entry)?;
+    entry["hash"] = json!(hex::encode(Sha256::digest(body.as_bytes())));
+    if let Some(key) = signing_key() {
+        entry["sig"] = json!(sign(&key, &body));
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open audit log {:?}", path))?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)
+        .with_context(|| format!("Failed to write audit log {:?}", path))?;
+
+    Ok(())
+}
+
+fn log_path() -> PathBuf {
+    match std::env::var(LOG_ENV) {
+        Ok(path) if !path.trim().is_empty() => PathBuf::from(path),
+        _ => PathBuf::from(DEFAULT_LOG),
+    }
+}
+
+fn signing_key() -> Option<String> {
+    std::env::var(KEY_ENV).ok().filter(|k| !k.is_empty())
+}
+
+fn last_entry(path: &Path) -> Result<Option<JsonValue>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read audit log {:?}", path))?;
+    match raw.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => Ok(Some(serde_json::from_str(line).with_context(|| {
+            format!("Audit log {:?} ends with an invalid entry", path)
+        })?)),
+        None => Ok(None),
+    }
+}
+
+/// `HEAD` commit and whether the work tree has uncommitted changes.
+fn git_state() -> (Option<String>, Option<bool>) {
+    let commit = git(&["rev-parse", "HEAD"]);
+    let dirty = commit
+        .as_ref()
+        .and_then(|_| git(&["status", "--porcelain"]))
+        .map(|status| !status.is_empty());
+    (commit, dirty)
+}
+
+/// CI actor, else git identity, else OS user.
+fn local_user() -> Option<String> {
+    std::env::var("GITHUB_ACTOR")
+        .ok()
+        .or_else(|| git(&["config", "user.email"]))
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .filter(|u| !u.is_empty())
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let out = Command::new("git").args(args).output().ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+fn sign(key: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes any key size");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/* ---------------- verification ---------------- */
+
+/// Entry point for `hsemulate audit verify [--log <path>]`.
+pub fn verify(log: Option<PathBuf>) -> Result<()> {
+    let path = log.unwrap_or_else(log_path);
+    let raw = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read audit log {:?}", path))?;
+    let key = signing_key();
+
+    let mut prev = JsonValue::Null;
+    let mut count = 0u64;
+    let mut signed = 0u64;
+
+    for (i, line) in raw.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line_no = i + 1;
+        let mut entry: JsonValue = serde_json::from_str(line)
+            .with_context(|| format!("Line {}: not a valid JSON entry", line_no))?;
+        let Some(obj) = entry.as_object_mut() else {
+            bail!("Line {}: not a JSON object", line_no);
+        };
+
+        let hash = obj.remove("hash");
+        let sig = obj.remove("sig");
+
+        count += 1;
+        if obj.get("seq").and_then(|v| v.as_u64()) != Some(count) {
+            bail!(
+                "Line {}: expected seq {}, found {} (entries removed or reordered)",
+                line_no,
+                count,
+                obj.get("seq").unwrap_or(&JsonValue::Null)
+            );
+        }
+        if obj.get("prev").unwrap_or(&JsonValue::Null) != &prev {
+            bail!(
+                "Line {}: prev does not match the hash of the entry before (chain broken)",
+                line_no
+            );
+        }
+
+        let body = serde_json::to_string(&entry)?;
+        let expected = hex::encode(Sha256::digest(body.as_bytes()));
+        if hash.as_ref().and_then(|h| h.as_str()) != Some(expected.as_str()) {
+            bail!("Line {}: hash mismatch (entry modified)", line_no);
+        }
+
+        match (&key, sig.as_ref().and_then(|s| s.as_str())) {
+            (Some(key), Some(sig)) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+                    .expect("HMAC takes any key size");
+                mac.update(body.as_bytes());
+                let valid = hex::decode(sig)
+                    .map(|sig| mac.verify_slice(&sig).is_ok())
+                    .unwrap_or(false);
+                if !valid {
+                    bail!("Line {}: invalid signature", line_no);
+                }
+                signed += 1;
+            }
+            (Some(_), None) => bail!("Line {}: entry is not signed", line_no),
+            (None, Some(_)) => bail!(
+                "Line {}: entry is signed; set {} to verify the log",
+                line_no,
+                KEY_ENV
+            ),
+            (None, None) => {}
+        }
+
+        prev = JsonValue::String(expected);
+    }
+
+    let head = prev.as_str().unwrap_or("-");
+    if signed > 0 {
+        println!(
+            "Audit log OK: {} entries, all signed (head {})",
+            count, head
+        );
+    } else {
+        println!("Audit log OK: {} entries, unsigned (head {})", count, head);
+    }
+
+    Ok(())
+}
@@ -5,10 +5,24 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use sha2::{Digest, Sha256};
 use std::env;
 
+/// The API key a request authenticated with, as `key:<sha256 prefix>`.
+///
+/// Added to the request extensions by `api_key_auth`; safe to log.
+#[derive(Debug, Clone)]
+pub struct ApiKeyId(pub String);
+
+impl ApiKeyId {
+    fn of(key: &str) -> Self {
+        let digest = hex::encode(Sha256::digest(key.as_bytes()));
+        ApiKeyId(format!("key:{}", &digest[..12]))
+    }
+}
+
 pub async fn api_key_auth(
-    req: Request<Body>,
+    mut req: Request<Body>,
     next: Next,
 ) -> Response {
     tracing::info!("api_key_auth called");
@@ -34,6 +48,7 @@ pub async fn api_key_auth(
 
     match auth_header {
         Some(value) if value == format!("Bearer {}", expected) => {
+            req.extensions_mut().insert(ApiKeyId::of(&expected));
             // ✅ body is preserved as long as you DO NOT read it
             next.run(req).await
         }
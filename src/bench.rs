@@ -0,0 +1,358 @@
+// src/bench.rs
+
+//! Performance benchmarking (`hsemulate bench`).
+//!
+//! Unlike budgets (a single max check per run), bench mode:
+//! - Executes warm-up runs that are discarded
+//! - Executes a fixed number of measured iterations per fixture
+//! - Reports mean / stddev / percentiles for duration and peak RSS
+//! - Renders an ASCII histogram of durations
+//! - Optionally saves or compares against a stored JSON baseline
+//!
+//! Regressions are only flagged when they are statistically significant
+//! (one-sided Welch's t-test, 95%) AND larger than the configured threshold.
+
+use crate::config::Config;
+use crate::runner::{invoke_once, Invocation, InvokeOptions};
+use crate::stats::{t_critical_95, welch_t, Summary};
+use crate::typescript;
+use crate::util::{ensure_dir, read_to_string};
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Default baseline location when `--save-baseline` / `--compare-baseline`
+/// are passed without a value.
+pub const DEFAULT_BASELINE_PATH: &str = ".hsemulator/bench-baseline.json";
+
+const HISTOGRAM_BUCKETS: usize = 10;
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Options resolved from the CLI.
+#[derive(Debug)]
+pub struct BenchOptions {
+    pub config: PathBuf,
+    pub fixtures: Vec<PathBuf>,
+    pub iterations: u32,
+    pub warmup: u32,
+    pub save_baseline: Option<PathBuf>,
+    pub compare_baseline: Option<PathBuf>,
+    pub threshold_pct: f64,
+    /// Reuse warm workers instead of a cold start per iteration
+    pub warm: bool,
+}
+
+/// Stored benchmark baseline.
+#[derive(Debug, Serialize, Deserialize)]
+struct Baseline {
+    version: u32,
+    created_at: String,
+    action: String,
+    fixtures: BTreeMap<String, FixtureBench>,
+}
+
+/// Benchmark statistics for one fixture.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FixtureBench {
+    duration_ms: Summary,
+    memory_kb: Option<Summary>,
+    failures: u64,
+}
+
+/// Entry point for `hsemulate bench`.
+pub async fn handle(opts: BenchOptions) -> Result<()> {
+    if opts.iterations == 0 {
+        bail!("--iterations must be >= 1");
+    }
+    if opts.threshold_pct < 0.0 {
+        bail!("--threshold must be >= 0");
+    }
+
+    let mut cfg = Config::load(&opts.config)?;
+    if !opts.fixtures.is_empty() {
+        cfg.fixtures = opts
+            .fixtures
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+    }
+
+    let action = cfg
+        .action
+        .as_ref()
+        .context("Missing action configuration")?;
+    let action_file = PathBuf::from(&action.entry)
+        .canonicalize()
+        .context("Unable to resolve action entry")?;
+
+    // Compare against a baseline loaded BEFORE any save, so save + compare
+    // in one invocation compares against the previous run.
+    let previous = match &opts.compare_baseline {
+        Some(path) => Some(load_baseline(path)?),
+        None => None,
+    };
+
+    let invoke_opts = InvokeOptions {
+        quiet: true,
+        warm: opts.warm,
+        compiled: typescript::Compiled::for_action(&cfg.runtime.typescript, &action_file)?
+            .map(Arc::new),
+        ..InvokeOptions::default()
+    };
+    let mut results: BTreeMap<String, FixtureBench> = BTreeMap::new();
+
+    for fixture in &cfg.fixtures {
+        let event: Value = serde_json::from_str(&read_to_string(Path::new(fixture))?)
+            .with_context(|| format!("Fixture is not valid JSON: {}", fixture))?;
+
+        eprintln!(
+            "Benchmarking {} ({} warm-up, {} iterations)…",
+            fixture, opts.warmup, opts.iterations
+        );
+
+        for _ in 0..opts.warmup {
+            invoke_once(&cfg, &action_file, &event, &invoke_opts).await?;
+        }
+
+        let mut durations = Vec::with_capacity(opts.iterations as usize);
+        let mut memory = Vec::with_capacity(opts.iterations as usize);
+        let mut failures = 0u64;
+
+        for _ in 0..opts.iterations {
+            let Invocation {
+                output, metrics, ..
+            } = invoke_once(&cfg, &action_file, &event, &invoke_opts).await?;
+
+            if !output.get("ok").and_then(|v| v.as_bool()).unwrap_or(false) {
+                failures += 1;
+            }
+
+            durations.push(u64::try_from(metrics.duration_ms).unwrap_or(u64::MAX));
+            if let Some(kb) = metrics.peak_memory_kb() {
+                memory.push(kb);
+            }
+        }
+
+        let bench = FixtureBench {
+            duration_ms: Summary::from_samples(&durations).expect("iterations >= 1"),
+            memory_kb: Summary::from_samples(&memory),
+            failures,
+        };
+
+        println!("{}", format_fixture(fixture, &bench, &durations));
+        results.insert(fixture.clone(), bench);
+    }
+
+    let mut regressions: Vec<String> = Vec::new();
+
+    if let Some(base) = &previous {
+        println!("{}", format_comparison_header(base));
+
+        for (fixture, current) in &results {
+            let Some(old) = base.fixtures.get(fixture) else {
+                println!("{}: not present in baseline (skipped)", fixture);
+                continue;
+            };
+
+            let (line, regressed) = compare(
+                "time",
+                "ms",
+                &old.duration_ms,
+                &current.duration_ms,
+                opts.threshold_pct,
+            );
+            println!("{}: {}", fixture, line);
+            if regressed {
+                regressions.push(format!("{} duration", fixture));
+            }
+
+            if let (Some(old_mem), Some(new_mem)) = (&old.memory_kb, &current.memory_kb) {
+                let (line, regressed) =
+                    compare("memory", "kb", old_mem, new_mem, opts.threshold_pct);
+                println!("{}: {}", fixture, line);
+                if regressed {
+                    regressions.push(format!("{} memory", fixture));
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &opts.save_baseline {
+        let baseline = Baseline {
+            version: 1,
+            created_at: Utc::now().to_rfc3339(),
+            action: action.entry.clone(),
+            fixtures: results.clone(),
+        };
+        write_baseline(path, &baseline)?;
+        eprintln!("Saved benchmark baseline to {}", path.display());
+    }
+
+    let failed_runs: u64 = results.values().map(|r| r.failures).sum();
+    if failed_runs > 0 {
+        eprintln!(
+            "WARNING: {} measured run(s) returned ok=false; timings may not be representative.",
+            failed_runs
+        );
+    }
+
+    if !regressions.is_empty() {
+        for r in &regressions {
+            eprintln!("✖ Performance regression: {}", r);
+        }
+        bail!("Benchmark regressed against baseline");
+    }
+
+    Ok(())
+}
+
+/* ---------------- baseline io ---------------- */
+
+fn load_baseline(path: &Path) -> Result<Baseline> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read benchmark baseline at {:?}", path))?;
+    let baseline: Baseline =
+        serde_json::from_str(&raw).context("Failed to parse benchmark baseline JSON")?;
+
+    if baseline.version != 1 {
+        bail!(
+            "Unsupported benchmark baseline version: {} (expected 1)",
+            baseline.version
+        );
+    }
+
+    Ok(baseline)
+}
+
+fn write_baseline(path: &Path, baseline: &Baseline) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            ensure_dir(parent)?;
+        }
+    }
+
+    let bytes =
+        serde_json::to_vec_pretty(baseline).context("Failed to serialize benchmark baseline")?;
+    std::fs::write(path, bytes)
+        .with_context(|| format!("Failed to write benchmark baseline {:?}", path))?;
+
+    Ok(())
+}
+
+/* ---------------- comparison ---------------- */
+
+/// Compare one metric; returns the rendered line and whether it regressed.
+fn compare(
+    label: &str,
+    unit: &str,
+    old: &Summary,
+    new: &Summary,
+    threshold_pct: f64,
+) -> (String, bool) {
+    let change_pct = if old.mean == 0.0 {
+        0.0
+    } else {
+        (new.mean - old.mean) / old.mean * 100.0
+    };
+
+    let (slower, faster) = match welch_t(old, new) {
+        Some((t, df)) => {
+            let critical = t_critical_95(df);
+            (t > critical, t < -critical)
+        }
+        None => (false, false),
+    };
+
+    let regressed = slower && change_pct > threshold_pct;
+
+    let verdict = if regressed {
+        "REGRESSION"
+    } else if faster && change_pct < -threshold_pct {
+        "improved"
+    } else if slower || faster {
+        "changed (within threshold)"
+    } else {
+        "no significant change"
+    };
+
+    let line = format!(
+        "{} mean {:.1}{} → {:.1}{} ({:+.1}%) — {}",
+        label, old.mean, unit, new.mean, unit, change_pct, verdict
+    );
+
+    (line, regressed)
+}
+
+/* ---------------- rendering ---------------- */
+
+fn format_fixture(fixture: &str, bench: &FixtureBench, durations: &[u64]) -> String {
+    let d = &bench.duration_ms;
+
+    let mut out = String::new();
+    out.push_str(&format!("fixture: {}\n", fixture));
+    out.push_str(&format!("iterations: {}\n", d.n));
+    out.push_str(&format!(
+        "time: mean {:.1}ms ± {:.1}ms | min {}ms | p50 {}ms | p90 {}ms | p95 {}ms | p99 {}ms | max {}ms\n",
+        d.mean, d.stddev, d.min, d.p50, d.p90, d.p95, d.p99, d.max
+    ));
+
+    match &bench.memory_kb {
+        Some(m) => out.push_str(&format!(
+            "memory: mean {:.0}kb ± {:.0}kb | min {}kb | p50 {}kb | p95 {}kb | max {}kb\n",
+            m.mean, m.stddev, m.min, m.p50, m.p95, m.max
+        )),
+        None => out.push_str("memory: n/a\n"),
+    }
+
+    out.push_str("histogram (time):\n");
+    out.push_str(&histogram(durations, HISTOGRAM_BUCKETS, HISTOGRAM_WIDTH));
+
+    out.trim_end().to_string()
+}
+
+fn format_comparison_header(base: &Baseline) -> String {
+    format!("\nbaseline: {} (created {})", base.action, base.created_at)
+}
+
+/// Render an ASCII histogram with `buckets` equal-width bins.
+fn histogram(samples: &[u64], buckets: usize, width: usize) -> String {
+    let (Some(min), Some(max)) = (samples.iter().min(), samples.iter().max()) else {
+        return String::new();
+    };
+
+    let span = (max - min).max(1);
+    let buckets = buckets.min(span as usize + 1).max(1);
+    let bucket_size = span.div_ceil(buckets as u64).max(1);
+
+    let mut counts = vec![0usize; buckets];
+    for s in samples {
+        let idx = (((s - min) / bucket_size) as usize).min(buckets - 1);
+        counts[idx] += 1;
+    }
+
+    let peak = counts.iter().copied().max().unwrap_or(1).max(1);
+    let label_width = (min + bucket_size * buckets as u64).to_string().len();
+
+    let mut out = String::new();
+    for (i, count) in counts.iter().enumerate() {
+        let lo = min + bucket_size * i as u64;
+        let hi = lo + bucket_size;
+        let bar = "#".repeat(count * width / peak);
+        out.push_str(&format!(
+            "  {:>w$}–{:<w$}ms | {:<width$} {}\n",
+            lo,
+            hi,
+            bar,
+            count,
+            w = label_width,
+            width = width
+        ));
+    }
+
+    out
+}
@@ -0,0 +1,487 @@
+// src/bundle.rs
+
+//! Bundling of multi-file JavaScript actions.
+//!
+//! HubSpot takes one source file per action. Actions may still share
+//! helpers through relative CommonJS requires (`require("./lib/x")`):
+//! hsemulate follows them from `action.entry` and inlines every module into
+//! one CommonJS file. The bundle is what runs locally and what `promote`
+//! uploads.
+//!
+//! Only relative requires are inlined. Anything else must already exist in
+//! HubSpot's runtime (Node built-ins and the packages HubSpot provides), so
+//! bundling fails on:
+//! - other package imports
+//! - circular relative imports
+//! - relative ES module `import` / `export … from`
+//!
+//! Single-file actions are not bundled and run exactly as written.
+//!
+//! Locally the bundle is written to the invocation's temp dir with a source
+//! map, so stack traces and breakpoints point at the original files.
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::process::Command as TokioCommand;
+
+/// Packages HubSpot makes available to Node custom code actions.
+const HUBSPOT_PACKAGES: &[&str] = &[
+    "@hubspot/api-client",
+    "async",
+    "aws-sdk",
+    "axios",
+    "bluebird",
+    "googleapis",
+    "lodash",
+    "mongoose",
+    "mysql",
+    "random-number-csprng",
+    "redis",
+    "request",
+];
+
+/// Node built-in modules an action may require without the `node:` prefix.
+const NODE_BUILTINS: &[&str] = &[
+    "assert",
+    "buffer",
+    "child_process",
+    "crypto",
+    "dns",
+    "events",
+    "fs",
+    "http",
+    "https",
+    "net",
+    "os",
+    "path",
+    "perf_hooks",
+    "querystring",
+    "readline",
+    "stream",
+    "string_decoder",
+    "timers",
+    "tls",
+    "url",
+    "util",
+    "worker_threads",
+    "zlib",
+];
+
+/// First line of every bundle.
+const BUNDLE_HEADER: &str = "// Bundled by hsemulate from ";
+
+/// A multi-file action inlined into one CommonJS file.
+#[derive(Debug)]
+pub struct Bundle {
+    /// Bundled source (without a source map reference)
+    pub code: String,
+    /// Every inlined file, entry first
+    pub modules: Vec<PathBuf>,
+    source_map: String,
+}
+
+/// One parsed module of the bundle.
+struct Module {
+    id: String,
+    source: String,
+    /// Relative specifier → module id
+    deps: BTreeMap<String, String>,
+}
+
+/// Bundle `entry` if it requires other local files.
+///
+/// Returns `None` for single-file actions. Fails on relative ES module
+/// imports, which can be neither bundled nor uploaded as a single file.
+pub fn build(entry: &Path) -> Result<Option<Bundle>> {
+    let ext = entry
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    if !matches!(ext.as_str(), "js" | "cjs" | "mjs") {
+        return Ok(None);
+    }
+
+    let entry = entry
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve action entry {:?}", entry))?;
+    let source = std::fs::read_to_string(&entry)
+        .with_context(|| format!("Failed to read action entry {:?}", entry))?;
+    let root = entry.parent().unwrap_or(Path::new("/")).to_path_buf();
+
+    check_esm_imports(&module_id(&root, &entry), &source)?;
+    // An ES module entry cannot be wrapped into a CommonJS bundle
+    if ext == "mjs" || !requires(&source).any(|spec| is_relative(&spec)) {
+        return Ok(None);
+    }
+
+    let mut modules: Vec<(PathBuf, Module)> = Vec::new();
+    let mut stack: Vec<PathBuf> = Vec::new();
+    collect(&root, &entry, &mut stack, &mut modules)?;
+
+    Ok(Some(render(modules)))
+}
+
+/// Whether `source` is a bundle (possibly behind a promotion hash marker).
+pub fn is_bundle(source: &str) -> bool {
+    source
+        .lines()
+        .take(10)
+        .any(|line| line.starts_with(BUNDLE_HEADER))
+}
+
+/// Fail if `entry` cannot be bundled (no-op for single-file actions).
+pub fn check(entry: &Path) -> Result<()> {
+    build(entry).map(|_| ())
+}
+
+impl Bundle {
+    /// Write the bundle and its source map into `dir`; returns the bundle path.
+    pub fn write(&self, dir: &Path, file_name: &str) -> Result<PathBuf> {
+        let path = dir.join(file_name);
+        let map_name = format!("{}.map", file_name);
+
+        let code = format!("{}\n//# sourceMappingURL={}\n", self.code, map_name);
+        std::fs::write(&path, code).context("Failed to write action bundle")?;
+        std::fs::write(dir.join(&map_name), &self.source_map)
+            .context("Failed to write action bundle source map")?;
+        Ok(path)
+    }
+}
+
+/// Write `bundle` into `out_dir` and set up Node to run it.
+///
+/// Must be called before the shim path is added. Returns the file the shim
+/// should load.
+pub fn prepare(
+    bundle: &Bundle,
+    entry: &Path,
+    out_dir: &Path,
+    cmd: &mut TokioCommand,
+) -> Result<PathBuf> {
+    let file_name = entry
+        .file_name()
+        .context("Action entry has no file name")?
+        .to_string_lossy()
+        .to_string();
+    let path = bundle.write(out_dir, &file_name)?;
+    link_runtime(entry, cmd)?;
+    Ok(path)
+}
+
+/// Run code built into a temp dir as if it lived next to `entry`.
+///
+/// Enables source maps and resolves packages from the `node_modules`
+/// folders above `entry`.
+pub fn link_runtime(entry: &Path, cmd: &mut TokioCommand) -> Result<()> {
+    cmd.arg("--enable-source-maps");
+
+    let mut paths: Vec<PathBuf> = entry
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join("node_modules"))
+        .filter(|dir| dir.is_dir())
+        .collect();
+    if let Some(existing) = std::env::var_os("NODE_PATH") {
+        paths.extend(std::env::split_paths(&existing));
+    }
+    if !paths.is_empty() {
+        let joined: OsString =
+            std::env::join_paths(paths).context("Invalid node_modules path for NODE_PATH")?;
+        cmd.env("NODE_PATH", joined);
+    }
+    Ok(())
+}
+
+/* ---------------- module graph ---------------- */
+
+fn collect(
+    root: &Path,
+    file: &Path,
+    stack: &mut Vec<PathBuf>,
+    modules: &mut Vec<(PathBuf, Module)>,
+) -> Result<()> {
+    if let Some(pos) = stack.iter().position(|p| p == file) {
+        let cycle = stack[pos..]
+            .iter()
+            .chain(std::iter::once(&file.to_path_buf()))
+            .map(|p| module_id(root, p))
+            .collect::<Vec<_>>()
+            .join(" → ");
+        bail!("Circular import cannot be bundled: {}", cycle);
+    }
+    if modules.iter().any(|(p, _)| p == file) {
+        return Ok(());
+    }
+
+    let id = module_id(root, file);
+    let raw =
+        std::fs::read_to_string(file).with_context(|| format!("Failed to read module {}", id))?;
+
+    let is_json = file.extension().and_then(|e| e.to_str()) == Some("json");
+    let source = if is_json {
+        format!("module.exports = {};", raw.trim_end())
+    } else {
+        check_esm_imports(&id, &raw)?;
+        raw
+    };
+
+    let mut deps = BTreeMap::new();
+    let mut targets = Vec::new();
+    if !is_json {
+        for spec in requires(&source) {
+            if is_relative(&spec) {
+                let base = file.parent().unwrap_or(root);
+                let target = resolve(base, &spec)
+                    .with_context(|| format!("Cannot resolve require(\"{}\") in {}", spec, id))?;
+                deps.insert(spec, module_id(root, &target));
+                targets.push(target);
+            } else if !is_available_on_hubspot(&spec) {
+                bail!(
+                    "require(\"{}\") in {} cannot be bundled: only relative files, Node \
+                    built-ins and packages provided by HubSpot ({}) are available",
+                    spec,
+                    id,
+                    HUBSPOT_PACKAGES.join(", ")
+                );
+            }
+        }
+    }
+
+    stack.push(file.to_path_buf());
+    modules.push((file.to_path_buf(), Module { id, source, deps }));
+    for target in targets {
+        collect(root, &target, stack, modules)?;
+    }
+    stack.pop();
+    Ok(())
+}
+
+/// Static `require("...")` specifiers in `source`.
+fn requires(source: &str) -> impl Iterator<Item = String> + '_ {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r#"\brequire\s*\(\s*(?:"([^"]+)"|'([^']+)')\s*\)"#).expect("valid regex")
+    });
+    re.captures_iter(source).filter_map(|c| {
+        c.get(1)
+            .or_else(|| c.get(2))
+            .map(|m| m.as_str().to_string())
+    })
+}
+
+fn check_esm_imports(id: &str, source: &str) -> Result<()> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r#"(?m)^\s*(?:import|export)\b[^;'"]*?\bfrom\s*["'](\.{1,2}/[^"']*)["']|\bimport\s*\(\s*["'](\.{1,2}/[^"']*)["']|^\s*import\s*["'](\.{1,2}/[^"']*)["']"#)
+            .expect("valid regex")
+    });
+    if let Some(c) = re.captures(source) {
+        let spec = c
+            .get(1)
+            .or_else(|| c.get(2))
+            .or_else(|| c.get(3))
+            .map_or("", |m| m.as_str());
+        bail!(
+            "ES module import of \"{}\" in {} cannot be bundled; use require(\"{}\")",
+            spec,
+            id,
+            spec
+        );
+    }
+    Ok(())
+}
+
+fn is_relative(spec: &str) -> bool {
+    spec.starts_with("./") || spec.starts_with("../")
+}
+
+fn is_available_on_hubspot(spec: &str) -> bool {
+    if spec.starts_with("node:") {
+        return true;
+    }
+
+    // `lodash/get` → `lodash`, `@hubspot/api-client/lib/x` → `@hubspot/api-client`
+    let segments = if spec.starts_with('@') { 2 } else { 1 };
+    let package = spec
+        .splitn(segments + 1, '/')
+        .take(segments)
+        .collect::<Vec<_>>()
+        .join("/");
+
+    NODE_BUILTINS.contains(&package.as_str()) || HUBSPOT_PACKAGES.contains(&package.as_str())
+}
+
+/// Node's file resolution for a relative specifier (files only, no packages).
+fn resolve(base: &Path, spec: &str) -> Option<PathBuf> {
+    let target = base.join(spec);
+    let candidates = [
+        target.clone(),
+        with_suffix(&target, ".js"),
+        with_suffix(&target, ".cjs"),
+        with_suffix(&target, ".json"),
+        target.join("index.js"),
+        target.join("index.json"),
+    ];
+    candidates
+        .into_iter()
+        .find(|p| p.is_file())
+        .and_then(|p| p.canonicalize().ok())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = path.as_os_str().to_os_string();
+    s.push(suffix);
+    PathBuf::from(s)
+}
+
+/// Stable module id: path relative to the entry's directory, `/`-separated.
+fn module_id(root: &Path, file: &Path) -> String {
+    let root: Vec<Component> = root.components().collect();
+    let file: Vec<Component> = file.components().collect();
+    let common = root.iter().zip(&file).take_while(|(a, b)| a == b).count();
+
+    let mut parts: Vec<String> = vec!["..".to_string(); root.len() - common];
+    parts.extend(
+        file[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().to_string()),
+    );
+    parts.join("/")
+}
+
+/* ---------------- output ---------------- */
+
+fn render(modules: Vec<(PathBuf, Module)>) -> Bundle {
+    let entry_id = modules[0].1.id.clone();
+    let mut code = String::new();
+    let mut map = SourceMap::default();
+
+    let mut push = |code: &mut String, line: &str, origin: Option<(usize, usize)>| {
+        code.push_str(line);
+        code.push('\n');
+        map.line(origin);
+    };
+
+    push(
+        &mut code,
+        &format!("{}{} ({} modules)", BUNDLE_HEADER, entry_id, modules.len()),
+        None,
+    );
+    push(&mut code, "const __hse_modules = {", None);
+
+    for (index, (_, module)) in modules.iter().enumerate() {
+        push(
+            &mut code,
+            &format!(
+                "{}: [function (module, exports, require) {{",
+                json!(module.id)
+            ),
+            None,
+        );
+        for (line_no, line) in module.source.lines().enumerate() {
+            push(&mut code, line, Some((index, line_no)));
+        }
+        push(&mut code, &format!("}}, {}],", json!(module.deps)), None);
+    }
+
+    for line in LOADER.lines() {
+        push(&mut code, line, None);
+    }
+    push(
+        &mut code,
+        &format!("module.exports = __hse_load({});", json!(entry_id)),
+        None,
+    );
+
+    let sources = modules
+        .iter()
+        .map(|(path, _)| {
+            reqwest::Url::from_file_path(path)
+                .map(|u| u.to_string())
+                .unwrap_or_else(|_| path.display().to_string())
+        })
+        .collect::<Vec<_>>();
+    let source_map = json!({
+        "version": 3,
+        "sourceRoot": "",
+        "sources": sources,
+        "names": [],
+        "mappings": map.mappings,
+    })
+    .to_string();
+
+    Bundle {
+        code: code.trim_end().to_string(),
+        modules: modules.into_iter().map(|(path, _)| path).collect(),
+        source_map,
+    }
+}
+
+/// Module loader appended after the inlined modules.
+const LOADER: &str = r#"};
+const __hse_cache = {};
+function __hse_load(id) {
+  if (__hse_cache[id]) return __hse_cache[id].exports;
+  const [factory, deps] = __hse_modules[id];
+  const module = { exports: {} };
+  __hse_cache[id] = module;
+  const localRequire = (spec) =>
+    Object.prototype.hasOwnProperty.call(deps, spec) ? __hse_load(deps[spec]) : require(spec);
+  factory.call(module.exports, module, module.exports, localRequire);
+  return module.exports;
+}"#;
+
+/// Line-level source map (each inlined line maps to column 0 of its origin).
+#[derive(Default)]
+struct SourceMap {
+    mappings: String,
+    lines: usize,
+    last_source: i64,
+    last_line: i64,
+}
+
+impl SourceMap {
+    fn line(&mut self, origin: Option<(usize, usize)>) {
+        if self.lines > 0 {
+            self.mappings.push(';');
+        }
+        self.lines += 1;
+
+        if let Some((source, line)) = origin {
+            let (source, line) = (source as i64, line as i64);
+            vlq(0, &mut self.mappings);
+            vlq(source - self.last_source, &mut self.mappings);
+            vlq(line - self.last_line, &mut self.mappings);
+            vlq(0, &mut self.mappings);
+            self.last_source = source;
+            self.last_line = line;
+        }
+    }
+}
+
+fn vlq(value: i64, out: &mut String) {
+    const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut v = if value < 0 {
+        ((-value) << 1) | 1
+    } else {
+        value << 1
+    };
+    loop {
+        let mut digit = (v & 31) as usize;
+        v >>= 5;
+        if v > 0 {
+            digit |= 32;
+        }
+        out.push(BASE64[digit] as char);
+        if v == 0 {
+            break;
+        }
+    }
+}
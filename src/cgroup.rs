@@ -0,0 +1,148 @@
+// src/cgroup.rs
+
+//! Per-invocation cgroup (v2) for exact resource accounting on Linux.
+//!
+//! When enabled (`metrics.cgroup: true`), every invocation gets its own
+//! cgroup below the configured parent. The child moves itself into it before
+//! `exec`, so every process the action spawns is accounted for, including
+//! orphaned / daemonised grandchildren.
+//!
+//! Requirements:
+//! - cgroup v2 (unified hierarchy)
+//! - Write access to the parent directory (root, or a delegated subtree)
+//! - The `memory` controller enabled for the subtree (for `memory.max` / `memory.peak`)
+//!
+//! The cgroup directory is removed on drop.
+
+use anyhow::{bail, Context, Result};
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use tokio::process::Command as TokioCommand;
+
+/// A cgroup created for one invocation.
+#[derive(Debug)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Create a fresh cgroup below `parent`.
+    ///
+    /// Defaults to the cgroup hsemulate itself runs in.
+    pub fn create(parent: Option<&str>) -> Result<Self> {
+        let parent = match parent {
+            Some(p) => PathBuf::from(p),
+            None => own_cgroup_dir()?,
+        };
+
+        if !parent.join("cgroup.controllers").exists() {
+            bail!(
+                "{:?} is not a cgroup v2 directory (cgroup v1 is not supported)",
+                parent
+            );
+        }
+
+        let path = parent.join(format!("hsemulate-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&path)
+            .with_context(|| format!("Failed to create cgroup {:?}", path))?;
+
+        Ok(Self { path })
+    }
+
+    /// Move the spawned child into this cgroup before it execs.
+    pub fn attach(&self, cmd: &mut TokioCommand) -> Result<()> {
+        let procs = CString::new(self.path.join("cgroup.procs").to_string_lossy().as_bytes())
+            .context("cgroup path contains a NUL byte")?;
+
+        // SAFETY: the hook only calls async-signal-safe libc functions
+        // (open / write / close) on memory allocated before fork.
+        unsafe {
+            cmd.pre_exec(move || {
+                let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                if fd < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                // "0" moves the writing process
+                let rc = libc::write(fd, b"0".as_ptr() as *const libc::c_void, 1);
+                libc::close(fd);
+                if rc < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Apply a hard memory limit (`memory.max`) and disable swap for the cgroup.
+    ///
+    /// Fails when the `memory` controller is not enabled for this subtree.
+    pub fn set_memory_max(&self, bytes: u64) -> Result<()> {
+        let max = self.path.join("memory.max");
+        std::fs::write(&max, bytes.to_string())
+            .with_context(|| format!("Failed to write {:?}", max))?;
+
+        // Without this, the kernel swaps instead of OOM-killing
+        let _ = std::fs::write(self.path.join("memory.swap.max"), "0");
+        Ok(())
+    }
+
+    /// Number of processes OOM-killed in this cgroup (`memory.events`).
+    pub fn oom_kills(&self) -> u64 {
+        std::fs::read_to_string(self.path.join("memory.events"))
+            .ok()
+            .and_then(|raw| {
+                raw.lines()
+                    .find_map(|l| l.strip_prefix("oom_kill "))
+                    .and_then(|v| v.trim().parse().ok())
+            })
+            .unwrap_or(0)
+    }
+
+    /// `memory.peak` in KB (kernel >= 5.19).
+    pub fn memory_peak_kb(&self) -> Option<u64> {
+        read_u64(&self.path.join("memory.peak")).map(|b| b / 1024)
+    }
+
+    /// (user, system) CPU time in milliseconds from `cpu.stat`.
+    pub fn cpu_ms(&self) -> Option<(u64, u64)> {
+        let raw = std::fs::read_to_string(self.path.join("cpu.stat")).ok()?;
+        let field = |name: &str| {
+            raw.lines()
+                .find_map(|l| l.strip_prefix(name))
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        Some((field("user_usec ")? / 1000, field("system_usec ")? / 1000))
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        // Processes may take a moment to leave after exit; retry briefly.
+        for _ in 0..10 {
+            if std::fs::remove_dir(&self.path).is_ok() {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        tracing::warn!("Failed to remove cgroup {:?}", self.path);
+    }
+}
+
+/* ---------------- helpers ---------------- */
+
+fn own_cgroup_dir() -> Result<PathBuf> {
+    let raw = std::fs::read_to_string("/proc/self/cgroup").context("Failed to read /proc/self/cgroup")?;
+
+    let rel = raw
+        .lines()
+        .find_map(|l| l.strip_prefix("0::"))
+        .context("No cgroup v2 entry in /proc/self/cgroup")?;
+
+    Ok(Path::new("/sys/fs/cgroup").join(rel.trim_start_matches('/')))
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
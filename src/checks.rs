@@ -2,7 +2,8 @@
 
 //! Assertions and budget enforcement.
 
-use crate::config::Assertion;
+use crate::config::{Assertion, RequestAssertion};
+use crate::http_capture::CapturedRequest;
 use anyhow::{bail, Result};
 use regex::Regex;
 use serde_json::Value;
@@ -105,6 +106,84 @@ pub fn assert_json(actual: &Value, assertions: &BTreeMap<String, Assertion>) ->
     Ok(())
 }
 
+/// Check that every expected request was made.
+///
+/// Each expectation must be satisfied by at least one captured request.
+pub fn assert_requests(requests: &[CapturedRequest], expected: &[RequestAssertion]) -> Result<()> {
+    for exp in expected {
+        let candidates = requests
+            .iter()
+            .filter(|r| {
+                exp.method
+                    .as_ref()
+                    .is_none_or(|m| m.eq_ignore_ascii_case(&r.method))
+                    && exp
+                        .host
+                        .as_ref()
+                        .is_none_or(|h| h.eq_ignore_ascii_case(&r.host))
+                    && exp.path.as_ref().is_none_or(|p| *p == r.path)
+            })
+            .collect::<Vec<_>>();
+
+        if candidates.is_empty() {
+            let made = if requests.is_empty() {
+                "none".to_string()
+            } else {
+                requests
+                    .iter()
+                    .map(|r| format!("{} {}", r.method, r.path))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            bail!(
+                "Expected request {} was not made (captured: {})",
+                describe_expectation(exp),
+                made
+            );
+        }
+
+        let mut last_err = None;
+        for request in &candidates {
+            let value = serde_json::to_value(request)?;
+            match assert_json(&value, &exp.fields) {
+                Ok(()) => {
+                    last_err = None;
+                    break;
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        if let Some(e) = last_err {
+            bail!(
+                "Request {} did not match ({} candidate(s)): {}",
+                describe_expectation(exp),
+                candidates.len(),
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Enforce the maximum number of outbound requests.
+pub fn check_max_requests(count: usize, max: u64) -> Result<()> {
+    if count as u64 > max {
+        bail!("Request limit exceeded: {} requests (max {})", count, max);
+    }
+    Ok(())
+}
+
+fn describe_expectation(exp: &RequestAssertion) -> String {
+    format!(
+        "{} {}{}",
+        exp.method.as_deref().unwrap_or("ANY"),
+        exp.host.as_deref().unwrap_or(""),
+        exp.path.as_deref().unwrap_or("/*")
+    )
+}
+
 /// Enforce duration and memory budgets.
 pub fn check_budgets(
     duration_ms: u128,
@@ -27,7 +27,7 @@ fn init(kind: Option<CicdInitKind>, runtime: String, branch: Option<String>) ->
     }
 
     let runtime = match runtime.as_str() {
-        "js" => "NODE20X",
+        "js" | "ts" => "NODE20X",
         "python" => "PYTHON39",
         _ => bail!("Unsupported runtime: {}", runtime),
     };
@@ -91,9 +91,11 @@ hubspot:
 
 targets:
   production:
-    portal: eu1
     workflow_id: "REPLACE_ME"
 
+    # Optional: HubSpot API base URL (regional endpoint, proxy or stand-in server)
+    # base_url: https://api.hubapi.com
+
     selector:
       type: secret
       value: HS_ACTION__REPLACE_ME
@@ -108,6 +110,9 @@ targets:
       # Recommended: 4000ms for local testing and deployment, 20000ms/50000ms for CI/CD
       max_duration_ms: 4000
 
+      # Optional: minimum line coverage (%) from `hsemulate test --coverage`
+      # min_coverage: 80
+
     deploy:
       mode: full-flow-replace
       dry_run: false
@@ -117,8 +122,7 @@ targets:
 }
 
 fn default_github_action(_branch: &str) -> String {
-    format!(
-        r#"
+    r#"
 name: hsemulator
 
 on:
@@ -169,5 +173,5 @@ jobs:
           HUBSPOT_TOKEN: ${{ secrets.HUBSPOT_TOKEN }}
 
 "#
-    )
+    .to_string()
 }
@@ -136,6 +136,30 @@ pub enum Command {
         /// Override memory budget (MB, peak RSS)
         #[arg(long)]
         budget_mem: Option<u64>,
+
+        /// Enforce the memory budget as a hard limit (defaults to HubSpot's 128MB)
+        ///
+        /// The runtime is killed when it exceeds the limit, like in production.
+        #[arg(long)]
+        enforce_mem: bool,
+
+        /// Wait for a debugger to attach before running the action
+        ///
+        /// Node starts with --inspect-brk, Python under debugpy.
+        /// Budgets are disabled for the run.
+        #[arg(long)]
+        debug: bool,
+
+        /// Debugger port (defaults: 9229 for Node, 5678 for Python)
+        #[arg(long, requires = "debug")]
+        debug_port: Option<u16>,
+
+        /// Reuse warm runtime workers between invocations
+        ///
+        /// Same as `runtime.mode: warm`. Faster, but module state persists
+        /// between runs; see docs for the isolation trade-offs.
+        #[arg(long)]
+        warm: bool,
     },
 
     /// CI-first execution mode.
@@ -152,12 +176,91 @@ pub enum Command {
     ///
     /// Example:
     ///   hsemulate test
+    ///   hsemulate test --update-perf-baseline
+    ///   hsemulate test --coverage
     Test {
         /// Path to config file
         ///
         /// Defaults to ./config.yaml
         #[arg(short, long, default_value = "config.yaml")]
         config: PathBuf,
+
+        /// Overwrite stored performance baselines with this run's metrics
+        ///
+        /// Only used when `regression` is configured in config.yaml.
+        #[arg(long)]
+        update_perf_baseline: bool,
+
+        /// Collect line / branch coverage of the action source
+        ///
+        /// Writes coverage/lcov.info and prints a summary.
+        /// Python actions need the `coverage` package installed.
+        #[arg(long)]
+        coverage: bool,
+    },
+
+    /// Benchmark an action's performance.
+    ///
+    /// Runs warm-up iterations (discarded), then measured iterations per
+    /// fixture, and reports:
+    /// - mean / stddev / percentiles for duration and peak RSS
+    /// - an ASCII histogram of durations
+    ///
+    /// Budgets and assertions are not applied.
+    ///
+    /// Example:
+    ///   hsemulate bench --iterations 50
+    ///   hsemulate bench --save-baseline
+    ///   hsemulate bench --compare-baseline --threshold 10
+    Bench {
+        /// Path to config file
+        ///
+        /// Defaults to ./config.yaml
+        #[arg(short, long, default_value = "config.yaml")]
+        config: PathBuf,
+
+        /// Override fixture file (repeatable)
+        #[arg(long)]
+        fixture: Vec<PathBuf>,
+
+        /// Measured iterations per fixture
+        #[arg(long, default_value_t = 20)]
+        iterations: u32,
+
+        /// Warm-up iterations per fixture (not measured)
+        #[arg(long, default_value_t = 3)]
+        warmup: u32,
+
+        /// Save results as a JSON baseline
+        ///
+        /// Defaults to .hsemulator/bench-baseline.json when no path is given.
+        #[arg(
+            long,
+            value_name = "FILE",
+            num_args = 0..=1,
+            default_missing_value = crate::bench::DEFAULT_BASELINE_PATH
+        )]
+        save_baseline: Option<PathBuf>,
+
+        /// Compare results against a stored JSON baseline
+        ///
+        /// Fails when a statistically significant regression is found.
+        /// Defaults to .hsemulator/bench-baseline.json when no path is given.
+        #[arg(
+            long,
+            value_name = "FILE",
+            num_args = 0..=1,
+            default_missing_value = crate::bench::DEFAULT_BASELINE_PATH
+        )]
+        compare_baseline: Option<PathBuf>,
+
+        /// Minimum mean increase (percent) counted as a regression
+        #[arg(long, default_value_t = 5.0)]
+        threshold: f64,
+
+        /// Benchmark warm workers instead of cold starts
+        #[arg(long)]
+        warm: bool,
     },
 
     /// Start the HTTP runtime server.
@@ -173,10 +276,16 @@ pub enum Command {
     ///
     /// Example:
     ///   hsemulate runtime --listen 0.0.0.0:8080
+    ///   hsemulate runtime --registry registry.yaml
     Runtime {
         /// Address to listen on
         #[arg(long, default_value = "127.0.0.1:8080")]
         listen: String,
+
+        /// Project and target registry (YAML), enabling
+        /// `POST /projects/{project}/targets/{target}/promote`
+        #[arg(long)]
+        registry: Option<PathBuf>,
     },
 
     /// CI/CD related commands.
@@ -195,8 +304,9 @@ pub enum Command {
     /// - Workflow and action already exist
     /// - CI/CD configuration is present
     ///
-    /// Example:
+    /// Examples:
     ///   hsemulate promote production
+    ///   hsemulate promote production --diff
     Promote {
         /// Promotion target name from .hsemulator/cicd.yaml
         ///
@@ -207,12 +317,117 @@ pub enum Command {
         #[arg(long)]
         force: bool,
 
+        /// Show a diff of the action in HubSpot against the code to promote,
+        /// then ask for confirmation
+        #[arg(long)]
+        diff: bool,
+
+        /// Skip the `--diff` confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Path to action config file
+        ///
+        /// Defaults to ./config.yaml
+        #[arg(short, long, default_value = "config.yaml")]
+        config: PathBuf,
+    },
+
+    /// Report whether each promotion target is in sync with the local code.
+    ///
+    /// Checks every target in .hsemulator/cicd.yaml. Exits non-zero when any
+    /// target is drifted, unmanaged, missing or cannot be checked.
+    ///
+    /// Examples:
+    ///   hsemulate status
+    ///   hsemulate status --json
+    Status {
+        /// Print a machine-readable JSON report
+        #[arg(long)]
+        json: bool,
+
+        /// Path to action config file
+        ///
+        /// Defaults to ./config.yaml
+        #[arg(short, long, default_value = "config.yaml")]
+        config: PathBuf,
+    },
+
+    /// Restore a previously deployed action source.
+    ///
+    /// Every promotion is recorded under .hsemulator/promotions/<target>/.
+    /// Without `--to`, undoes the latest recorded promotion (or rollback).
+    ///
+    /// Examples:
+    ///   hsemulate rollback production --list
+    ///   hsemulate rollback production
+    ///   hsemulate rollback production --to 20260101T120000123Z
+    Rollback {
+        /// Promotion target name from .hsemulator/cicd.yaml
+        target: String,
+
+        /// History entry whose deployed source to restore
+        #[arg(long, value_name = "ENTRY")]
+        to: Option<String>,
+
+        /// List the target's promotion history and exit
+        #[arg(long)]
+        list: bool,
+
+        /// Overwrite the action even if it has no hsemulator-sha marker
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Download the deployed action source into action.entry.
+    ///
+    /// Brings changes made in the HubSpot UI back into the local tree.
+    /// Uncommitted local edits are not overwritten unless `--force` is used.
+    ///
+    /// Example:
+    ///   hsemulate pull production
+    Pull {
+        /// Promotion target name from .hsemulator/cicd.yaml
+        target: String,
+
+        /// Overwrite action.entry even if it has uncommitted changes
+        #[arg(long)]
+        force: bool,
+
         /// Path to action config file
         ///
         /// Defaults to ./config.yaml
         #[arg(short, long, default_value = "config.yaml")]
         config: PathBuf,
     },
+
+    /// Promotion audit log commands.
+    ///
+    /// Every promotion and rollback is appended to .hsemulator/audit.jsonl
+    /// (or HSEMULATE_AUDIT_LOG), signed when HSEMULATE_AUDIT_KEY is set.
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+}
+
+/// Audit log subcommands.
+#[derive(Subcommand, Debug)]
+pub enum AuditCommand {
+    /// Check the audit log's chain, hashes and signatures.
+    ///
+    /// Signatures are checked with HSEMULATE_AUDIT_KEY.
+    ///
+    /// Examples:
+    ///   hsemulate audit verify
+    ///   hsemulate audit verify --log /var/log/hsemulate/audit.jsonl
+    Verify {
+        /// Audit log to check
+        ///
+        /// Defaults to HSEMULATE_AUDIT_LOG, then .hsemulator/audit.jsonl
+        #[arg(long)]
+        log: Option<PathBuf>,
+    },
 }
 
 /// CI/CD subcommands.
@@ -232,8 +447,8 @@ pub enum CicdCommand {
     Init {
         /// Runtime language for the action
         ///
-        /// Required: js | python
-        #[arg(value_parser = ["js", "python"])]
+        /// Required: js | ts | python
+        #[arg(value_parser = ["js", "ts", "python"])]
         runtime: String,
 
         /// Optional CI/CD init type
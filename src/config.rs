@@ -1,5 +1,9 @@
 // src/config.rs
 
+use crate::bundle;
+use crate::fields;
+use crate::typescript;
+
 use anyhow::{Context, Result};
 use serde::Deserialize;
 use std::{collections::BTreeMap, fs, path::Path};
@@ -30,6 +34,18 @@ pub struct Config {
     #[serde(default)]
     pub budgets: Option<Budgets>,
 
+    /// Optional performance regression gate (relative to stored baselines)
+    #[serde(default)]
+    pub regression: Option<RegressionConfig>,
+
+    /// Resource measurement settings
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+
+    /// Outbound network sandbox (disabled when absent)
+    #[serde(default)]
+    pub network: Option<NetworkConfig>,
+
     /// Assertions applied to the action output
     #[serde(default)]
     pub assertions: BTreeMap<String, Assertion>,
@@ -38,6 +54,18 @@ pub struct Config {
     #[serde(default)]
     pub assertions_file: Option<String>,
 
+    /// Outbound HTTP requests the action is expected to make
+    #[serde(default)]
+    pub requests: Vec<RequestAssertion>,
+
+    /// Maximum number of outbound HTTP requests per invocation
+    #[serde(default)]
+    pub max_requests: Option<u64>,
+
+    /// Assertions and snapshots on the action's logs
+    #[serde(default)]
+    pub logs: Option<LogAssertions>,
+
     /// Snapshot configuration
     #[serde(default)]
     pub snapshots: SnapshotConfig,
@@ -62,19 +90,43 @@ pub struct Config {
 /// Action definition.
 #[derive(Debug, Deserialize)]
 pub struct Action {
-    /// js | python
+    /// js | ts | python
     #[serde(rename = "type")]
-    #[allow(dead_code)]
     pub action_type: ActionType,
 
     /// Path to the action file
     pub entry: String,
+
+    /// Secret names, synced to the action's `secretNames` on promote
+    #[serde(default)]
+    pub secrets: Option<Vec<String>>,
+
+    /// Input fields synced on promote: field name → object property
+    #[serde(default)]
+    pub inputs: Option<BTreeMap<String, String>>,
+
+    /// Output fields synced on promote: field name → type
+    #[serde(default)]
+    pub outputs: Option<BTreeMap<String, OutputType>>,
+}
+
+/// Type of an output field, as offered by HubSpot's custom code action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputType {
+    String,
+    Number,
+    Boolean,
+    Date,
+    Datetime,
+    PhoneNumber,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ActionType {
     Js,
+    Ts,
     Python,
 }
 
@@ -84,9 +136,9 @@ pub struct SnapshotConfig {
     #[serde(default)]
     pub enabled: bool,
 
-    /// Paths to ignore when comparing snapshots
+    /// Paths to ignore when comparing snapshots (so far only applied
+    /// when comparing repeated runs)
     #[serde(default)]
-    #[allow(dead_code)]
     pub ignore: Vec<String>,
 }
 
@@ -103,6 +155,62 @@ pub enum Assertion {
     Regex { regex: String },
 }
 
+/// Expectation for one outbound HTTP request.
+///
+/// At least one captured request must match `method` / `host` / `path` and
+/// satisfy every remaining field assertion (e.g. `body.properties.stage`).
+#[derive(Debug, Deserialize)]
+pub struct RequestAssertion {
+    #[serde(default)]
+    pub method: Option<String>,
+
+    #[serde(default)]
+    pub host: Option<String>,
+
+    /// URL path without the query string
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Assertions on the captured request (`body.*`, `headers.*`, `status`, …)
+    #[serde(flatten)]
+    pub fields: BTreeMap<String, Assertion>,
+}
+
+/// Assertions on the lines an action logs.
+#[derive(Debug, Deserialize, Default)]
+pub struct LogAssertions {
+    /// Every entry must appear in at least one line
+    #[serde(default)]
+    pub contains: Vec<String>,
+
+    /// No line may contain any entry
+    #[serde(default)]
+    pub not_contains: Vec<String>,
+
+    /// Every pattern must match at least one line
+    #[serde(default)]
+    pub regex: Vec<String>,
+
+    /// Exact number of lines containing each key
+    #[serde(default)]
+    pub count: BTreeMap<String, u64>,
+
+    #[serde(default)]
+    pub max_lines: Option<u64>,
+
+    /// Fail when the action logs anything via `console.error`
+    #[serde(default)]
+    pub no_errors: bool,
+
+    /// Store logs as a snapshot and compare later runs against it
+    #[serde(default)]
+    pub snapshot: bool,
+
+    /// Lines matching any of these patterns are left out of the snapshot
+    #[serde(default)]
+    pub snapshot_ignore: Vec<String>,
+}
+
 /// Output configuration.
 #[derive(Debug, Deserialize)]
 pub struct OutputConfig {
@@ -144,6 +252,14 @@ pub struct Runtime {
 
     #[serde(default = "default_python")]
     pub python: String,
+
+    /// Process model: a fresh runtime per invocation, or reused workers
+    #[serde(default)]
+    pub mode: RuntimeMode,
+
+    /// Compiler for TypeScript actions
+    #[serde(default)]
+    pub typescript: TypeScriptConfig,
 }
 
 impl Default for Runtime {
@@ -151,6 +267,55 @@ impl Default for Runtime {
         Self {
             node: default_node(),
             python: default_python(),
+            mode: RuntimeMode::default(),
+            typescript: TypeScriptConfig::default(),
+        }
+    }
+}
+
+/// How runtime processes are started.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeMode {
+    /// New interpreter per invocation (closest to a HubSpot cold start)
+    #[default]
+    Cold,
+
+    /// Long-lived workers that keep the action loaded between invocations
+    Warm,
+}
+
+/// TypeScript compiler settings (`action.type: ts`).
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct TypeScriptConfig {
+    #[serde(default)]
+    pub compiler: TsCompiler,
+
+    /// Compiler binary (defaults to `node_modules/.bin/<compiler>`, then PATH)
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Also run `tsc --noEmit` when compiling with esbuild / swc
+    /// (tsc always type-checks)
+    #[serde(default)]
+    pub typecheck: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TsCompiler {
+    #[default]
+    Tsc,
+    Esbuild,
+    Swc,
+}
+
+impl TsCompiler {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TsCompiler::Tsc => "tsc",
+            TsCompiler::Esbuild => "esbuild",
+            TsCompiler::Swc => "swc",
         }
     }
 }
@@ -163,27 +328,92 @@ fn default_python() -> String {
     "python".to_string()
 }
 
+/// Memory ceiling of HubSpot's custom code runtime (MB).
+pub const HUBSPOT_MEMORY_LIMIT_MB: u64 = 128;
+
 /// Optional performance budgets.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct Budgets {
     pub duration_ms: Option<u64>,
     pub memory_mb: Option<u64>,
+
+    /// Enforce the memory budget while the action runs (kill on overrun)
+    /// instead of only checking the measured peak afterwards.
+    #[serde(default)]
+    pub enforce_memory: bool,
+}
+
+impl Budgets {
+    /// Hard memory limit in MB when enforcement is enabled.
+    ///
+    /// Defaults to HubSpot's ceiling when no `memory_mb` is configured.
+    pub fn enforced_memory_mb(&self) -> Option<u64> {
+        if self.enforce_memory {
+            Some(self.memory_mb.unwrap_or(HUBSPOT_MEMORY_LIMIT_MB))
+        } else {
+            None
+        }
+    }
+}
+
+/// Outbound network sandbox.
+///
+/// When present, the action can only reach the listed hosts; every other
+/// connection is rejected and fails the run.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct NetworkConfig {
+    /// Allowed hosts (`api.hubapi.com`, or `*.hubapi.com` for subdomains)
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+/// Resource measurement settings.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct MetricsConfig {
+    /// Run each invocation in its own cgroup (Linux, cgroup v2) for exact
+    /// memory / CPU accounting of the whole process tree
+    #[serde(default)]
+    pub cgroup: bool,
+
+    /// Parent cgroup directory (defaults to hsemulate's own cgroup)
+    #[serde(default)]
+    pub cgroup_parent: Option<String>,
+}
+
+/// Performance regression gate.
+///
+/// Compares each `hsemulate test` run against per-fixture baselines
+/// stored in `snapshots/<action>.<fixture>.perf.json`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RegressionConfig {
+    /// Maximum allowed increase in median duration (percent)
+    pub duration_pct: Option<f64>,
+
+    /// Maximum allowed increase in median peak RSS (percent)
+    pub memory_pct: Option<f64>,
+
+    /// What to do when a regression is detected
+    #[serde(default)]
+    pub on_regression: RegressionAction,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RegressionAction {
+    #[default]
+    Fail,
+    Warn,
 }
 
 /// Execution mode.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum Mode {
+    #[default]
     Normal,
     Ci,
 }
 
-impl Default for Mode {
-    fn default() -> Self {
-        Mode::Normal
-    }
-}
-
 fn default_repeat() -> u32 {
     1
 }
@@ -211,6 +441,11 @@ impl Config {
                 type: js\n\
                 entry: actions/action.js\n\
                 \n\
+                # TypeScript\n\
+                action:\n\
+                type: ts\n\
+                entry: actions/action.ts\n\
+                \n\
                 # Python\n\
                 action:\n\
                 type: python\n\
@@ -225,6 +460,7 @@ impl Config {
                 "Missing action.entry in config.yaml.\n\
                 Set one of:\n\
                 - JS:     action: {{ type: js,     entry: actions/action.js }}\n\
+                - TS:     action: {{ type: ts,     entry: actions/action.ts }}\n\
                 - Python: action: {{ type: python, entry: actions/action.py }}"
             );
         }
@@ -237,6 +473,7 @@ impl Config {
 
         let ext_ok = match action.action_type {
             ActionType::Js => matches!(ext.as_str(), "js" | "mjs" | "cjs"),
+            ActionType::Ts => ext == "ts",
             ActionType::Python => ext == "py",
         };
 
@@ -247,6 +484,7 @@ impl Config {
                 - action.entry: {}\n\
                 Expected extensions:\n\
                 - js: js | mjs | cjs\n\
+                - ts: ts\n\
                 - python: py",
                 action.action_type,
                 entry
@@ -263,6 +501,18 @@ impl Config {
             );
         }
 
+        // Unbundlable imports fail here, before anything runs. TypeScript is
+        // only compiled when the action runs (or by `hsemulate validate`),
+        // so loading a config stays cheap.
+        match action.action_type {
+            ActionType::Ts => typescript::check_tool(&self.runtime.typescript)?,
+            ActionType::Js => bundle::check(entry_path)?,
+            ActionType::Python => {}
+        }
+
+        // Synced to HubSpot on promote
+        fields::validate(action)?;
+
         // ---------- fixtures ----------
         if self.fixtures.is_empty() {
             anyhow::bail!(
@@ -342,6 +592,69 @@ impl Config {
             }
         }
 
+        // ---------- regression ----------
+        if let Some(r) = &self.regression {
+            if r.duration_pct.is_none() && r.memory_pct.is_none() {
+                anyhow::bail!(
+                    "regression is set but neither regression.duration_pct nor regression.memory_pct is configured.\n\
+                    Example:\n\
+                    regression:\n\
+                    duration_pct: 50\n\
+                    memory_pct: 25"
+                );
+            }
+            for (name, pct) in [("duration_pct", r.duration_pct), ("memory_pct", r.memory_pct)] {
+                if let Some(pct) = pct {
+                    if pct <= 0.0 {
+                        anyhow::bail!("regression.{} must be > 0 when set", name);
+                    }
+                }
+            }
+        }
+
+        // ---------- requests ----------
+        for (i, r) in self.requests.iter().enumerate() {
+            if r.method.is_none() && r.host.is_none() && r.path.is_none() && r.fields.is_empty() {
+                anyhow::bail!(
+                    "requests[{}] is empty. Set at least one of method, host, path or a field assertion.\n\
+                    Example:\n\
+                    requests:\n\
+                    - method: PATCH\n\
+                    path: /crm/v3/objects/deals/123",
+                    i
+                );
+            }
+        }
+
+        // ---------- logs ----------
+        if let Some(l) = &self.logs {
+            for pattern in l.regex.iter().chain(&l.snapshot_ignore) {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    anyhow::bail!("Invalid regex in logs: '{}': {}", pattern, e);
+                }
+            }
+        }
+
+        // ---------- network ----------
+        if let Some(n) = &self.network {
+            for host in &n.allow {
+                let host = host.trim();
+                if host.is_empty() {
+                    anyhow::bail!("network.allow contains an empty host (remove it).");
+                }
+                if host.contains("://") || host.contains('/') {
+                    anyhow::bail!(
+                        "network.allow entries must be host names, not URLs: {}\n\
+                        Example:\n\
+                        network:\n\
+                        allow:\n\
+                        - api.hubapi.com",
+                        host
+                    );
+                }
+            }
+        }
+
         // ---------- assertions ----------
         // Basic assertion key sanity so typos fail early
         for (k, v) in &self.assertions {
@@ -0,0 +1,584 @@
+// src/coverage.rs
+
+//! Line / branch coverage of action source (`hsemulate test --coverage`).
+//!
+//! Collection happens per invocation:
+//! - Node: `NODE_V8_COVERAGE` makes V8 dump precise block coverage on exit
+//! - Python: the shim runs the action under the `coverage` package and
+//!   writes its JSON report (the package must be installed)
+//!
+//! Results are merged across fixtures, repeats and configs, then written as
+//! lcov (`coverage/lcov.info`) with a short terminal summary.
+//!
+//! V8 omits block ranges whose count equals their parent's, so a run can
+//! not be turned into line / branch hits on its own. Raw per-offset counts
+//! are merged first and resolved to lines only when reporting.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::limits::Language;
+use crate::util::ensure_dir;
+
+/// Default lcov output path.
+pub const LCOV_PATH: &str = "coverage/lcov.info";
+
+/// Env var telling the Python shim where to write its JSON report.
+const PYTHON_REPORT_ENV: &str = "HSE_COVERAGE_FILE";
+
+/// Coverage data for one action file (possibly merged across runs).
+#[derive(Debug, Clone)]
+pub enum FileCoverage {
+    /// Raw V8 block coverage
+    V8 {
+        source: String,
+        /// Execution count per UTF-16 offset
+        counts: Vec<u64>,
+        /// Start offsets of reported blocks (branches)
+        blocks: BTreeSet<usize>,
+        /// Start offset -> function name
+        functions: BTreeMap<usize, String>,
+    },
+
+    /// Line / branch hits from Python's `coverage` package
+    Lines {
+        lines: BTreeMap<u32, u64>,
+        /// (from line, to line) arcs; negative targets are exits
+        branches: BTreeMap<(u32, i64), u64>,
+    },
+}
+
+/// Coverage merged per action file.
+#[derive(Debug, Clone, Default)]
+pub struct CoverageMap {
+    files: BTreeMap<PathBuf, FileCoverage>,
+}
+
+/// Hit / total counts for one metric.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Ratio {
+    pub hit: u64,
+    pub total: u64,
+}
+
+impl Ratio {
+    pub fn pct(&self) -> Option<f64> {
+        if self.total == 0 {
+            None
+        } else {
+            Some(self.hit as f64 / self.total as f64 * 100.0)
+        }
+    }
+
+    fn add(&mut self, other: Ratio) {
+        self.hit += other.hit;
+        self.total += other.total;
+    }
+}
+
+/// Coverage totals for one file, or for everything.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CoverageSummary {
+    pub lines: Ratio,
+    pub branches: Ratio,
+    pub functions: Ratio,
+}
+
+/// Resolved hits for one file, ready for lcov.
+#[derive(Debug, Default)]
+struct Record {
+    lines: BTreeMap<u32, u64>,
+    /// line -> hits per branch, in source order
+    branches: BTreeMap<u32, Vec<u64>>,
+    /// (line, name, hits)
+    functions: Vec<(u32, String, u64)>,
+}
+
+/* ---------------- collection ---------------- */
+
+/// Configure a runtime to collect coverage into `dir`.
+pub fn prepare(language: Language, dir: &Path, cmd: &mut tokio::process::Command) -> Result<()> {
+    ensure_dir(dir)?;
+    match language {
+        Language::Node => cmd.env("NODE_V8_COVERAGE", dir),
+        Language::Python => cmd.env(PYTHON_REPORT_ENV, dir.join("coverage.json")),
+    };
+    Ok(())
+}
+
+/// Read the coverage a runtime wrote into `dir` for `action_file`.
+///
+/// Returns `None` (with a warning) when no usable data was produced.
+pub fn collect(language: Language, dir: &Path, action_file: &Path) -> Option<FileCoverage> {
+    let result = match language {
+        Language::Node => collect_v8(dir, action_file),
+        Language::Python => collect_python(&dir.join("coverage.json")),
+    };
+
+    match result {
+        Ok(Some(cov)) => Some(cov),
+        Ok(None) => {
+            let hint = match language {
+                Language::Node => "",
+                Language::Python => " (is the `coverage` package installed? pip install coverage)",
+            };
+            eprintln!(
+                "WARNING: no coverage data for {}{}",
+                action_file.display(),
+                hint
+            );
+            None
+        }
+        Err(e) => {
+            eprintln!("WARNING: failed to read coverage data: {e:#}");
+            None
+        }
+    }
+}
+
+fn collect_v8(dir: &Path, action_file: &Path) -> Result<Option<FileCoverage>> {
+    let source = std::fs::read_to_string(action_file)
+        .with_context(|| format!("Failed to read action source {:?}", action_file))?;
+
+    let mut found: Option<FileCoverage> = None;
+
+    for entry in std::fs::read_dir(dir).context("Failed to read V8 coverage directory")? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+
+        let raw = std::fs::read_to_string(&path)?;
+        let parsed: V8Dump = serde_json::from_str(&raw)
+            .with_context(|| format!("Invalid V8 coverage file {:?}", path))?;
+
+        for script in parsed.result {
+            let is_action = reqwest::Url::parse(&script.url)
+                .ok()
+                .and_then(|u| u.to_file_path().ok())
+                .is_some_and(|p| p == action_file);
+            if !is_action {
+                continue;
+            }
+
+            let cov = from_v8(&source, &script.functions);
+            match &mut found {
+                Some(existing) => existing.merge(cov),
+                None => found = Some(cov),
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+#[derive(Debug, Deserialize)]
+struct V8Dump {
+    result: Vec<V8Script>,
+}
+
+#[derive(Debug, Deserialize)]
+struct V8Script {
+    url: String,
+    functions: Vec<V8Function>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V8Function {
+    function_name: String,
+    ranges: Vec<V8Range>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V8Range {
+    start_offset: usize,
+    end_offset: usize,
+    count: u64,
+}
+
+fn from_v8(source: &str, functions: &[V8Function]) -> FileCoverage {
+    let len = source.encode_utf16().count();
+    let mut counts = vec![0u64; len];
+    let mut blocks = BTreeSet::new();
+    let mut fn_starts = BTreeMap::new();
+
+    // Nested ranges override their parents: apply the widest first
+    let mut ranges = Vec::new();
+    for f in functions {
+        for (i, r) in f.ranges.iter().enumerate() {
+            ranges.push(r);
+            if i == 0 {
+                let top_level = r.start_offset == 0 && r.end_offset >= len;
+                if !top_level {
+                    let name = if f.function_name.is_empty() {
+                        format!("(anonymous_{})", r.start_offset)
+                    } else {
+                        f.function_name.clone()
+                    };
+                    fn_starts.insert(r.start_offset, name);
+                }
+            } else {
+                blocks.insert(r.start_offset);
+            }
+        }
+    }
+    ranges.sort_by_key(|r| std::cmp::Reverse(r.end_offset.saturating_sub(r.start_offset)));
+
+    for r in ranges {
+        let end = r.end_offset.min(len);
+        if r.start_offset < end {
+            counts[r.start_offset..end].fill(r.count);
+        }
+    }
+
+    FileCoverage::V8 {
+        source: source.to_string(),
+        counts,
+        blocks,
+        functions: fn_starts,
+    }
+}
+
+fn collect_python(report: &Path) -> Result<Option<FileCoverage>> {
+    if !report.exists() {
+        return Ok(None);
+    }
+
+    let raw =
+        std::fs::read_to_string(report).with_context(|| format!("Failed to read {:?}", report))?;
+    let parsed: Value = serde_json::from_str(&raw).context("Invalid coverage.py JSON report")?;
+
+    // The shim restricts measurement to the action file
+    let Some(file) = parsed
+        .get("files")
+        .and_then(|f| f.as_object())
+        .and_then(|f| f.values().next())
+    else {
+        return Ok(None);
+    };
+
+    let numbers = |key: &str| -> Vec<u32> {
+        file.get(key)
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|n| n.as_u64())
+                    .map(|n| n as u32)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let arcs = |key: &str| -> Vec<(u32, i64)> {
+        file.get(key)
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|pair| {
+                        let from = pair.get(0)?.as_i64()?;
+                        let to = pair.get(1)?.as_i64()?;
+                        Some((u32::try_from(from).ok()?, to))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut lines = BTreeMap::new();
+    for l in numbers("missing_lines") {
+        lines.insert(l, 0);
+    }
+    for l in numbers("executed_lines") {
+        lines.insert(l, 1);
+    }
+
+    let mut branches = BTreeMap::new();
+    for a in arcs("missing_branches") {
+        branches.insert(a, 0);
+    }
+    for a in arcs("executed_branches") {
+        branches.insert(a, 1);
+    }
+
+    Ok(Some(FileCoverage::Lines { lines, branches }))
+}
+
+/* ---------------- merging ---------------- */
+
+impl FileCoverage {
+    /// Merge another run of the same file into this one.
+    pub fn merge(&mut self, other: FileCoverage) {
+        match (self, other) {
+            (
+                FileCoverage::V8 {
+                    counts,
+                    blocks,
+                    functions,
+                    ..
+                },
+                FileCoverage::V8 {
+                    counts: other_counts,
+                    blocks: other_blocks,
+                    functions: other_functions,
+                    ..
+                },
+            ) => {
+                if counts.len() == other_counts.len() {
+                    for (c, o) in counts.iter_mut().zip(other_counts) {
+                        *c += o;
+                    }
+                }
+                blocks.extend(other_blocks);
+                functions.extend(other_functions);
+            }
+            (
+                FileCoverage::Lines { lines, branches },
+                FileCoverage::Lines {
+                    lines: other_lines,
+                    branches: other_branches,
+                },
+            ) => {
+                for (l, hits) in other_lines {
+                    *lines.entry(l).or_insert(0) += hits;
+                }
+                for (b, hits) in other_branches {
+                    *branches.entry(b).or_insert(0) += hits;
+                }
+            }
+            // The language of a file never changes between runs
+            _ => {}
+        }
+    }
+
+    fn record(&self) -> Record {
+        match self {
+            FileCoverage::V8 {
+                source,
+                counts,
+                blocks,
+                functions,
+            } => v8_record(source, counts, blocks, functions),
+            FileCoverage::Lines { lines, branches } => {
+                let mut by_line: BTreeMap<u32, Vec<u64>> = BTreeMap::new();
+                for ((line, _), hits) in branches {
+                    by_line.entry(*line).or_default().push(*hits);
+                }
+                Record {
+                    lines: lines.clone(),
+                    branches: by_line,
+                    functions: Vec::new(),
+                }
+            }
+        }
+    }
+}
+
+fn v8_record(
+    source: &str,
+    counts: &[u64],
+    blocks: &BTreeSet<usize>,
+    functions: &BTreeMap<usize, String>,
+) -> Record {
+    // UTF-16 offset of the start of each line
+    let mut line_starts = vec![0usize];
+    let mut offset = 0usize;
+    for ch in source.chars() {
+        offset += ch.len_utf16();
+        if ch == '\n' {
+            line_starts.push(offset);
+        }
+    }
+    let line_of = |off: usize| match line_starts.binary_search(&off) {
+        Ok(i) => i as u32 + 1,
+        Err(i) => i as u32,
+    };
+    let count_at = |off: usize| counts.get(off).copied().unwrap_or(0);
+
+    let mut record = Record::default();
+
+    let mut line_offset = 0usize;
+    for (idx, text) in source.split('\n').enumerate() {
+        let trimmed = text.trim_start();
+        let indent: usize = text[..text.len() - trimmed.len()]
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+        let code = trimmed.trim_end();
+
+        // Blank, comment and closing-bracket-only lines are not statements
+        let is_code = !code
+            .chars()
+            .all(|c| matches!(c, '}' | ')' | ']' | ';' | ','))
+            && !code.starts_with("//")
+            && !code.starts_with("/*")
+            && !code.starts_with('*');
+        if is_code {
+            record
+                .lines
+                .insert(idx as u32 + 1, count_at(line_offset + indent));
+        }
+
+        line_offset += text.chars().map(char::len_utf16).sum::<usize>() + 1;
+    }
+
+    for off in blocks {
+        record
+            .branches
+            .entry(line_of(*off))
+            .or_default()
+            .push(count_at(*off));
+    }
+
+    for (off, name) in functions {
+        record
+            .functions
+            .push((line_of(*off), name.clone(), count_at(*off)));
+    }
+
+    record
+}
+
+impl CoverageMap {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Add one run's coverage of `file`.
+    pub fn add(&mut self, file: &Path, cov: FileCoverage) {
+        match self.files.get_mut(file) {
+            Some(existing) => existing.merge(cov),
+            None => {
+                self.files.insert(file.to_path_buf(), cov);
+            }
+        }
+    }
+
+    /// Merge every file of another map.
+    pub fn extend(&mut self, other: CoverageMap) {
+        for (file, cov) in other.files {
+            self.add(&file, cov);
+        }
+    }
+
+    /// Per-file summaries plus the overall total.
+    pub fn summarize(&self) -> (Vec<(PathBuf, CoverageSummary)>, CoverageSummary) {
+        let mut total = CoverageSummary::default();
+        let mut per_file = Vec::new();
+
+        for (file, cov) in &self.files {
+            let s = summarize_record(&cov.record());
+            total.lines.add(s.lines);
+            total.branches.add(s.branches);
+            total.functions.add(s.functions);
+            per_file.push((file.clone(), s));
+        }
+
+        (per_file, total)
+    }
+
+    /// Write all files as one lcov tracefile.
+    pub fn write_lcov(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                ensure_dir(parent)?;
+            }
+        }
+
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let mut out = String::new();
+
+        for (file, cov) in &self.files {
+            let record = cov.record();
+            let shown = file.strip_prefix(&cwd).unwrap_or(file);
+
+            out.push_str("TN:\n");
+            out.push_str(&format!("SF:{}\n", shown.display()));
+
+            for (line, name, _) in &record.functions {
+                out.push_str(&format!("FN:{},{}\n", line, name));
+            }
+            for (_, name, hits) in &record.functions {
+                out.push_str(&format!("FNDA:{},{}\n", hits, name));
+            }
+            out.push_str(&format!("FNF:{}\n", record.functions.len()));
+            out.push_str(&format!(
+                "FNH:{}\n",
+                record.functions.iter().filter(|f| f.2 > 0).count()
+            ));
+
+            let mut brf = 0;
+            let mut brh = 0;
+            for (line, hits) in &record.branches {
+                for (i, h) in hits.iter().enumerate() {
+                    out.push_str(&format!("BRDA:{},0,{},{}\n", line, i, h));
+                    brf += 1;
+                    if *h > 0 {
+                        brh += 1;
+                    }
+                }
+            }
+            out.push_str(&format!("BRF:{}\nBRH:{}\n", brf, brh));
+
+            for (line, hits) in &record.lines {
+                out.push_str(&format!("DA:{},{}\n", line, hits));
+            }
+            out.push_str(&format!("LF:{}\n", record.lines.len()));
+            out.push_str(&format!(
+                "LH:{}\n",
+                record.lines.values().filter(|h| **h > 0).count()
+            ));
+            out.push_str("end_of_record\n");
+        }
+
+        std::fs::write(path, out).with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(())
+    }
+}
+
+fn summarize_record(record: &Record) -> CoverageSummary {
+    let ratio = |hits: &mut dyn Iterator<Item = u64>| {
+        let mut r = Ratio::default();
+        for h in hits {
+            r.total += 1;
+            if h > 0 {
+                r.hit += 1;
+            }
+        }
+        r
+    };
+
+    CoverageSummary {
+        lines: ratio(&mut record.lines.values().copied()),
+        branches: ratio(&mut record.branches.values().flatten().copied()),
+        functions: ratio(&mut record.functions.iter().map(|f| f.2)),
+    }
+}
+
+/* ---------------- rendering ---------------- */
+
+/// Terminal summary table.
+pub fn format_summary(per_file: &[(PathBuf, CoverageSummary)], total: &CoverageSummary) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let pct = |r: &Ratio| match r.pct() {
+        Some(p) => format!("{:>6.1}% ({}/{})", p, r.hit, r.total),
+        None => "     n/a".to_string(),
+    };
+
+    let mut out = String::from("coverage:\n");
+    for (file, s) in per_file {
+        let shown = file.strip_prefix(&cwd).unwrap_or(file);
+        out.push_str(&format!("  {}\n", shown.display()));
+        out.push_str(&format!("    lines:     {}\n", pct(&s.lines)));
+        out.push_str(&format!("    branches:  {}\n", pct(&s.branches)));
+        out.push_str(&format!("    functions: {}\n", pct(&s.functions)));
+    }
+    if per_file.len() > 1 {
+        out.push_str(&format!("  total lines: {}\n", pct(&total.lines)));
+    }
+
+    out.trim_end().to_string()
+}
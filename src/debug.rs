@@ -0,0 +1,115 @@
+// src/debug.rs
+
+//! Debugger attach mode (`hsemulate run --debug`).
+//!
+//! The runtime is started paused, listening for a debugger on loopback:
+//! - Node: `--inspect-brk` (Chrome DevTools / VS Code "Attach")
+//! - Python: `debugpy --listen … --wait-for-client` (VS Code "Remote Attach")
+//!
+//! Actions are always loaded from their real path, so breakpoints set in the
+//! action file bind as soon as it is imported. In debug mode the shim is
+//! written to a stable path (`.hsemulator/debug/`) instead of a temp dir,
+//! so it can be listed in `skipFiles` and keeps the same name across runs.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command as TokioCommand;
+
+use crate::limits::Language;
+use crate::util::ensure_dir;
+
+/// Default inspector port for Node.
+pub const NODE_DEFAULT_PORT: u16 = 9229;
+
+/// Default debugpy port for Python.
+pub const PYTHON_DEFAULT_PORT: u16 = 5678;
+
+/// Where shims are written in debug mode.
+const DEBUG_SHIM_DIR: &str = ".hsemulator/debug";
+
+/// Debugger settings for one `run --debug`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DebugOptions {
+    /// Listen port (defaults per language)
+    pub port: Option<u16>,
+}
+
+impl DebugOptions {
+    pub fn port_for(&self, language: Language) -> u16 {
+        self.port.unwrap_or(match language {
+            Language::Node => NODE_DEFAULT_PORT,
+            Language::Python => PYTHON_DEFAULT_PORT,
+        })
+    }
+}
+
+/// Stable directory for the shim while debugging.
+pub fn shim_dir() -> Result<PathBuf> {
+    let dir = Path::new(DEBUG_SHIM_DIR);
+    ensure_dir(dir)?;
+    dir.canonicalize()
+        .with_context(|| format!("Failed to resolve {}", DEBUG_SHIM_DIR))
+}
+
+/// Add the debugger arguments to `cmd`.
+///
+/// Must be called before the shim path is added, as these are runtime
+/// options rather than script arguments.
+pub fn apply(language: Language, runtime: &str, port: u16, cmd: &mut TokioCommand) -> Result<()> {
+    let listen = format!("127.0.0.1:{}", port);
+
+    match language {
+        Language::Node => {
+            cmd.arg(format!("--inspect-brk={}", listen));
+        }
+        Language::Python => {
+            ensure_debugpy(runtime)?;
+            cmd.args(["-m", "debugpy", "--listen", &listen, "--wait-for-client"]);
+        }
+    }
+
+    // Lets the shim pause once more right before the action's main()
+    cmd.env("HSE_DEBUG", "1");
+    Ok(())
+}
+
+/// Tell the user where to attach.
+pub fn announce(language: Language, port: u16, action_file: &Path) {
+    eprintln!(
+        "Debugger listening on 127.0.0.1:{} ({})",
+        port,
+        language.as_str()
+    );
+    match language {
+        Language::Node => eprintln!(
+            "Attach with chrome://inspect or VS Code \"Attach to Node Process\" (port {})",
+            port
+        ),
+        Language::Python => eprintln!(
+            "Attach with VS Code \"Python Debugger: Remote Attach\" (host 127.0.0.1, port {})",
+            port
+        ),
+    }
+    eprintln!(
+        "Waiting for a debugger… breakpoints in {} bind once it loads; execution pauses again before main()",
+        action_file.display()
+    );
+}
+
+fn ensure_debugpy(runtime: &str) -> Result<()> {
+    let status = std::process::Command::new(runtime)
+        .args(["-c", "import debugpy"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .with_context(|| format!("Failed to run {}", runtime))?;
+
+    if !status.success() {
+        bail!(
+            "debugpy is not installed for '{}'\n\nInstall it with:\n  {} -m pip install debugpy",
+            runtime,
+            runtime
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,186 @@
+// src/diff.rs
+
+//! Line diffs for `promote --diff`.
+//!
+//! Edits are found with Myers' algorithm and rendered as a unified diff
+//! (`git diff` style hunks with 3 lines of context), optionally coloured.
+
+/// Lines of unchanged context around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// One line of the edit script, with its 0-based positions in both inputs.
+#[derive(Debug)]
+struct Line<'a> {
+    edit: Edit,
+    old: usize,
+    new: usize,
+    text: &'a str,
+}
+
+/// Unified diff from `old` to `new`; empty if they have the same lines.
+pub fn unified(old: &str, new: &str, old_label: &str, new_label: &str, use_color: bool) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let script = edit_script(&a, &b);
+
+    if script.iter().all(|l| l.edit == Edit::Equal) {
+        return String::new();
+    }
+
+    let mut out = String::new();
+    out.push_str(&paint(&format!("--- {}", old_label), "1", use_color));
+    out.push('\n');
+    out.push_str(&paint(&format!("+++ {}", new_label), "1", use_color));
+    out.push('\n');
+
+    for (start, end) in hunks(&script) {
+        let lines = &script[start..end];
+        let old_len = lines.iter().filter(|l| l.edit != Edit::Insert).count();
+        let new_len = lines.iter().filter(|l| l.edit != Edit::Delete).count();
+        let header = format!(
+            "@@ -{} +{} @@",
+            range(lines[0].old, old_len),
+            range(lines[0].new, new_len)
+        );
+        out.push_str(&paint(&header, "36", use_color));
+        out.push('\n');
+
+        for line in lines {
+            let rendered = match line.edit {
+                Edit::Equal => format!(" {}", line.text),
+                Edit::Delete => paint(&format!("-{}", line.text), "31", use_color),
+                Edit::Insert => paint(&format!("+{}", line.text), "32", use_color),
+            };
+            out.push_str(&rendered);
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+/// Hunk range as `start,len` (1-based; the line before the hunk when empty).
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Script index ranges of each hunk, changes plus surrounding context.
+fn hunks(script: &[Line<'_>]) -> Vec<(usize, usize)> {
+    let mut out: Vec<(usize, usize)> = Vec::new();
+
+    for (i, line) in script.iter().enumerate() {
+        if line.edit == Edit::Equal {
+            continue;
+        }
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + 1 + CONTEXT).min(script.len());
+        match out.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => out.push((start, end)),
+        }
+    }
+
+    out
+}
+
+/* ---------------- Myers ---------------- */
+
+/// Shortest edit script turning `a` into `b`.
+fn edit_script<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Line<'a>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    let offset = max;
+    let idx = |k: isize| (k + offset) as usize;
+
+    // Furthest x reached on each diagonal k = x - y, saved before every round
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk back from the end through the saved rounds
+    let mut script = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[idx(prev_k)];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            script.push(Line {
+                edit: Edit::Equal,
+                old: x as usize,
+                new: y as usize,
+                text: a[x as usize],
+            });
+        }
+        if d > 0 {
+            if x == prev_x {
+                script.push(Line {
+                    edit: Edit::Insert,
+                    old: x as usize,
+                    new: prev_y as usize,
+                    text: b[prev_y as usize],
+                });
+            } else {
+                script.push(Line {
+                    edit: Edit::Delete,
+                    old: prev_x as usize,
+                    new: y as usize,
+                    text: a[prev_x as usize],
+                });
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    script.reverse();
+    script
+}
+
+fn paint(text: &str, color: &str, use_color: bool) -> String {
+    if use_color {
+        format!("\x1b[{}m{}\x1b[0m", color, text)
+    } else {
+        text.to_string()
+    }
+}
@@ -12,6 +12,7 @@ pub enum ExecutionEventKind {
     ValidationStarted,
     Stdout,
     Stderr,
+    NetworkBlocked,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -42,6 +43,15 @@ pub fn stdout_event(execution_id: ExecutionId, message: String) -> ExecutionEven
     }
 }
 
+pub fn network_blocked_event(execution_id: ExecutionId, destination: String) -> ExecutionEvent {
+    ExecutionEvent {
+        execution_id,
+        kind: ExecutionEventKind::NetworkBlocked,
+        timestamp: SystemTime::now(),
+        message: Some(destination),
+    }
+}
+
 pub fn stderr_event(execution_id: ExecutionId, message: String) -> ExecutionEvent {
     ExecutionEvent {
         execution_id,
@@ -2,18 +2,30 @@ use anyhow::{bail, Context, Result};
 use serde_json::Value;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::time::{Duration, Instant, SystemTime};
+use std::time::{Instant, SystemTime};
 use tempfile::tempdir;
-use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command as TokioCommand;
 
-use crate::config::Config;
-use crate::engine::events::{ExecutionEvent, ExecutionEventKind};
+use crate::bundle;
+use crate::config::{Config, RuntimeMode};
+use crate::engine::events::{
+    network_blocked_event, stderr_event, stdout_event, ExecutionEvent, ExecutionEventKind,
+};
 use crate::engine::sink::EventSink;
 use crate::engine::ExecutionResult;
 use crate::execution_id::ExecutionId;
-use crate::metrics::{InvocationMetrics, MemoryTracker};
+use crate::http_capture::{take_requests, CapturedRequest};
+use crate::limits::{
+    apply_memory_limit, is_memory_error, killed_by_memory_limit, memory_exceeded_message,
+    memory_exceeded_output, Language,
+};
+use crate::logs::{LogLine, LogStream};
+use crate::metrics::{InvocationMetrics, ResourceMonitor};
+use crate::network::{self, NetworkSandbox};
+use crate::protocol::{self, Channel};
 use crate::shim::{node_shim, python_shim};
+use crate::typescript;
+use crate::worker;
 
 /* ============================================================
    Public entrypoint
@@ -43,6 +55,10 @@ pub async fn execute_action(
     let mut max_duration_ms: Option<u128> = None;
     let mut max_memory_kb: Option<u64> = None;
     let mut output_fields: Option<serde_json::Map<String, Value>> = None;
+    let mut requests: Vec<CapturedRequest> = Vec::new();
+
+    // Compile TypeScript once, not once per fixture and repeat
+    let compiled = typescript::Compiled::for_action(&cfg.runtime.typescript, &action_file)?;
 
     for fixture in &cfg.fixtures {
         let event: Value = serde_json::from_str(
@@ -56,8 +72,16 @@ pub async fn execute_action(
         for _ in 0..repeat {
             runs += 1;
 
-            let (output, metrics) =
-                invoke_once(&cfg, &execution_id, &action_file, &event, sink).await?;
+            let (mut output, metrics) = invoke_once(
+                &cfg,
+                &execution_id,
+                &action_file,
+                compiled.as_ref(),
+                &event,
+                sink,
+            )
+            .await?;
+            requests.extend(take_requests(&mut output));
 
             if let Some(fields) = output.get("outputFields").and_then(|v| v.as_object()) {
                 output_fields = Some(fields.clone());
@@ -69,12 +93,21 @@ pub async fn execute_action(
                     .unwrap_or(metrics.duration_ms),
             );
 
-            if let Some(mem) = metrics.max_rss_kb {
+            if let Some(mem) = metrics.peak_memory_kb() {
                 max_memory_kb = Some(max_memory_kb.map(|m| m.max(mem)).unwrap_or(mem));
             }
 
+            for destination in &metrics.network_blocked {
+                failures.push(network::blocked_message(destination));
+            }
+
             if !output.get("ok").and_then(|v| v.as_bool()).unwrap_or(false) {
-                failures.push("Action returned ok=false".to_string());
+                match cfg.budgets.as_ref().and_then(|b| b.enforced_memory_mb()) {
+                    Some(mb) if is_memory_error(&output) => {
+                        failures.push(memory_exceeded_message(mb))
+                    }
+                    _ => failures.push("Action returned ok=false".to_string()),
+                }
             }
         }
     }
@@ -91,7 +124,8 @@ pub async fn execute_action(
         max_duration_ms,
         max_memory_kb,
         snapshots_ok: true,
-        outputFields: output_fields,
+        output_fields,
+        requests,
     })
 }
 
@@ -103,33 +137,63 @@ async fn invoke_once(
     cfg: &Config,
     execution_id: &ExecutionId,
     action_file: &Path,
+    compiled: Option<&typescript::Compiled>,
     event: &Value,
     sink: &mut dyn EventSink,
 ) -> Result<(Value, InvocationMetrics)> {
-    let tmp = tempdir().context("Failed to create temp dir")?;
-
-    let event_path = tmp.path().join("event.json");
-    std::fs::write(&event_path, serde_json::to_vec_pretty(event)?)
-        .context("Failed to write event.json")?;
-
     let ext = action_file
         .extension()
         .and_then(|s| s.to_str())
         .unwrap_or("")
         .to_lowercase();
 
-    let (runtime, shim_name, shim_code) = match ext.as_str() {
-        "py" => (&cfg.runtime.python, "hs_python_runner.py", python_shim()),
-        "js" | "mjs" | "cjs" => (&cfg.runtime.node, "hs_node_runner.mjs", node_shim()),
+    let (language, runtime, shim_name, shim_code) = match ext.as_str() {
+        "py" => (
+            Language::Python,
+            &cfg.runtime.python,
+            "hs_python_runner.py",
+            python_shim(),
+        ),
+        "js" | "mjs" | "cjs" | "ts" => (
+            Language::Node,
+            &cfg.runtime.node,
+            "hs_node_runner.mjs",
+            node_shim(),
+        ),
         _ => bail!("Unsupported action file extension: {}", ext),
     };
 
+    let is_typescript = typescript::is_typescript(action_file);
+    let bundle = match language {
+        Language::Node if !is_typescript => bundle::build(action_file)?,
+        _ => None,
+    };
+    let is_built = is_typescript || bundle.is_some();
+    if cfg.runtime.mode == RuntimeMode::Warm && !is_built && worker::supported(cfg) {
+        return invoke_warm(cfg, execution_id, language, runtime, action_file, event, sink).await;
+    }
+
+    let tmp = tempdir().context("Failed to create temp dir")?;
+
+    let event_path = tmp.path().join("event.json");
+    std::fs::write(&event_path, serde_json::to_vec_pretty(event)?)
+        .context("Failed to write event.json")?;
+
     let shim_path = tmp.path().join(shim_name);
     std::fs::write(&shim_path, shim_code).context("Failed to write runner shim")?;
 
     let mut cmd = TokioCommand::new(runtime);
+    let run_file = if let Some(compiled) = compiled {
+        typescript::prepare(compiled, action_file, &mut cmd)?
+    } else if let Some(bundle) = &bundle {
+        let out_dir = tmp.path().join("bundle");
+        std::fs::create_dir_all(&out_dir).context("Failed to create bundle dir")?;
+        bundle::prepare(bundle, action_file, &out_dir, &mut cmd)?
+    } else {
+        action_file.to_path_buf()
+    };
     cmd.arg(&shim_path)
-        .arg(action_file)
+        .arg(&run_file)
         .arg(&event_path)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
@@ -138,82 +202,125 @@ async fn invoke_once(
         cmd.env(k, v);
     }
 
+    let sandbox = match &cfg.network {
+        Some(network) => Some(NetworkSandbox::start(network).await?),
+        None => None,
+    };
+    if let Some(sandbox) = &sandbox {
+        sandbox.apply(&mut cmd);
+    }
+
+    let mut monitor = ResourceMonitor::new(&cfg.metrics);
+    let memory_limit_mb = apply_memory_limit(cfg, language, &mut cmd, &mut monitor);
+    monitor.prepare(&mut cmd)?;
+
+    let channel = Channel::attach(&mut cmd)?;
+
     let start = Instant::now();
     let mut child = cmd.spawn().context("Failed to spawn runtime")?;
 
     let pid = child.id().context("Failed to get child PID")?;
-    let mem = MemoryTracker::start(pid, Duration::from_millis(20));
-
-    let mut stdout_lines = BufReader::new(child.stdout.take().unwrap()).lines();
-    let mut stderr_lines = BufReader::new(child.stderr.take().unwrap()).lines();
-
-    let mut final_stdout = String::new();
+    monitor.start(pid);
+
+    let stdout = child
+        .stdout
+        .take()
+        .context("Failed to capture runtime stdout")?;
+    let stderr = child
+        .stderr
+        .take()
+        .context("Failed to capture runtime stderr")?;
+
+    let transcript = protocol::collect(channel, stdout, stderr, |log| {
+        emit_log(sink, execution_id, log)
+    })
+    .await?;
 
-    loop {
-        tokio::select! {
-            line = stdout_lines.next_line() => {
-                match line? {
-                    Some(l) => final_stdout.push_str(&l),
-                    None => break,
-                }
-            }
-            line = stderr_lines.next_line() => {
-    if let Some(l) = line? {
-        let msg = l.trim_start();
-
-        if let Some(rest) = msg.strip_prefix("__HSE_ERR__ ") {
-            sink.emit(ExecutionEvent {
-                execution_id: execution_id.clone(),
-                kind: ExecutionEventKind::Stderr,
-                timestamp: SystemTime::now(),
-                message: Some(rest.to_string()),
-            });
-        } else if let Some(rest) = msg.strip_prefix("__HSE_LOG__ ") {
-            sink.emit(ExecutionEvent {
-                execution_id: execution_id.clone(),
-                kind: ExecutionEventKind::Stdout,
-                timestamp: SystemTime::now(),
-                message: Some(rest.to_string()),
-            });
-        } else {
-            // Fallback: treat untagged stderr as INFO
-            sink.emit(ExecutionEvent {
-                execution_id: execution_id.clone(),
-                kind: ExecutionEventKind::Stdout,
-                timestamp: SystemTime::now(),
-                message: Some(msg.to_string()),
-            });
+    // Collect accounting before the child is reaped
+    monitor.wait_for_exit().await;
+    let duration_ms = start.elapsed().as_millis();
+    let mut metrics = monitor.finish(duration_ms);
+    if let Some(sandbox) = &sandbox {
+        metrics.network_blocked = sandbox.blocked();
+        for destination in &metrics.network_blocked {
+            sink.emit(network_blocked_event(
+                execution_id.clone(),
+                destination.clone(),
+            ));
         }
     }
-}
+    metrics.phases = transcript.phases.clone();
+
+    let status = child.wait().await?;
 
+    if let Some(mb) = memory_limit_mb {
+        if !transcript.is_complete() && killed_by_memory_limit(&metrics, &transcript.logs) {
+            return Ok((memory_exceeded_output(language, mb), metrics));
         }
     }
 
-    let status = child.wait().await?;
-    let duration_ms = start.elapsed().as_millis();
-    let max_rss_kb = mem.stop_and_take();
-
     if !status.success() {
         bail!("Runtime exited with non-zero status");
     }
 
-    let parsed: Value =
-        serde_json::from_str(final_stdout.trim()).context("Shim did not emit valid JSON")?;
+    let output = transcript.output().context("Shim did not emit a result")?;
 
-    Ok((
-        parsed,
-        InvocationMetrics {
-            duration_ms,
-            max_rss_kb,
-        },
-    ))
+    Ok((output, metrics))
+}
+
+/// Same as the cold path above, served by a warm worker.
+async fn invoke_warm(
+    cfg: &Config,
+    execution_id: &ExecutionId,
+    language: Language,
+    runtime: &str,
+    action_file: &Path,
+    event: &Value,
+    sink: &mut dyn EventSink,
+) -> Result<(Value, InvocationMetrics)> {
+    let served = worker::invoke(cfg, language, runtime, action_file, event, false).await?;
+
+    for log in &served.logs {
+        emit_log(sink, execution_id, log);
+    }
+    for destination in &served.metrics.network_blocked {
+        sink.emit(network_blocked_event(
+            execution_id.clone(),
+            destination.clone(),
+        ));
+    }
+
+    // Cold shims exit non-zero on runtime errors (e.g. the action failed to load)
+    let is_runtime_error = served
+        .output
+        .pointer("/error/type")
+        .and_then(|t| t.as_str())
+        == Some("runtime");
+    if is_runtime_error {
+        let message = served
+            .output
+            .pointer("/error/message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("unknown error");
+        bail!("Runtime error: {}", message);
+    }
+
+    Ok((served.output, served.metrics))
 }
 
 /* ============================================================
    Helpers
 ============================================================ */
 
+/// Error-level lines go to the STDERR event stream, everything else to STDOUT.
+fn emit_log(sink: &mut dyn EventSink, execution_id: &ExecutionId, log: &LogLine) {
+    let message = log.message.clone();
+    match log.stream {
+        LogStream::Error => sink.emit(stderr_event(execution_id.clone(), message)),
+        LogStream::Log => sink.emit(stdout_event(execution_id.clone(), message)),
+    }
+}
+
 fn event(id: ExecutionId, kind: ExecutionEventKind) -> ExecutionEvent {
     ExecutionEvent {
         execution_id: id,
@@ -26,7 +26,12 @@ pub struct ExecutionResult {
     pub max_duration_ms: Option<u128>,
     pub max_memory_kb: Option<u64>,
     pub snapshots_ok: bool,
-    pub outputFields: Option<serde_json::Map<String, Value>>,
+    #[serde(rename = "outputFields")]
+    pub output_fields: Option<serde_json::Map<String, Value>>,
+
+    /// Outbound HTTP requests made across all runs, in order
+    #[serde(default)]
+    pub requests: Vec<crate::http_capture::CapturedRequest>,
 }
 
 /* ---------------- validation ---------------- */
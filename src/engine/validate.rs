@@ -2,8 +2,10 @@
 use anyhow::Result;
 use std::path::Path;
 
+use crate::bundle;
 use crate::config::Config;
 use crate::engine::ValidationResult;
+use crate::typescript;
 use crate::util::read_to_string;
 
 pub fn validate_config(cfg: &Config) -> Result<ValidationResult> {
@@ -47,13 +49,21 @@ fn validate_action(cfg: &Config, result: &mut ValidationResult) -> Result<()> {
 
     let ext = entry.extension().and_then(|s| s.to_str()).unwrap_or("");
 
-    if ext != "js" && ext != "mjs" && ext != "cjs" && ext != "py" {
+    if !matches!(ext, "js" | "mjs" | "cjs" | "ts" | "py") {
         result.push_error(
             "ACTION_UNSUPPORTED_TYPE",
             format!("Unsupported action file extension: .{}", ext),
         );
     }
 
+    if ext == "ts" {
+        if let Err(e) = typescript::check(&cfg.runtime.typescript, entry) {
+            result.push_error("ACTION_TYPE_ERRORS", format!("{:#}", e));
+        }
+    } else if let Err(e) = bundle::check(entry) {
+        result.push_error("ACTION_BUNDLE_FAILED", format!("{:#}", e));
+    }
+
     Ok(())
 }
 
@@ -112,15 +122,11 @@ fn validate_runtime(cfg: &Config, result: &mut ValidationResult) -> Result<()> {
         .unwrap_or("");
 
     match ext {
-        "py" => {
-            if cfg.runtime.python.trim().is_empty() {
-                result.push_error("RUNTIME_PYTHON_MISSING", "Python runtime is not configured");
-            }
+        "py" if cfg.runtime.python.trim().is_empty() => {
+            result.push_error("RUNTIME_PYTHON_MISSING", "Python runtime is not configured");
         }
-        "js" | "mjs" | "cjs" => {
-            if cfg.runtime.node.trim().is_empty() {
-                result.push_error("RUNTIME_NODE_MISSING", "Node runtime is not configured");
-            }
+        "js" | "mjs" | "cjs" | "ts" if cfg.runtime.node.trim().is_empty() => {
+            result.push_error("RUNTIME_NODE_MISSING", "Node runtime is not configured");
         }
         _ => {}
     }
@@ -25,6 +25,14 @@ pub fn validate_inline_config(cfg: &InlineConfig) -> Result<()> {
                 );
             }
         }
+        InlineLanguage::Ts => {
+            if ext != "ts" {
+                anyhow::bail!(
+                    "TypeScript action must use .ts (got '{}')",
+                    cfg.action.entry
+                );
+            }
+        }
         InlineLanguage::Python => {
             if ext != "py" {
                 anyhow::bail!(
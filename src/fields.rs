@@ -0,0 +1,245 @@
+// src/fields.rs
+
+//! Secrets and input/output fields of a custom code action.
+//!
+//! Declared under `action` in config.yaml and written into the HubSpot
+//! action by `promote`:
+//! - `secrets`: `[NAME, ...]` → `secretNames`
+//! - `inputs`:  `{field: property}` → `inputFields` (object property values)
+//! - `outputs`: `{field: type}` → `outputFields`
+//!
+//! Each is only synced when declared; an absent key leaves the deployed
+//! action's value alone. Values are compared in a canonical line form (one
+//! field per line, sorted), which is also what `promote --diff` shows.
+
+use crate::config::{Action, OutputType};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::BTreeSet;
+
+/// Check the declared secrets and fields (config.yaml validation).
+pub fn validate(action: &Action) -> Result<()> {
+    if let Some(secrets) = &action.secrets {
+        let mut seen = BTreeSet::new();
+        for name in secrets {
+            if name.trim().is_empty() || name.chars().any(char::is_whitespace) {
+                bail!("action.secrets: '{}' is not a valid secret name", name);
+            }
+            if !seen.insert(name) {
+                bail!("action.secrets: '{}' is listed twice", name);
+            }
+        }
+    }
+
+    for (name, property) in action.inputs.iter().flatten() {
+        if name.trim().is_empty() || property.trim().is_empty() {
+            bail!("action.inputs: field names and properties must not be empty");
+        }
+    }
+
+    for name in action.outputs.iter().flat_map(|o| o.keys()) {
+        if name.trim().is_empty() {
+            bail!("action.outputs: field names must not be empty");
+        }
+    }
+
+    Ok(())
+}
+
+/// The synced parts of an action, as HubSpot JSON.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ActionFields {
+    #[serde(rename = "secretNames")]
+    secrets: Option<Vec<String>>,
+    #[serde(rename = "inputFields")]
+    input_fields: Option<Vec<JsonValue>>,
+    #[serde(rename = "outputFields")]
+    output_fields: Option<Vec<JsonValue>>,
+}
+
+/// One synced key whose deployed value differs from the declared one.
+pub struct FieldChange {
+    /// HubSpot key, e.g. `secretNames`
+    pub key: &'static str,
+    /// Canonical lines, deployed
+    pub deployed: String,
+    /// Canonical lines, declared
+    pub declared: String,
+}
+
+impl ActionFields {
+    pub fn from_action(action: &Action) -> Self {
+        let secrets = action.secrets.as_ref().map(|secrets| {
+            let mut secrets = secrets.clone();
+            secrets.sort();
+            secrets
+        });
+
+        // BTreeMaps, so already sorted by field name
+        let input_fields = action.inputs.as_ref().map(|inputs| {
+            inputs
+                .iter()
+                .map(|(name, property)| {
+                    json!({
+                        "name": name,
+                        "value": { "type": "OBJECT_PROPERTY", "propertyName": property },
+                    })
+                })
+                .collect()
+        });
+
+        let output_fields = action.outputs.as_ref().map(|outputs| {
+            outputs
+                .iter()
+                .map(|(name, ty)| json!({ "name": name, "type": hubspot_type(*ty) }))
+                .collect()
+        });
+
+        ActionFields {
+            secrets,
+            input_fields,
+            output_fields,
+        }
+    }
+
+    /// Every synced key of a deployed HubSpot action (absent keys as empty),
+    /// so that applying it restores the action as it was.
+    pub fn from_hubspot(action: &JsonValue) -> Self {
+        let array = |key: &str| {
+            action
+                .get(key)
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let mut secrets: Vec<String> = array("secretNames")
+            .iter()
+            .filter_map(|n| n.as_str().map(|s| s.to_string()))
+            .collect();
+        secrets.sort();
+
+        ActionFields {
+            secrets: Some(secrets),
+            input_fields: Some(array("inputFields")),
+            output_fields: Some(array("outputFields")),
+        }
+    }
+
+    /// Secret names the action will reference (empty when not declared).
+    pub fn secrets(&self) -> &[String] {
+        self.secrets.as_deref().unwrap_or_default()
+    }
+
+    /// Declared keys that differ from the deployed `action`.
+    pub fn changes(&self, action: &JsonValue) -> Vec<FieldChange> {
+        let mut out = Vec::new();
+
+        if let Some(secrets) = &self.secrets {
+            let declared = secrets.join("\n");
+            let deployed = secret_lines(action);
+            if declared != deployed {
+                out.push(FieldChange {
+                    key: "secretNames",
+                    deployed,
+                    declared,
+                });
+            }
+        }
+
+        for (key, fields) in [
+            ("inputFields", &self.input_fields),
+            ("outputFields", &self.output_fields),
+        ] {
+            if let Some(fields) = fields {
+                let declared = field_lines(fields);
+                let deployed = match action.get(key).and_then(|v| v.as_array()) {
+                    Some(existing) => field_lines(existing),
+                    None => String::new(),
+                };
+                if declared != deployed {
+                    out.push(FieldChange {
+                        key,
+                        deployed,
+                        declared,
+                    });
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Write the declared keys into a HubSpot action object.
+    pub fn apply(&self, action: &mut Map<String, JsonValue>) {
+        if let Some(secrets) = &self.secrets {
+            action.insert("secretNames".to_string(), json!(secrets));
+        }
+        if let Some(fields) = &self.input_fields {
+            action.insert("inputFields".to_string(), json!(fields));
+        }
+        if let Some(fields) = &self.output_fields {
+            action.insert("outputFields".to_string(), json!(fields));
+        }
+    }
+}
+
+/// Secret names referenced by any CUSTOM_CODE action of the flow.
+pub fn flow_secrets(flow: &JsonValue) -> BTreeSet<String> {
+    flow.get("actions")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|a| a.get("type").and_then(|v| v.as_str()) == Some("CUSTOM_CODE"))
+        .filter_map(|a| a.get("secretNames").and_then(|v| v.as_array()))
+        .flatten()
+        .filter_map(|n| n.as_str().map(|s| s.to_string()))
+        .collect()
+}
+
+fn hubspot_type(ty: OutputType) -> &'static str {
+    match ty {
+        OutputType::String => "STRING",
+        OutputType::Number => "NUMBER",
+        OutputType::Boolean => "BOOLEAN",
+        OutputType::Date => "DATE",
+        OutputType::Datetime => "DATETIME",
+        OutputType::PhoneNumber => "PHONE_NUMBER",
+    }
+}
+
+fn secret_lines(action: &JsonValue) -> String {
+    let mut names: Vec<&str> = action
+        .get("secretNames")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|n| n.as_str())
+        .collect();
+    names.sort();
+    names.join("\n")
+}
+
+/// `name: <value>` per field, sorted by name.
+///
+/// Object property inputs show as `name → property`, output fields as
+/// `name: TYPE`; anything else as its JSON.
+fn field_lines(fields: &[JsonValue]) -> String {
+    let mut lines: Vec<String> = fields
+        .iter()
+        .map(|f| {
+            let name = f.get("name").and_then(|v| v.as_str()).unwrap_or("?");
+            if let Some(property) = f.pointer("/value/propertyName").and_then(|v| v.as_str()) {
+                format!("{} → {}", name, property)
+            } else if let Some(ty) = f.get("type").and_then(|v| v.as_str()) {
+                format!("{}: {}", name, ty)
+            } else {
+                format!("{}: {}", name, f.get("value").unwrap_or(&JsonValue::Null))
+            }
+        })
+        .collect();
+    lines.sort();
+    lines.join("\n")
+}
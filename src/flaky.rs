@@ -0,0 +1,186 @@
+// src/flaky.rs
+
+//! Flakiness analysis across repeated runs.
+//!
+//! When an action is executed more than once for the same fixture
+//! (`repeat > 1`), every run is compared against the first one to find:
+//! - JSON paths whose value differs between runs (nondeterminism), except
+//!   `error.stack` (it holds per-run temp paths, so errors compare by type
+//!   and message) and the `snapshots.ignore` paths
+//! - The pass / fail ratio
+//! - The spread of duration and peak memory
+//!
+//! The result is a single verdict per fixture:
+//! - `pass`:  every run passed and produced identical output
+//! - `flaky`: runs disagree (mixed pass/fail, or differing output)
+//! - `fail`:  every run failed with identical output
+
+use crate::stats::Spread;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// Outcome of a single repeated invocation.
+#[derive(Debug, Clone)]
+pub struct RunSample {
+    pub output: Value,
+    pub passed: bool,
+    pub duration_ms: u128,
+    pub max_rss_kb: Option<u64>,
+}
+
+/// Verdict for a fixture after all repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Pass,
+    Flaky,
+    Fail,
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Pass => "pass",
+            Verdict::Flaky => "flaky",
+            Verdict::Fail => "fail",
+        }
+    }
+}
+
+/// Flakiness report for one fixture.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlakyReport {
+    pub fixture: String,
+    pub verdict: Verdict,
+    pub runs: u64,
+    pub passed: u64,
+    pub failed: u64,
+
+    /// JSON paths whose value differed from the first run.
+    pub nondeterministic_paths: Vec<String>,
+
+    pub duration_ms: Option<Spread>,
+    pub memory_kb: Option<Spread>,
+}
+
+impl FlakyReport {
+    /// Fraction of runs that passed (0.0..=1.0).
+    pub fn pass_ratio(&self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        self.passed as f64 / self.runs as f64
+    }
+}
+
+/// Analyse all runs of a single fixture; `ignore` paths may differ.
+pub fn analyze(fixture: &str, samples: &[RunSample], ignore: &[String]) -> FlakyReport {
+    let runs = samples.len() as u64;
+    let passed = samples.iter().filter(|s| s.passed).count() as u64;
+    let failed = runs - passed;
+
+    let mut paths = BTreeSet::new();
+    if let Some(first) = samples.first() {
+        let first = comparable(&first.output);
+        for s in &samples[1..] {
+            diff_paths(&first, &comparable(&s.output), "", &mut paths);
+        }
+    }
+    paths.retain(|path| !ignore.iter().any(|ig| is_within(path, ig)));
+
+    let durations = samples
+        .iter()
+        .map(|s| u64::try_from(s.duration_ms).unwrap_or(u64::MAX))
+        .collect::<Vec<_>>();
+    let memory = samples
+        .iter()
+        .filter_map(|s| s.max_rss_kb)
+        .collect::<Vec<_>>();
+
+    let verdict = if (passed > 0 && failed > 0) || !paths.is_empty() {
+        Verdict::Flaky
+    } else if failed > 0 {
+        Verdict::Fail
+    } else {
+        Verdict::Pass
+    };
+
+    FlakyReport {
+        fixture: fixture.to_string(),
+        verdict,
+        runs,
+        passed,
+        failed,
+        nondeterministic_paths: paths.into_iter().collect(),
+        duration_ms: Spread::from_samples(&durations),
+        memory_kb: Spread::from_samples(&memory),
+    }
+}
+
+/// The output without `error.stack`, which differs between runs of the same
+/// error (the shim runs from a fresh temp dir each time).
+fn comparable(output: &Value) -> Value {
+    let mut output = output.clone();
+    if let Some(error) = output.get_mut("error").and_then(Value::as_object_mut) {
+        error.remove("stack");
+    }
+    output
+}
+
+/// Whether `path` is `prefix` or below it.
+fn is_within(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.') || rest.starts_with('['),
+        None => false,
+    }
+}
+
+/// Collect every path at which `a` and `b` differ.
+///
+/// Paths use the same syntax accepted by assertions
+/// (`callback.outputFields.id`, `items[0].id`).
+fn diff_paths(a: &Value, b: &Value, prefix: &str, out: &mut BTreeSet<String>) {
+    match (a, b) {
+        (Value::Object(ma), Value::Object(mb)) => {
+            let keys = ma.keys().chain(mb.keys()).collect::<BTreeSet<_>>();
+            for k in keys {
+                let path = if prefix.is_empty() {
+                    k.to_string()
+                } else {
+                    format!("{}.{}", prefix, k)
+                };
+                match (ma.get(k), mb.get(k)) {
+                    (Some(va), Some(vb)) => diff_paths(va, vb, &path, out),
+                    _ => {
+                        out.insert(path);
+                    }
+                }
+            }
+        }
+
+        (Value::Array(va), Value::Array(vb)) => {
+            for i in 0..va.len().max(vb.len()) {
+                let path = format!("{}[{}]", prefix, i);
+                match (va.get(i), vb.get(i)) {
+                    (Some(x), Some(y)) => diff_paths(x, y, &path, out),
+                    _ => {
+                        out.insert(path);
+                    }
+                }
+            }
+        }
+
+        _ => {
+            if a != b {
+                let path = if prefix.is_empty() {
+                    "$".to_string()
+                } else {
+                    prefix.to_string()
+                };
+                out.insert(path);
+            }
+        }
+    }
+}
@@ -0,0 +1,489 @@
+// src/history.rs
+
+//! Promotion history and `hsemulate rollback`.
+//!
+//! Every promotion (and rollback) that reaches HubSpot leaves a record under
+//! `.hsemulator/promotions/<target>/`:
+//! - `<id>.json`: the flow as it was before the PUT, plus each changed
+//!   action's source, runtime, secrets and fields before and after
+//! - `history.jsonl`: one summary line per record, oldest first
+//!
+//! Record ids are UTC timestamps (`20260101T120000123Z`), so they sort in
+//! promotion order.
+//!
+//! `rollback` restores the recorded code and fields of every action of an
+//! entry in one revision-guarded PUT, like `promote`, and is itself
+//! recorded. Actions are found again by their HubSpot `actionId` (the index
+//! as a fallback), so a rollback does not depend on the current selectors.
+
+use crate::audit;
+use crate::fields::ActionFields;
+use crate::promote;
+use crate::util::ensure_dir;
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const PROMOTIONS_DIR: &str = ".hsemulator/promotions";
+const HISTORY_FILE: &str = "history.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Promote,
+    Rollback,
+}
+
+/// One line of `history.jsonl`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub kind: Kind,
+    pub at: String,
+    pub workflow_id: String,
+    /// Actions changed by this entry, in PUT order
+    pub actions: Vec<ActionChange>,
+    pub revision_id_before: JsonValue,
+    pub revision_id_after: JsonValue,
+}
+
+/// One action changed by a history entry.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActionChange {
+    pub action_index: usize,
+    /// HubSpot `actionId`, to find the action again on rollback
+    pub action_id: Option<String>,
+    /// Hash marker of the source deployed by this entry
+    pub hash: Option<String>,
+    /// Hash marker of the source it replaced
+    pub previous_hash: Option<String>,
+}
+
+/// Full record of one deployment, `<id>.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PromotionRecord {
+    #[serde(flatten)]
+    pub entry: HistoryEntry,
+    /// Code of each entry in `actions`, same order
+    pub code: Vec<ActionCode>,
+    /// The whole flow before the PUT
+    pub previous_flow: JsonValue,
+}
+
+/// Source, runtime, secrets and fields of one action before and after a
+/// deployment.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActionCode {
+    pub source: String,
+    pub runtime: Option<String>,
+    // Records written before field sync only hold code and runtime
+    #[serde(default)]
+    pub fields: Option<ActionFields>,
+    pub previous_source: String,
+    pub previous_runtime: Option<String>,
+    #[serde(default)]
+    pub previous_fields: Option<ActionFields>,
+}
+
+/// A PUT that replaced the code of one or more actions.
+pub struct Deployment<'a> {
+    pub kind: Kind,
+    pub target: &'a str,
+    pub workflow_id: &'a str,
+    pub action_indices: &'a [usize],
+    /// Flow fetched before the PUT
+    pub flow_before: &'a JsonValue,
+    /// Payload that was PUT
+    pub flow_after: &'a JsonValue,
+    /// HubSpot's response to the PUT
+    pub put_result: &'a JsonValue,
+}
+
+/* ---------------- recording ---------------- */
+
+/// Record a deployment; returns the record id.
+pub fn record(deployment: &Deployment<'_>) -> Result<String> {
+    let dir = target_dir(deployment.target);
+    ensure_dir(&dir)?;
+
+    let now = Utc::now();
+    let id = now.format("%Y%m%dT%H%M%S%3fZ").to_string();
+
+    let mut actions = Vec::new();
+    let mut code = Vec::new();
+    for &action_index in deployment.action_indices {
+        let previous_source =
+            promote::get_action_source_code(deployment.flow_before, action_index)?;
+        let source = promote::get_action_source_code(deployment.flow_after, action_index)?;
+
+        actions.push(ActionChange {
+            action_index,
+            action_id: action_id(deployment.flow_before, action_index),
+            hash: promote::extract_hash_marker(&source),
+            previous_hash: promote::extract_hash_marker(&previous_source),
+        });
+        code.push(ActionCode {
+            runtime: action_runtime(deployment.flow_after, action_index),
+            fields: action_fields(deployment.flow_after, action_index),
+            source,
+            previous_runtime: action_runtime(deployment.flow_before, action_index),
+            previous_fields: action_fields(deployment.flow_before, action_index),
+            previous_source,
+        });
+    }
+
+    let record = PromotionRecord {
+        entry: HistoryEntry {
+            id: id.clone(),
+            kind: deployment.kind,
+            at: now.to_rfc3339(),
+            workflow_id: deployment.workflow_id.to_string(),
+            actions,
+            revision_id_before: revision_id(deployment.flow_before),
+            revision_id_after: revision_id(deployment.put_result),
+        },
+        code,
+        previous_flow: deployment.flow_before.clone(),
+    };
+
+    let path = dir.join(format!("{}.json", id));
+    let bytes =
+        serde_json::to_vec_pretty(&record).context("Failed to serialise promotion record")?;
+    std::fs::write(&path, bytes)
+        .with_context(|| format!("Failed to write promotion record {:?}", path))?;
+
+    let history_path = dir.join(HISTORY_FILE);
+    let mut history = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&history_path)
+        .with_context(|| format!("Failed to open promotion history {:?}", history_path))?;
+    writeln!(history, "{}", serde_json::to_string(&record.entry)?)
+        .with_context(|| format!("Failed to write promotion history {:?}", history_path))?;
+
+    Ok(id)
+}
+
+/// Record a deployment that already reached HubSpot.
+///
+/// The workflow has changed either way, so a failure only warns: the
+/// deployment is still audited and reported, just not rollback-able.
+pub fn record_or_warn(deployment: &Deployment<'_>) -> Option<String> {
+    match record(deployment) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            eprintln!(
+                "Warning: workflow {} was updated but its history could not be saved; \
+                 `hsemulate rollback` will not see this change: {:#}",
+                deployment.workflow_id, e
+            );
+            None
+        }
+    }
+}
+
+/* ---------------- loading ---------------- */
+
+fn target_dir(target: &str) -> PathBuf {
+    Path::new(PROMOTIONS_DIR).join(target)
+}
+
+/// History of a target, oldest first (empty if never promoted).
+pub fn load_history(target: &str) -> Result<Vec<HistoryEntry>> {
+    let path = target_dir(target).join(HISTORY_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let raw = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read promotion history {:?}", path))?;
+    raw.lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line).with_context(|| {
+                format!(
+                    "Invalid promotion history entry at {:?} line {}",
+                    path,
+                    i + 1
+                )
+            })
+        })
+        .collect()
+}
+
+pub fn load_record(target: &str, id: &str) -> Result<PromotionRecord> {
+    let path = target_dir(target).join(format!("{}.json", id));
+    let raw = std::fs::read_to_string(&path).with_context(|| {
+        format!(
+            "No promotion record '{}' for target '{}' ({:?})",
+            id, target, path
+        )
+    })?;
+    serde_json::from_str(&raw).with_context(|| format!("Invalid promotion record {:?}", path))
+}
+
+fn revision_id(flow: &JsonValue) -> JsonValue {
+    flow.get("revisionId").cloned().unwrap_or(JsonValue::Null)
+}
+
+fn action_runtime(flow: &JsonValue, action_index: usize) -> Option<String> {
+    flow.pointer(&format!("/actions/{}/runtime", action_index))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+fn action_fields(flow: &JsonValue, action_index: usize) -> Option<ActionFields> {
+    flow.pointer(&format!("/actions/{}", action_index))
+        .map(ActionFields::from_hubspot)
+}
+
+fn action_id(flow: &JsonValue, action_index: usize) -> Option<String> {
+    match flow.pointer(&format!("/actions/{}/actionId", action_index))? {
+        JsonValue::String(id) => Some(id.clone()),
+        JsonValue::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Where a recorded action is now: by `actionId`, else the recorded index.
+fn locate(flow: &JsonValue, change: &ActionChange) -> Result<usize> {
+    let Some(id) = &change.action_id else {
+        return Ok(change.action_index);
+    };
+
+    let count = flow
+        .get("actions")
+        .and_then(|v| v.as_array())
+        .map(|a| a.len())
+        .unwrap_or(0);
+    (0..count)
+        .find(|&i| action_id(flow, i).as_deref() == Some(id.as_str()))
+        .ok_or_else(|| anyhow::anyhow!("Action {} no longer exists in the workflow", id))
+}
+
+/* ---------------- rollback ---------------- */
+
+/// Entry point for `hsemulate rollback <target> [--to <id>] [--list] [--force]`.
+///
+/// Without `--to`, undoes the latest entry (restores what it replaced).
+/// With `--to`, restores the source that entry deployed.
+pub async fn rollback(target: String, to: Option<String>, list: bool, force: bool) -> Result<()> {
+    let cicd = promote::load_cicd()?;
+    let t = promote::get_target(&cicd, &target)?;
+    let history = load_history(&target)?;
+
+    if list {
+        print_history(&history);
+        return Ok(());
+    }
+
+    let Some(latest) = history.last() else {
+        bail!(
+            "No promotion history for target '{}' ({:?})",
+            target,
+            target_dir(&target)
+        );
+    };
+
+    let chosen = load_record(&target, to.as_deref().unwrap_or(&latest.id))?;
+    if chosen.code.len() != chosen.entry.actions.len() {
+        bail!("Promotion record '{}' is inconsistent", chosen.entry.id);
+    }
+
+    let token = promote::resolve_token(&cicd)?;
+    let base_url = promote::resolve_base_url(&cicd, t)?;
+    let client = reqwest::Client::new();
+    let headers = promote::hubspot_headers(&token)?;
+
+    let flow = promote::hubspot_get_flow(&client, &headers, &base_url, &t.workflow_id).await?;
+
+    let mut restores = Vec::new();
+    for (change, code) in chosen.entry.actions.iter().zip(&chosen.code) {
+        let (source, runtime, fields) = match &to {
+            Some(_) => (&code.source, &code.runtime, &code.fields),
+            None => (
+                &code.previous_source,
+                &code.previous_runtime,
+                &code.previous_fields,
+            ),
+        };
+        let action_index = locate(&flow, change)?;
+        let existing_source = promote::get_action_source_code(&flow, action_index)?;
+
+        // Same ownership rule as `promote`
+        if promote::extract_hash_marker(&existing_source).is_none() && !force {
+            bail!(
+                "Refusing to roll back workflow {}.\n\
+                \n\
+                The deployed action at index {} has no hsemulator-sha marker, so it was changed\n\
+                outside hsemulate since the last promotion. Roll back anyway with --force.",
+                t.workflow_id,
+                action_index
+            );
+        }
+        let fields_differ = match (fields, flow.pointer(&format!("/actions/{}", action_index))) {
+            (Some(fields), Some(action)) => !fields.changes(action).is_empty(),
+            _ => false,
+        };
+        if existing_source == *source && !fields_differ {
+            continue;
+        }
+
+        eprintln!(
+            "Rolling back '{}' action {}: {} → {}",
+            target,
+            action_index,
+            promote::extract_hash_marker(&existing_source).unwrap_or_else(|| "(no marker)".into()),
+            promote::extract_hash_marker(source).unwrap_or_else(|| "(no marker)".into())
+        );
+        restores.push(promote::ActionUpdate {
+            action_index,
+            source,
+            runtime: runtime.as_deref(),
+            fields: fields.as_ref(),
+        });
+    }
+
+    if restores.is_empty() {
+        eprintln!("Workflow already runs the restored source. No changes required.");
+        return Ok(());
+    }
+
+    let update = promote::hubspot_update_actions(
+        &client,
+        &headers,
+        &base_url,
+        &t.workflow_id,
+        flow,
+        &restores,
+    )
+    .await?;
+
+    let record_id = record_or_warn(&Deployment {
+        kind: Kind::Rollback,
+        target: &target,
+        workflow_id: &t.workflow_id,
+        action_indices: &update.action_indices,
+        flow_before: &update.flow_before,
+        flow_after: &update.flow_after,
+        put_result: &update.put_result,
+    });
+
+    let audited = audit::record(&audit::Event {
+        origin: audit::Origin::Cli,
+        kind: Kind::Rollback,
+        target: Some(&target),
+        workflow_id: &t.workflow_id,
+        actions: update
+            .action_indices
+            .iter()
+            .map(|&action_index| audit::AuditAction {
+                action_index,
+                old_hash: promote::get_action_source_code(&update.flow_before, action_index)
+                    .ok()
+                    .and_then(|s| promote::extract_hash_marker(&s)),
+                new_hash: promote::get_action_source_code(&update.flow_after, action_index)
+                    .ok()
+                    .and_then(|s| promote::extract_hash_marker(&s)),
+            })
+            .collect(),
+        revision_id_before: revision_id(&update.flow_before),
+        revision_id_after: revision_id(&update.put_result),
+        force,
+        tests: None,
+        user: None,
+        claimed_actor: None,
+        history_id: record_id.as_deref(),
+    });
+
+    let actions: Vec<JsonValue> = restores
+        .iter()
+        .zip(&update.action_indices)
+        .map(|(r, action_index)| {
+            json!({
+                "action_index": action_index,
+                "hash": promote::extract_hash_marker(r.source),
+            })
+        })
+        .collect();
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json!({
+            "ok": true,
+            "target": target,
+            "workflow_id": t.workflow_id,
+            "restored": to,
+            "undone": to.is_none().then(|| latest.id.clone()),
+            "actions": actions,
+            "revision_id_before": revision_id(&update.flow_before),
+            "revision_id_after": revision_id(&update.put_result),
+            "history_id": record_id,
+            "audit_failed": audited.is_err(),
+        }))?
+    );
+
+    audited.context("Rollback succeeded but the audit log could not be written")
+}
+
+fn print_history(history: &[HistoryEntry]) {
+    if history.is_empty() {
+        println!("No promotions recorded.");
+        return;
+    }
+
+    println!(
+        "{:<20}  {:<8}  {:<12}  {:<12}  REVISION",
+        "ID", "KIND", "HASH", "PREVIOUS"
+    );
+    for entry in history {
+        let kind = match entry.kind {
+            Kind::Promote => "promote",
+            Kind::Rollback => "rollback",
+        };
+        let (first, rest) = match entry.actions.split_first() {
+            Some((first, rest)) => (Some(first), rest),
+            None => (None, &[][..]),
+        };
+        println!(
+            "{:<20}  {:<8}  {:<12}  {:<12}  {} → {}",
+            entry.id,
+            kind,
+            short(first.and_then(|a| a.hash.as_deref())),
+            short(first.and_then(|a| a.previous_hash.as_deref())),
+            revision(&entry.revision_id_before),
+            revision(&entry.revision_id_after)
+        );
+        // Further actions of a multi-action promotion
+        for action in rest {
+            println!(
+                "{:<20}  {:<8}  {:<12}  {:<12}",
+                "",
+                "",
+                short(action.hash.as_deref()),
+                short(action.previous_hash.as_deref())
+            );
+        }
+    }
+}
+
+/// Revision ids are strings in HubSpot's API, but show them bare either way.
+fn revision(id: &JsonValue) -> String {
+    match id {
+        JsonValue::String(s) => s.clone(),
+        JsonValue::Null => "-".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn short(hash: Option<&str>) -> &str {
+    match hash {
+        Some(hash) => hash.get(..12).unwrap_or(hash),
+        None => "-",
+    }
+}
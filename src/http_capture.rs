@@ -0,0 +1,112 @@
+// src/http_capture.rs
+
+//! Outbound HTTP requests captured by the runtime shims.
+//!
+//! The shims record every request the action makes and report them in their
+//! result JSON under `requests`:
+//! - Node: `http` / `https` request APIs and the global `fetch`
+//! - Python: `http.client` (used by `urllib`, `requests` / `urllib3`, …)
+//!
+//! The runner removes that list from the output before assertions and
+//! snapshots run, so existing output snapshots are unaffected.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Headers never stored with a captured request.
+const REDACTED_HEADERS: [&str; 5] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+    "x-hubspot-api-key",
+];
+
+/// One outbound HTTP request made by the action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturedRequest {
+    pub method: String,
+    pub url: String,
+    pub host: String,
+    pub path: String,
+
+    /// Request headers (lowercase names, credentials removed)
+    pub headers: BTreeMap<String, String>,
+
+    /// Parsed JSON body, raw text, or null
+    pub body: Value,
+
+    /// Response status (null when no response was received)
+    pub status: Option<u16>,
+}
+
+/// Remove the shim's `requests` list from `output` and normalise it.
+pub fn take_requests(output: &mut Value) -> Vec<CapturedRequest> {
+    let Some(raw) = output.as_object_mut().and_then(|o| o.remove("requests")) else {
+        return Vec::new();
+    };
+
+    raw.as_array()
+        .map(|items| items.iter().filter_map(normalise).collect())
+        .unwrap_or_default()
+}
+
+/// Short `METHOD url → status` line for human-readable output.
+pub fn describe(request: &CapturedRequest) -> String {
+    match request.status {
+        Some(status) => format!("{} {} → {}", request.method, request.url, status),
+        None => format!("{} {} → no response", request.method, request.url),
+    }
+}
+
+/* ---------------- helpers ---------------- */
+
+fn normalise(raw: &Value) -> Option<CapturedRequest> {
+    let method = raw.get("method")?.as_str()?.to_ascii_uppercase();
+    let url = raw.get("url")?.as_str()?.to_string();
+
+    let (host, path) = match reqwest::Url::parse(&url) {
+        Ok(parsed) => (
+            parsed.host_str().unwrap_or_default().to_string(),
+            parsed.path().to_string(),
+        ),
+        Err(_) => (String::new(), url.clone()),
+    };
+
+    let headers = raw
+        .get("headers")
+        .and_then(|h| h.as_object())
+        .map(|h| {
+            h.iter()
+                .map(|(k, v)| (k.to_ascii_lowercase(), header_value(v)))
+                .filter(|(k, _)| !REDACTED_HEADERS.contains(&k.as_str()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(CapturedRequest {
+        method,
+        url,
+        host,
+        path,
+        headers,
+        body: raw.get("body").cloned().unwrap_or(Value::Null),
+        status: raw
+            .get("status")
+            .and_then(|s| s.as_u64())
+            .and_then(|s| u16::try_from(s).ok()),
+    })
+}
+
+fn header_value(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(header_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
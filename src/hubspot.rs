@@ -0,0 +1,197 @@
+// src/hubspot.rs
+
+//! HTTP transport for the HubSpot API.
+//!
+//! Every call goes through `send`, which:
+//! - retries rate limits (429) and transient failures (500/502/503/504,
+//!   timeouts, connection errors) with exponential backoff, honouring
+//!   `Retry-After`
+//! - turns failures into a `HubSpotError`, so callers can tell a bad token
+//!   from a missing workflow or a revision conflict (`error_kind`)
+
+use anyhow::Result;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::fmt;
+use std::time::Duration;
+
+/// Attempts per call, the first included.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Backoff before the first retry; doubled for each further one.
+const BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest wait between attempts (also caps `Retry-After`).
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// 401: token missing, revoked or malformed
+    Unauthorized,
+    /// 403: token lacks a scope, or the resource is in another portal
+    Forbidden,
+    /// 404
+    NotFound,
+    /// 429, still failing after every retry
+    RateLimited,
+    /// PUT against a stale `revisionId`
+    Conflict,
+    /// 5xx, still failing after every retry
+    Unavailable,
+    /// Any other status
+    Failed,
+}
+
+impl ErrorKind {
+    fn is_retryable(self) -> bool {
+        matches!(self, ErrorKind::RateLimited | ErrorKind::Unavailable)
+    }
+}
+
+/// A HubSpot API call that returned an error status.
+#[derive(Debug)]
+pub struct HubSpotError {
+    pub kind: ErrorKind,
+    /// e.g. `GET flow`
+    what: &'static str,
+    status: StatusCode,
+    body: String,
+    attempts: u32,
+}
+
+impl fmt::Display for HubSpotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Unauthorized => write!(
+                f,
+                "HubSpot {} failed: the token was rejected ({}).\n\
+                Check HUBSPOT_TOKEN (private app tokens start with pat-).",
+                self.what, self.status
+            ),
+            ErrorKind::Forbidden => write!(
+                f,
+                "HubSpot {} failed: access denied ({}).\n\
+                The private app needs the `automation` scope, and the workflow must be in its portal.",
+                self.what, self.status
+            ),
+            ErrorKind::NotFound => {
+                write!(f, "HubSpot {} failed: not found ({})", self.what, self.status)
+            }
+            ErrorKind::RateLimited => write!(
+                f,
+                "HubSpot {} failed: rate limited ({}), gave up after {} attempts",
+                self.what, self.status, self.attempts
+            ),
+            ErrorKind::Conflict => write!(
+                f,
+                "HubSpot {} failed: revision conflict ({}), the workflow changed since it was fetched. {}",
+                self.what, self.status, self.body
+            ),
+            ErrorKind::Unavailable => write!(
+                f,
+                "HubSpot {} failed: {} after {} attempts. {}",
+                self.what, self.status, self.attempts, self.body
+            ),
+            ErrorKind::Failed => {
+                write!(f, "HubSpot {} failed: {} {}", self.what, self.status, self.body)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HubSpotError {}
+
+/// Kind of the `HubSpotError` behind `err`, if it is one.
+pub fn error_kind(err: &anyhow::Error) -> Option<ErrorKind> {
+    err.downcast_ref::<HubSpotError>().map(|e| e.kind)
+}
+
+/// Send a request (rebuilt by `request` for each attempt); returns the body
+/// of the first successful response.
+pub async fn send<F>(what: &'static str, request: F) -> Result<String>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let mut attempt = 1;
+    loop {
+        let (reason, wait) = match request().send().await {
+            Ok(resp) => {
+                let status = resp.status();
+                let retry_after = retry_after(resp.headers());
+                let body = resp.text().await.unwrap_or_default();
+                if status.is_success() {
+                    return Ok(body);
+                }
+
+                let kind = classify(status, &body);
+                if !kind.is_retryable() || attempt >= MAX_ATTEMPTS {
+                    return Err(HubSpotError {
+                        kind,
+                        what,
+                        status,
+                        body,
+                        attempts: attempt,
+                    }
+                    .into());
+                }
+                (status.to_string(), retry_after.unwrap_or(backoff(attempt)))
+            }
+            Err(e) if (e.is_timeout() || e.is_connect()) && attempt < MAX_ATTEMPTS => {
+                (e.to_string(), backoff(attempt))
+            }
+            Err(e) => {
+                return Err(
+                    anyhow::Error::new(e).context(format!("Failed to call HubSpot {}", what))
+                )
+            }
+        };
+
+        let wait = wait.min(MAX_DELAY);
+        eprintln!(
+            "HubSpot {} failed ({}); retrying in {:.1}s (attempt {}/{})",
+            what,
+            reason,
+            wait.as_secs_f64(),
+            attempt + 1,
+            MAX_ATTEMPTS
+        );
+        tokio::time::sleep(wait).await;
+        attempt += 1;
+    }
+}
+
+fn classify(status: StatusCode, body: &str) -> ErrorKind {
+    match status {
+        StatusCode::UNAUTHORIZED => ErrorKind::Unauthorized,
+        StatusCode::FORBIDDEN => ErrorKind::Forbidden,
+        StatusCode::NOT_FOUND => ErrorKind::NotFound,
+        StatusCode::TOO_MANY_REQUESTS => ErrorKind::RateLimited,
+        StatusCode::CONFLICT => ErrorKind::Conflict,
+        // Stale revisions have also been reported as validation errors
+        StatusCode::BAD_REQUEST if body.to_ascii_lowercase().contains("revision") => {
+            ErrorKind::Conflict
+        }
+        StatusCode::INTERNAL_SERVER_ERROR
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT => ErrorKind::Unavailable,
+        _ => ErrorKind::Failed,
+    }
+}
+
+/// 0.5s, 1s, 2s, 4s, ...
+fn backoff(attempt: u32) -> Duration {
+    BASE_DELAY.saturating_mul(1 << (attempt - 1).min(16))
+}
+
+/// `Retry-After` as delay seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
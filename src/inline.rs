@@ -4,7 +4,8 @@ use std::collections::BTreeMap;
 use std::path::Path;
 
 use crate::config::{
-    Action, ActionType, Config, Mode, OutputConfig, Runtime, SnapshotConfig,
+    Action, ActionType, Config, Mode, NetworkConfig, OutputConfig, Runtime, RuntimeMode,
+    SnapshotConfig, TypeScriptConfig,
 };
 
 impl InlineConfig {
@@ -15,12 +16,16 @@ impl InlineConfig {
             action: Some(Action {
                 action_type: match self.action.language {
                     InlineLanguage::Js => ActionType::Js,
+                    InlineLanguage::Ts => ActionType::Ts,
                     InlineLanguage::Python => ActionType::Python,
                 },
                 entry: root
                     .join(&self.action.entry)
                     .to_string_lossy()
                     .to_string(),
+                secrets: None,
+                inputs: None,
+                outputs: None,
             }),
 
             fixtures: self
@@ -34,6 +39,8 @@ impl InlineConfig {
             runtime: Runtime {
                 node: self.runtime.node,
                 python: self.runtime.python,
+                mode: self.runtime.mode,
+                typescript: self.runtime.typescript,
             },
 
             snapshots: SnapshotConfig {
@@ -44,8 +51,14 @@ impl InlineConfig {
             output: OutputConfig::default(),
 
             budgets: None,
+            regression: None,
+            metrics: Default::default(),
+            network: self.network,
             assertions: Default::default(),
             assertions_file: None,
+            requests: Vec::new(),
+            max_requests: None,
+            logs: None,
 
             watch: false,
             repeat: self.repeat,
@@ -57,6 +70,7 @@ impl InlineConfig {
 
 #[derive(Debug, Deserialize)]
 pub struct InlineConfig {
+    #[allow(dead_code)]
     pub version: u32,
 
     pub action: InlineAction,
@@ -75,6 +89,9 @@ pub struct InlineConfig {
 
     #[serde(default = "default_repeat")]
     pub repeat: u32,
+
+    #[serde(default)]
+    pub network: Option<NetworkConfig>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -88,6 +105,7 @@ pub struct InlineAction {
 #[serde(rename_all = "lowercase")]
 pub enum InlineLanguage {
     Js,
+    Ts,
     Python,
 }
 
@@ -104,6 +122,13 @@ pub struct InlineRuntime {
 
     #[serde(default = "default_python")]
     pub python: String,
+
+    #[serde(default)]
+    pub mode: RuntimeMode,
+
+    /// Compiler settings for `language: ts`
+    #[serde(default)]
+    pub typescript: TypeScriptConfig,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -0,0 +1,167 @@
+// src/limits.rs
+
+//! Enforced memory limits (`budgets.enforce_memory`).
+//!
+//! A memory budget on its own is only checked after the run. With enforcement
+//! enabled the spawned runtime is constrained while it runs, so an action that
+//! HubSpot would kill also fails locally.
+//!
+//! The limit is applied with the most faithful mechanism available:
+//! 1. cgroup v2 `memory.max` (Linux) — the kernel OOM-kills the whole tree
+//! 2. Python: `RLIMIT_AS` (Unix) — allocations fail with `MemoryError`
+//! 3. Node: `--max-old-space-size` — V8 aborts when the heap is exhausted.
+//!    This caps the JavaScript heap only; buffers and native memory are not
+//!    limited, so it is weaker than the other two.
+//!
+//! A runtime killed by the limit cannot emit its JSON result, so a
+//! shim-shaped failure with `error.type = "memory"` is synthesised instead,
+//! but only with positive evidence that memory was the cause: a cgroup OOM
+//! kill, V8's heap exhaustion message or a Python `MemoryError`. Any other
+//! kill (an external `kill -9`, a CI timeout, a native abort) is reported as
+//! a plain runtime failure.
+
+use serde_json::{json, Value};
+use tokio::process::Command as TokioCommand;
+
+use crate::config::Config;
+use crate::logs::LogLine;
+use crate::metrics::{InvocationMetrics, ResourceMonitor};
+
+/// `error.type` reported when the memory limit was hit.
+pub const MEMORY_ERROR_TYPE: &str = "memory";
+
+/// Runtime language of the spawned shim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Node,
+    Python,
+}
+
+impl Language {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Language::Node => "node",
+            Language::Python => "python",
+        }
+    }
+}
+
+/// Apply the enforced memory limit (if configured) to a command about to be
+/// spawned. Returns the limit in MB when one is in effect.
+///
+/// Must be called AFTER the config environment has been applied to `cmd`,
+/// so a user-provided `NODE_OPTIONS` is extended rather than replaced.
+pub fn apply_memory_limit(
+    cfg: &Config,
+    language: Language,
+    cmd: &mut TokioCommand,
+    monitor: &mut ResourceMonitor,
+) -> Option<u64> {
+    let mb = cfg.budgets.as_ref()?.enforced_memory_mb()?;
+    let bytes = mb * 1024 * 1024;
+
+    if monitor.enforce_memory_limit(bytes, cfg.metrics.cgroup_parent.as_deref()) {
+        tracing::debug!("Enforcing {}MB memory limit via cgroup", mb);
+        return Some(mb);
+    }
+
+    match language {
+        Language::Node => {
+            let existing = cfg
+                .env
+                .get("NODE_OPTIONS")
+                .cloned()
+                .or_else(|| std::env::var("NODE_OPTIONS").ok())
+                .unwrap_or_default();
+            let flag = format!("--max-old-space-size={}", mb);
+            cmd.env("NODE_OPTIONS", format!("{} {}", existing, flag).trim());
+            tracing::debug!("Enforcing {}MB memory limit via V8 heap size", mb);
+        }
+        Language::Python => {
+            #[cfg(unix)]
+            {
+                limit_address_space(cmd, bytes);
+                tracing::debug!("Enforcing {}MB memory limit via RLIMIT_AS", mb);
+            }
+
+            #[cfg(not(unix))]
+            tracing::warn!(
+                "Memory enforcement for Python is not supported on this platform; \
+                 the memory budget is only checked after the run"
+            );
+        }
+    }
+
+    Some(mb)
+}
+
+/// Whether a runtime that produced no valid result was killed by the limit.
+///
+/// Requires positive evidence; the exit signal alone is not enough.
+pub fn killed_by_memory_limit(metrics: &InvocationMetrics, logs: &[LogLine]) -> bool {
+    metrics.oom_killed || logs.iter().any(|l| is_out_of_memory_message(&l.message))
+}
+
+/// V8 heap exhaustion, or a Python `MemoryError` that escaped the shim.
+fn is_out_of_memory_message(message: &str) -> bool {
+    let message = message.trim();
+    message.contains("JavaScript heap out of memory")
+        || message.contains("Reached heap limit")
+        || message.starts_with("MemoryError")
+}
+
+/// Shim-shaped failure output for a run killed by the memory limit.
+pub fn memory_exceeded_output(language: Language, limit_mb: u64) -> Value {
+    let result_key = match language {
+        Language::Node => "callback",
+        Language::Python => "result",
+    };
+
+    let mut out = json!({
+        "ok": false,
+        "language": language.as_str(),
+        "outputFields": null,
+        "error": {
+            "type": MEMORY_ERROR_TYPE,
+            "message": memory_exceeded_message(limit_mb),
+            "stack": null
+        }
+    });
+    out[result_key] = Value::Null;
+    out
+}
+
+/// Failure message for a run that exceeded the enforced limit.
+pub fn memory_exceeded_message(limit_mb: u64) -> String {
+    format!("Memory limit exceeded ({}MB)", limit_mb)
+}
+
+/// Whether an action output reports a memory limit failure.
+pub fn is_memory_error(output: &Value) -> bool {
+    output
+        .get("error")
+        .and_then(|e| e.get("type"))
+        .and_then(|t| t.as_str())
+        == Some(MEMORY_ERROR_TYPE)
+}
+
+/* ---------------- helpers ---------------- */
+
+#[cfg(unix)]
+fn limit_address_space(cmd: &mut TokioCommand, bytes: u64) {
+    let limit = libc::rlimit {
+        rlim_cur: bytes as libc::rlim_t,
+        rlim_max: bytes as libc::rlim_t,
+    };
+
+    // SAFETY: setrlimit is async-signal-safe and `limit` is plain data
+    // copied into the closure before fork.
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
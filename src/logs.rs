@@ -0,0 +1,166 @@
+// src/logs.rs
+
+//! Action log capture and log assertions.
+//!
+//! Shims report action logs as `log` messages of the shim protocol (see
+//! `protocol.rs`), each with a level and a timestamp:
+//! - Node: `console.debug` / `console.trace` → debug, `console.log` /
+//!   `console.info` → info, `console.warn` → warn, `console.error` → error
+//! - Python: `print` → info
+//!
+//! Anything written straight to STDOUT / STDERR (runtime warnings,
+//! subprocess output, `process.stdout.write`) is kept as info lines.
+
+use crate::config::LogAssertions;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Which channel a log line was written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Log,
+    Error,
+}
+
+impl LogStream {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogStream::Log => "log",
+            LogStream::Error => "error",
+        }
+    }
+}
+
+/// Severity reported by the shim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// One line written by the action.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    /// `error` for error-level lines, `log` for everything else
+    pub stream: LogStream,
+    pub level: LogLevel,
+    /// Milliseconds since the Unix epoch, as reported by the shim
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ts: Option<u64>,
+    pub message: String,
+}
+
+impl LogLine {
+    pub fn new(level: LogLevel, ts: Option<u64>, message: String) -> Self {
+        let stream = match level {
+            LogLevel::Error => LogStream::Error,
+            _ => LogStream::Log,
+        };
+        Self {
+            stream,
+            level,
+            ts,
+            message,
+        }
+    }
+
+    /// A line written straight to STDOUT / STDERR, outside the protocol.
+    pub fn raw(message: String) -> Self {
+        Self::new(LogLevel::Info, None, message)
+    }
+}
+
+/// Evaluate a `logs:` block; returns every failure found.
+pub fn check(lines: &[LogLine], cfg: &LogAssertions) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    for needle in &cfg.contains {
+        if !lines.iter().any(|l| l.message.contains(needle.as_str())) {
+            failures.push(format!(
+                "Log assertion failed: no line contains {:?}",
+                needle
+            ));
+        }
+    }
+
+    for needle in &cfg.not_contains {
+        if let Some(line) = lines.iter().find(|l| l.message.contains(needle.as_str())) {
+            failures.push(format!(
+                "Log assertion failed: line contains {:?}: {}",
+                needle, line.message
+            ));
+        }
+    }
+
+    for pattern in &cfg.regex {
+        match Regex::new(pattern) {
+            Ok(re) => {
+                if !lines.iter().any(|l| re.is_match(&l.message)) {
+                    failures.push(format!(
+                        "Log assertion failed: no line matches /{}/",
+                        pattern
+                    ));
+                }
+            }
+            Err(e) => failures.push(format!("Invalid log regex '{}': {}", pattern, e)),
+        }
+    }
+
+    for (needle, expected) in &cfg.count {
+        let actual = lines
+            .iter()
+            .filter(|l| l.message.contains(needle.as_str()))
+            .count() as u64;
+        if actual != *expected {
+            failures.push(format!(
+                "Log assertion failed: {:?} appears in {} line(s), expected {}",
+                needle, actual, expected
+            ));
+        }
+    }
+
+    if let Some(max) = cfg.max_lines {
+        if lines.len() as u64 > max {
+            failures.push(format!(
+                "Log assertion failed: {} lines logged (max {})",
+                lines.len(),
+                max
+            ));
+        }
+    }
+
+    if cfg.no_errors {
+        let errors = lines
+            .iter()
+            .filter(|l| l.stream == LogStream::Error)
+            .collect::<Vec<_>>();
+        if let Some(first) = errors.first() {
+            failures.push(format!(
+                "Log assertion failed: {} error line(s) logged, first: {}",
+                errors.len(),
+                first.message
+            ));
+        }
+    }
+
+    failures
+}
+
+/// Log lines as stored in a log snapshot (`"<stream>: <message>"`).
+///
+/// Lines matching any `logs.snapshot_ignore` pattern are left out.
+pub fn snapshot_value(lines: &[LogLine], ignore: &[Regex]) -> Value {
+    Value::Array(
+        lines
+            .iter()
+            .filter(|l| !ignore.iter().any(|re| re.is_match(&l.message)))
+            .map(|l| Value::String(format!("{}: {}", l.stream.as_str(), l.message)))
+            .collect(),
+    )
+}
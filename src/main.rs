@@ -14,22 +14,49 @@
 //!
 //! There is intentionally *no business logic* here.
 
+mod audit;
 mod auth;
+mod bench;
+mod bundle;
+#[cfg(target_os = "linux")]
+mod cgroup;
 mod checks;
 mod cicd;
 mod cli;
 mod config;
+mod coverage;
+mod debug;
+mod diff;
 mod engine;
 mod execution_id;
+mod fields;
+mod flaky;
+mod history;
+mod http_capture;
+mod hubspot;
+mod limits;
+mod logs;
 mod metrics;
+mod network;
+mod protocol;
+#[cfg(target_os = "linux")]
+mod proctree;
 mod promote;
+mod pull;
+mod registry;
+mod regression;
 mod runner;
 mod runtime;
+mod selector;
 mod shim;
 mod sinks;
 mod snapshot;
+mod stats;
+mod status;
 mod util;
+mod worker;
 mod types;
+mod typescript;
 mod inline;
 
 use anyhow::Result;
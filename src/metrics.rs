@@ -16,7 +16,13 @@
 //! - Extremely short-lived spikes may not be captured.
 //! - If the platform or PID cannot be inspected, memory tracking
 //!   degrades gracefully and returns `None`.
+//!
+//! On Linux, [`ResourceMonitor`] additionally:
+//! - Reads the `VmHWM` high-water mark of the whole process tree
+//! - Records user / system CPU time at exit
+//! - Optionally runs the child in its own cgroup for exact accounting
 
+use std::collections::BTreeMap;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc, Mutex,
@@ -25,18 +31,205 @@ use std::thread;
 use std::time::Duration;
 
 use sysinfo::{Pid, System};
+use tokio::process::Command as TokioCommand;
+
+use crate::config::MetricsConfig;
 
 /// Metrics collected for a single invocation.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct InvocationMetrics {
     /// Wall-clock duration in milliseconds.
     pub duration_ms: u128,
 
-    /// Peak RSS memory in KB (best-effort).
+    /// Peak RSS memory of the runtime process in KB (best-effort).
     pub max_rss_kb: Option<u64>,
+
+    /// Sum of the peak RSS of every process in the tree in KB (Linux only).
+    ///
+    /// Informational: an upper bound, since pages shared after fork are
+    /// counted once per process and the peaks need not coincide.
+    pub tree_max_rss_kb: Option<u64>,
+
+    /// Peak memory of the invocation's cgroup in KB (`memory.peak`, exact).
+    pub cgroup_peak_kb: Option<u64>,
+
+    /// User CPU time in milliseconds (Linux only).
+    pub cpu_user_ms: Option<u64>,
+
+    /// System CPU time in milliseconds (Linux only).
+    pub cpu_system_ms: Option<u64>,
+
+    /// The enforcing cgroup OOM-killed a process during this invocation.
+    pub oom_killed: bool,
+
+    /// Destinations (`host:port`) rejected by the network sandbox.
+    pub network_blocked: Vec<String>,
+
+    /// Time spent per shim phase (`load`, `execute`) in milliseconds.
+    pub phases: BTreeMap<String, f64>,
+}
+
+impl InvocationMetrics {
+    /// Peak memory checked against budgets: the cgroup peak if measured,
+    /// else the root process. The tree sum would overcount (see above).
+    pub fn peak_memory_kb(&self) -> Option<u64> {
+        self.cgroup_peak_kb.or(self.max_rss_kb)
+    }
+}
+
+/// Resource accounting for one spawned runtime process.
+///
+/// Usage:
+/// 1. `ResourceMonitor::new` + `prepare(&mut cmd)` before spawn
+/// 2. `start(pid)` right after spawn
+/// 3. `wait_for_exit().await` once the child's output has been drained
+/// 4. `finish(duration_ms)` BEFORE reaping the child (`child.wait()`)
+pub struct ResourceMonitor {
+    #[cfg(target_os = "linux")]
+    cgroup: Option<crate::cgroup::Cgroup>,
+
+    #[cfg(target_os = "linux")]
+    tree: Option<crate::proctree::ProcTreeTracker>,
+
+    memory: Option<MemoryTracker>,
+    pid: Option<u32>,
+}
+
+impl ResourceMonitor {
+    /// Create a monitor, setting up a cgroup when `metrics.cgroup` is enabled.
+    ///
+    /// Cgroup setup failures degrade to `/proc` sampling with a warning.
+    pub fn new(cfg: &MetricsConfig) -> Self {
+        #[cfg(target_os = "linux")]
+        let cgroup = if cfg.cgroup {
+            match crate::cgroup::Cgroup::create(cfg.cgroup_parent.as_deref()) {
+                Ok(cg) => Some(cg),
+                Err(e) => {
+                    tracing::warn!("cgroup accounting unavailable, falling back to /proc: {e:#}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        #[cfg(not(target_os = "linux"))]
+        if cfg.cgroup {
+            tracing::warn!("metrics.cgroup is only supported on Linux (ignored)");
+        }
+
+        Self {
+            #[cfg(target_os = "linux")]
+            cgroup,
+            #[cfg(target_os = "linux")]
+            tree: None,
+            memory: None,
+            pid: None,
+        }
+    }
+
+    /// Enforce a hard memory limit through the invocation's cgroup.
+    ///
+    /// Creates a cgroup when one was not requested via `metrics.cgroup`.
+    /// Returns `false` when cgroup enforcement is unavailable, so the caller
+    /// can fall back to a runtime-level limit.
+    pub fn enforce_memory_limit(&mut self, bytes: u64, cgroup_parent: Option<&str>) -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            if self.cgroup.is_none() {
+                self.cgroup = crate::cgroup::Cgroup::create(cgroup_parent).ok();
+            }
+            if let Some(cg) = &self.cgroup {
+                match cg.set_memory_max(bytes) {
+                    Ok(()) => return true,
+                    Err(e) => tracing::debug!("cgroup memory limit unavailable: {e:#}"),
+                }
+            }
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = (bytes, cgroup_parent);
+
+        false
+    }
+
+    /// Configure the command before it is spawned.
+    pub fn prepare(&self, cmd: &mut TokioCommand) -> anyhow::Result<()> {
+        #[cfg(target_os = "linux")]
+        if let Some(cg) = &self.cgroup {
+            cg.attach(cmd)?;
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = cmd;
+
+        Ok(())
+    }
+
+    /// Begin sampling the spawned process.
+    pub fn start(&mut self, pid: u32) {
+        self.pid = Some(pid);
+
+        #[cfg(target_os = "linux")]
+        {
+            self.tree = Some(crate::proctree::ProcTreeTracker::start(
+                pid,
+                Duration::from_millis(10),
+            ));
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            self.memory = Some(MemoryTracker::start(pid, Duration::from_millis(20)));
+        }
+    }
+
+    /// Wait until the child has exited without reaping it (Linux), so the
+    /// final accounting sample can still read its `/proc` entry.
+    pub async fn wait_for_exit(&self) {
+        #[cfg(target_os = "linux")]
+        if let Some(pid) = self.pid {
+            crate::proctree::wait_for_exit(pid).await;
+        }
+    }
+
+    /// Stop sampling and collect metrics.
+    pub fn finish(self, duration_ms: u128) -> InvocationMetrics {
+        let mut metrics = InvocationMetrics {
+            duration_ms,
+            max_rss_kb: self.memory.and_then(|m| m.stop_and_take()),
+            ..Default::default()
+        };
+
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(tree) = self.tree {
+                let usage = tree.stop_and_take();
+                metrics.max_rss_kb = usage.root_hwm_kb;
+                metrics.tree_max_rss_kb = usage.tree_hwm_kb;
+                metrics.cpu_user_ms = usage.cpu_user_ms;
+                metrics.cpu_system_ms = usage.cpu_system_ms;
+            }
+
+            // cgroup figures are exact and include orphaned descendants
+            if let Some(cg) = &self.cgroup {
+                metrics.oom_killed = cg.oom_kills() > 0;
+                metrics.cgroup_peak_kb = cg.memory_peak_kb();
+                if let Some((user, system)) = cg.cpu_ms() {
+                    metrics.cpu_user_ms = Some(user);
+                    metrics.cpu_system_ms = Some(system);
+                }
+            }
+        }
+
+        metrics
+    }
 }
 
 /// Tracks peak memory usage of a child process while it runs.
+///
+/// Used on platforms without `/proc` process-tree accounting, and for warm
+/// workers (whose `/proc` high-water marks span many invocations).
 pub struct MemoryTracker {
     stop: Arc<AtomicBool>,
     max_kb: Arc<Mutex<u64>>,
@@ -70,8 +263,8 @@ impl MemoryTracker {
                 system.refresh_process(pid);
 
                 if let Some(process) = system.process(pid) {
-                    // RSS memory in KB (sysinfo contract)
-                    let mem_kb = process.memory();
+                    // sysinfo reports RSS in bytes
+                    let mem_kb = process.memory() / 1024;
 
                     if let Ok(mut guard) = max_clone.lock() {
                         if mem_kb > *guard {
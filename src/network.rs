@@ -0,0 +1,321 @@
+// src/network.rs
+
+//! Outbound network sandbox (`network.allow`).
+//!
+//! When `network` is configured, every invocation gets a local HTTP proxy on
+//! `127.0.0.1` and the runtime is forced to send its traffic through it:
+//! - Node: the shim routes `http` / `https` agents and `fetch` through the
+//!   proxy (`HSE_NETWORK_PROXY`)
+//! - Python: the standard `HTTP(S)_PROXY` variables, honoured by `urllib`,
+//!   `requests`, `httpx`, …
+//!
+//! The proxy only connects to hosts on the allowlist. Anything else gets a
+//! `403` and is recorded, so the run can be failed and the attempt reported.
+//!
+//! Supported proxy requests:
+//! - `CONNECT host:port` (HTTPS and any other tunnelled protocol)
+//! - Absolute-form plain HTTP (`GET http://host/path HTTP/1.1`)
+
+use anyhow::{bail, Context, Result};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command as TokioCommand;
+use tokio::task::JoinHandle;
+
+use crate::config::NetworkConfig;
+
+/// Upper bound for a proxy request head (request line + headers).
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+/// Proxy environment variables set for the runtime (and its subprocesses).
+const PROXY_ENV_VARS: [&str; 4] = ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"];
+
+/// A running egress proxy for one invocation.
+///
+/// The proxy stops when this value is dropped.
+pub struct NetworkSandbox {
+    addr: std::net::SocketAddr,
+    blocked: Arc<Mutex<Vec<String>>>,
+    handle: JoinHandle<()>,
+}
+
+impl NetworkSandbox {
+    /// Start the proxy on an ephemeral loopback port.
+    pub async fn start(cfg: &NetworkConfig) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .context("Failed to start network sandbox proxy")?;
+        let addr = listener
+            .local_addr()
+            .context("Failed to read network sandbox proxy address")?;
+
+        let allow = Arc::new(Allowlist::new(&cfg.allow));
+        let blocked = Arc::new(Mutex::new(Vec::new()));
+
+        let blocked_clone = Arc::clone(&blocked);
+        let handle = tokio::spawn(async move {
+            while let Ok((client, _)) = listener.accept().await {
+                let allow = Arc::clone(&allow);
+                let blocked = Arc::clone(&blocked_clone);
+                tokio::spawn(async move {
+                    if let Err(e) = handle_client(client, &allow, &blocked).await {
+                        tracing::debug!("network sandbox connection error: {e:#}");
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            addr,
+            blocked,
+            handle,
+        })
+    }
+
+    /// Proxy URL handed to the runtime.
+    pub fn proxy_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Route the runtime's traffic through the proxy.
+    pub fn apply(&self, cmd: &mut TokioCommand) {
+        let url = self.proxy_url();
+
+        cmd.env("HSE_NETWORK_PROXY", &url);
+        for var in PROXY_ENV_VARS {
+            cmd.env(var, &url);
+        }
+
+        // Loopback must go through the proxy too
+        cmd.env("NO_PROXY", "").env("no_proxy", "");
+    }
+
+    /// Destinations (`host:port`) rejected so far, in order.
+    pub fn blocked(&self) -> Vec<String> {
+        self.blocked.lock().map(|b| b.clone()).unwrap_or_default()
+    }
+
+    /// Destinations rejected since the last call (used by warm workers,
+    /// whose proxy outlives a single invocation).
+    pub fn take_blocked(&self) -> Vec<String> {
+        self.blocked
+            .lock()
+            .map(|mut b| std::mem::take(&mut *b))
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for NetworkSandbox {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Failure message for one blocked destination.
+pub fn blocked_message(destination: &str) -> String {
+    format!(
+        "Network access blocked: {} (not in network.allow)",
+        destination
+    )
+}
+
+/* ---------------- allowlist ---------------- */
+
+/// Hosts the action may reach.
+///
+/// Entries are exact host names (`api.hubapi.com`) or wildcard suffixes
+/// (`*.hubapi.com`, which does not match `hubapi.com` itself).
+struct Allowlist {
+    hosts: Vec<String>,
+}
+
+impl Allowlist {
+    fn new(entries: &[String]) -> Self {
+        Self {
+            hosts: entries
+                .iter()
+                .map(|h| h.trim().to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    fn permits(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+        self.hosts
+            .iter()
+            .any(|entry| match entry.strip_prefix("*.") {
+                Some(suffix) => host
+                    .strip_suffix(suffix)
+                    .is_some_and(|rest| rest.ends_with('.')),
+                None => *entry == host,
+            })
+    }
+}
+
+/* ---------------- proxy ---------------- */
+
+async fn handle_client(
+    mut client: TcpStream,
+    allow: &Allowlist,
+    blocked: &Mutex<Vec<String>>,
+) -> Result<()> {
+    let (head, leftover) = read_head(&mut client).await?;
+    let head = String::from_utf8_lossy(&head).to_string();
+
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        bail!("Malformed proxy request line: {:?}", request_line);
+    };
+
+    let is_connect = method.eq_ignore_ascii_case("CONNECT");
+    let (host, port, path) = if is_connect {
+        let (host, port) = split_host_port(target, 443)?;
+        (host, port, String::new())
+    } else {
+        let url = reqwest::Url::parse(target)
+            .with_context(|| format!("Proxy request target is not a URL: {}", target))?;
+        if url.scheme() != "http" {
+            respond(
+                &mut client,
+                "501 Not Implemented",
+                "Only http:// URLs can be proxied without CONNECT",
+            )
+            .await?;
+            return Ok(());
+        }
+        let host = url.host_str().context("Proxy request URL has no host")?;
+        let path = match url.query() {
+            Some(q) => format!("{}?{}", url.path(), q),
+            None => url.path().to_string(),
+        };
+        (
+            host.trim_matches(|c| c == '[' || c == ']').to_string(),
+            url.port_or_known_default().unwrap_or(80),
+            path,
+        )
+    };
+
+    let destination = format!("{}:{}", host, port);
+
+    if !allow.permits(&host) {
+        tracing::debug!("network sandbox blocked {}", destination);
+        if let Ok(mut guard) = blocked.lock() {
+            guard.push(destination.clone());
+        }
+        respond(
+            &mut client,
+            "403 Forbidden",
+            &format!(
+                "hsemulate network sandbox: {} is not in network.allow",
+                destination
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut upstream = match TcpStream::connect((host.as_str(), port)).await {
+        Ok(s) => s,
+        Err(e) => {
+            respond(
+                &mut client,
+                "502 Bad Gateway",
+                &format!("{}: {}", destination, e),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if is_connect {
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+    } else {
+        // Rewrite to origin-form and force one request per connection, so a
+        // kept-alive socket cannot be reused for a different host.
+        let mut forwarded = format!("{} {} {}\r\n", method, path, version);
+        for line in lines.filter(|l| !l.is_empty()) {
+            let name = line.split(':').next().unwrap_or_default().trim();
+            if ["connection", "proxy-connection", "keep-alive"]
+                .iter()
+                .any(|h| name.eq_ignore_ascii_case(h))
+            {
+                continue;
+            }
+            forwarded.push_str(line);
+            forwarded.push_str("\r\n");
+        }
+        forwarded.push_str("Connection: close\r\n\r\n");
+        upstream.write_all(forwarded.as_bytes()).await?;
+    }
+
+    if !leftover.is_empty() {
+        upstream.write_all(&leftover).await?;
+    }
+
+    let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+    Ok(())
+}
+
+/// Read the request head; returns (head without the blank line, bytes after it).
+async fn read_head(stream: &mut TcpStream) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            bail!("Connection closed before the request head was complete");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let leftover = buf.split_off(pos + 4);
+            buf.truncate(pos);
+            return Ok((buf, leftover));
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            bail!("Proxy request head exceeds {} bytes", MAX_HEAD_BYTES);
+        }
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Split `host:port` / `[v6]:port`, falling back to `default_port`.
+fn split_host_port(target: &str, default_port: u16) -> Result<(String, u16)> {
+    if let Some(rest) = target.strip_prefix('[') {
+        let (host, after) = rest
+            .split_once(']')
+            .with_context(|| format!("Invalid CONNECT target: {}", target))?;
+        let port = match after.strip_prefix(':') {
+            Some(p) => p.parse().context("Invalid CONNECT port")?,
+            None => default_port,
+        };
+        return Ok((host.to_string(), port));
+    }
+
+    match target.rsplit_once(':') {
+        Some((host, port)) => Ok((
+            host.to_string(),
+            port.parse().context("Invalid CONNECT port")?,
+        )),
+        None => Ok((target.to_string(), default_port)),
+    }
+}
@@ -0,0 +1,223 @@
+// src/proctree.rs
+
+//! Linux process-tree accounting via `/proc`.
+//!
+//! Unlike the cross-platform `MemoryTracker` (which samples the RSS of a
+//! single PID), this tracker:
+//! - Walks the whole process tree rooted at the spawned runtime
+//! - Records each process's `VmHWM` (kernel-maintained RSS high-water mark),
+//!   so short spikes between samples are not lost
+//! - Reads user / system CPU time of the root at exit, including every
+//!   descendant the root waited for (`cutime` / `cstime`)
+//!
+//! The final sample must be taken after the root has exited but BEFORE it is
+//! reaped, so the zombie's `/proc/<pid>/stat` is still readable. Callers use
+//! [`wait_for_exit`] to block until that point.
+
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::thread;
+use std::time::Duration;
+
+/// Resource usage of a process tree.
+#[derive(Debug, Clone, Default)]
+pub struct TreeUsage {
+    /// `VmHWM` of the root process (KB)
+    pub root_hwm_kb: Option<u64>,
+
+    /// Sum of every process's `VmHWM` (KB).
+    ///
+    /// This is an upper bound when the processes did not peak at the same time.
+    pub tree_hwm_kb: Option<u64>,
+
+    pub cpu_user_ms: Option<u64>,
+    pub cpu_system_ms: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Highest `VmHWM` seen per PID (KB)
+    hwm_kb: HashMap<u32, u64>,
+
+    /// Last (utime, stime, cutime, cstime) of the root, in clock ticks
+    root_cpu: Option<(u64, u64, u64, u64)>,
+}
+
+/// Samples a process tree until stopped.
+pub struct ProcTreeTracker {
+    root: u32,
+    stop: Arc<AtomicBool>,
+    state: Arc<Mutex<State>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl ProcTreeTracker {
+    /// Start sampling the tree rooted at `root` every `sample_every`.
+    pub fn start(root: u32, sample_every: Duration) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let stop_clone = Arc::clone(&stop);
+        let state_clone = Arc::clone(&state);
+
+        let handle = thread::spawn(move || {
+            while !stop_clone.load(Ordering::Relaxed) {
+                if let Ok(mut guard) = state_clone.lock() {
+                    sample(root, &mut guard);
+                }
+                thread::sleep(sample_every);
+            }
+        });
+
+        Self {
+            root,
+            stop,
+            state,
+            handle: Some(handle),
+        }
+    }
+
+    /// Stop sampling, take a final sample and return the usage.
+    ///
+    /// For exact CPU figures the root must be a zombie at this point
+    /// (see [`wait_for_exit`]).
+    pub fn stop_and_take(mut self) -> TreeUsage {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+
+        let mut state = match self.state.lock() {
+            Ok(mut guard) => std::mem::take(&mut *guard),
+            Err(_) => return TreeUsage::default(),
+        };
+        sample(self.root, &mut state);
+
+        let tree_hwm_kb = if state.hwm_kb.is_empty() {
+            None
+        } else {
+            Some(state.hwm_kb.values().sum())
+        };
+
+        let ticks = clock_ticks_per_sec();
+        let to_ms = |t: u64| t * 1000 / ticks;
+
+        TreeUsage {
+            root_hwm_kb: state.hwm_kb.get(&self.root).copied(),
+            tree_hwm_kb,
+            cpu_user_ms: state.root_cpu.map(|(u, _, cu, _)| to_ms(u + cu)),
+            cpu_system_ms: state.root_cpu.map(|(_, s, _, cs)| to_ms(s + cs)),
+        }
+    }
+}
+
+/// Block until `pid` has exited, WITHOUT reaping it.
+///
+/// Leaves the process as a zombie so its final `/proc/<pid>/stat` can be read;
+/// the owner of the child handle reaps it afterwards.
+pub async fn wait_for_exit(pid: u32) {
+    let _ = tokio::task::spawn_blocking(move || {
+        // SAFETY: `siginfo_t` is plain data and `waitid` only writes into it.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        loop {
+            // SAFETY: valid pointer to a zeroed siginfo_t; WNOWAIT keeps the child waitable.
+            let rc = unsafe {
+                libc::waitid(
+                    libc::P_PID,
+                    pid as libc::id_t,
+                    &mut info,
+                    libc::WEXITED | libc::WNOWAIT,
+                )
+            };
+            if rc == 0 {
+                break;
+            }
+            if std::io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+                break;
+            }
+        }
+    })
+    .await;
+}
+
+/* ---------------- sampling ---------------- */
+
+fn sample(root: u32, state: &mut State) {
+    for pid in tree_pids(root) {
+        if let Some(hwm) = read_hwm_kb(pid) {
+            let entry = state.hwm_kb.entry(pid).or_insert(0);
+            if hwm > *entry {
+                *entry = hwm;
+            }
+        }
+
+        if pid == root {
+            if let Some(cpu) = read_cpu_ticks(pid) {
+                state.root_cpu = Some(cpu);
+            }
+        }
+    }
+}
+
+/// Root plus all live descendants (breadth-first).
+fn tree_pids(root: u32) -> Vec<u32> {
+    let mut out = vec![root];
+    let mut i = 0;
+
+    while i < out.len() {
+        let pid = out[i];
+        i += 1;
+
+        let Ok(tasks) = std::fs::read_dir(format!("/proc/{}/task", pid)) else {
+            continue;
+        };
+
+        for task in tasks.flatten() {
+            let Ok(raw) = std::fs::read_to_string(task.path().join("children")) else {
+                continue;
+            };
+            for child in raw.split_whitespace().filter_map(|s| s.parse::<u32>().ok()) {
+                if !out.contains(&child) {
+                    out.push(child);
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// `VmHWM` from `/proc/<pid>/status` in KB (absent for zombies).
+fn read_hwm_kb(pid: u32) -> Option<u64> {
+    let raw = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    raw.lines()
+        .find_map(|l| l.strip_prefix("VmHWM:"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|v| v.parse().ok())
+}
+
+/// (utime, stime, cutime, cstime) from `/proc/<pid>/stat`, in clock ticks.
+fn read_cpu_ticks(pid: u32) -> Option<(u64, u64, u64, u64)> {
+    let raw = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // comm (field 2) may contain spaces; everything after the last ')' is fixed-format
+    let rest = &raw[raw.rfind(')')? + 1..];
+    let fields = rest.split_whitespace().collect::<Vec<_>>();
+
+    // fields[0] is `state` (field 3), so utime (field 14) is fields[11]
+    let get = |i: usize| fields.get(i).and_then(|v| v.parse::<u64>().ok());
+    Some((get(11)?, get(12)?, get(13)?, get(14)?))
+}
+
+fn clock_ticks_per_sec() -> u64 {
+    // SAFETY: sysconf has no preconditions.
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as u64
+    } else {
+        100
+    }
+}
@@ -1,81 +1,62 @@
 // src/promote.rs
 
+use crate::audit;
+use crate::bundle;
 use crate::config::Config;
+use crate::diff;
+use crate::fields::{self, ActionFields, FieldChange};
+use crate::history;
+use crate::hubspot;
+use crate::selector;
+use crate::typescript;
 use crate::util::read_to_string;
 
 use anyhow::{bail, Context, Result};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use sha2::{Digest, Sha256};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use crate::types::PromoteSelector;
 use serde_json::json;
 
-const HUBSPOT_BASE_URL: &str = "https://api.hubapi.com";
+const DEFAULT_HUBSPOT_BASE_URL: &str = "https://api.hubapi.com";
+
+/// Flags of `hsemulate promote`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PromoteOptions {
+    /// Skip test gates and take over unmarked actions
+    pub force: bool,
+    /// Show the remote → local diff before promoting
+    pub diff: bool,
+    /// Do not ask for confirmation after the diff
+    pub yes: bool,
+}
 
-/// Entry point for `hsemulate promote <target> [--force]`.
-pub async fn handle(target: String, force: bool, config_path: PathBuf) -> Result<()> {
-    // 1) Load cicd.yaml first
-    let cicd = load_cicd_config(Path::new(".hsemulator/cicd.yaml"))
-        .context("Failed to load .hsemulator/cicd.yaml")?;
+/// Entry point for `hsemulate promote <target> [--force] [--diff [--yes]]`.
+pub async fn handle(target: String, opts: PromoteOptions, config_path: PathBuf) -> Result<()> {
+    let force = opts.force;
 
-    if cicd.version != 1 {
-        bail!(
-            "Unsupported cicd.yaml version: {} (expected 1)",
-            cicd.version
-        );
-    }
+    // 1) Load cicd.yaml first
+    let cicd = load_cicd()?;
 
     // 2) Resolve HUBSPOT_TOKEN (env preferred; yaml allowed)
-    let token_from_env = std::env::var("HUBSPOT_TOKEN").ok();
-
-    let token = match token_from_env {
-        Some(v) => v,
-        None => {
-            let t = cicd
-                .hubspot
-                .as_ref()
-                .and_then(|h| h.token.as_ref())
-                .map(|s| s.to_string())
-                .ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "No HubSpot token available.\n\
-                        \n\
-                        Promotion requires a HubSpot private app token.\n\
-                        Provide it using ONE of the following:\n\
-                        • Environment variable (recommended):\n\
-                            export HUBSPOT_TOKEN=pat-...\n\
-                        • cicd.yaml (local only):\n\
-                            hubspot:\n\
-                                token: pat-...\n"
-                    )
-                })?;
-
-            eprintln!(
-                "WARNING: Using HubSpot token from cicd.yaml. This is insecure and should only be used locally."
-            );
-            t
-        }
-    };
+    let token = resolve_token(&cicd)?;
 
     // 3) Load target
-    let t = cicd.targets.get(&target).with_context(|| {
-        let available = cicd.targets.keys().cloned().collect::<Vec<_>>().join(", ");
-        format!(
-            "Target '{}' not found in cicd.yaml.\n\
-                Available targets: {}",
-            target, available
-        )
-    })?;
+    let t = get_target(&cicd, &target)?;
+    let base_url = resolve_base_url(&cicd, t)?;
 
     // In force mode, only selector + workflow ID are required (plus HUBSPOT_TOKEN).
     // In non-force mode, we also enforce last-test.json + safety constraints (if present).
     validate_target_minimum(t, force)?;
 
     // 3) If not forced, enforce test gate from .hsemulator/last-test.json
-    if !force {
+    let last_test = if force {
+        None
+    } else {
         let last = load_last_test(Path::new(".hsemulator/last-test.json")).with_context(|| {
             "Promotion is test-gated.\n\
             Missing .hsemulator/last-test.json.\n\
@@ -89,78 +70,159 @@ pub async fn handle(target: String, force: bool, config_path: PathBuf) -> Result
         })?;
 
         enforce_last_test(&last, t)?;
-    }
+        Some(last)
+    };
 
-    // 4) Load local action code to promote (from config.yaml -> action.entry)
-    let action_code = load_action_source(&config_path).with_context(|| {
-        format!(
-            "Failed to load action source via config at {:?}",
-            config_path
-        )
-    })?;
+    // 4) Load local action code to promote (from each config.yaml -> action.entry)
+    let mut planned = Vec::new();
+    for action in t.actions(&config_path) {
+        let local = load_action_source(&action.config).with_context(|| {
+            format!(
+                "Failed to load action source via config at {:?}",
+                action.config
+            )
+        })?;
+
+        // 5) Build hash + inject marker comment
+        // (TypeScript is hashed as written but uploaded as compiled JavaScript)
+        let hash = local.hash();
+        let upload_source = match &local.compiled {
+            Some(compiled) => keep_id_marker(strip_hash_marker(compiled), &local.source),
+            None => strip_hash_marker(&local.source),
+        };
+        let source = inject_hash_marker(&upload_source, &hash);
+        selector::check_source(action.selector, &source)?;
+
+        planned.push(PlannedAction {
+            action,
+            hash,
+            source,
+            fields: local.fields,
+            action_index: 0,
+            existing_hash: None,
+            field_changes: Vec::new(),
+        });
+    }
 
-    // 5) Build hash + inject marker comment
-    let canonical_source = strip_hash_marker(&action_code);
-    let hash = sha256_hex(canonical_source.as_bytes());
-    let promoted_source = inject_hash_marker(&canonical_source, &hash);
+    // Every action must have been tested as it is now
+    if let Some(last) = &last_test {
+        enforce_tested_code(last, &planned)?;
+    }
 
-    // 6) Fetch workflow (revision-safe)
+    // 6) Fetch workflow once (revision-safe)
     let client = reqwest::Client::new();
     let headers = hubspot_headers(&token)?;
 
-    let flow = hubspot_get_flow(&client, &headers, &t.workflow_id).await?;
+    let flow = hubspot_get_flow(&client, &headers, &base_url, &t.workflow_id).await?;
 
-    // 7) Locate target action deterministically
-    let action_index = find_target_action_index(&flow, &t.selector)?;
+    // 7) Locate every target action deterministically
+    for i in 0..planned.len() {
+        let action_index = selector::find(&flow, planned[i].action.selector)?;
+        if let Some(other) = planned[..i].iter().find(|p| p.action_index == action_index) {
+            bail!(
+                "Selectors {} and {} select the same action",
+                selector::describe(other.action.selector),
+                selector::describe(planned[i].action.selector)
+            );
+        }
+        planned[i].action_index = action_index;
+    }
 
-    // 8) Drift guard (checksum comment) — fail in non-force if mismatch; warn otherwise
-    {
-        let existing_source = get_action_source_code(&flow, action_index)?;
-        if let Some(existing_hash) = extract_hash_marker(&existing_source) {
-            if existing_hash == hash {
-                eprintln!(
-                    "Action already up to date (hash {}). No changes required.",
-                    hash
-                );
-                return Ok(());
+    // 8) Drift guard (checksum comment) — fail in non-force if any action is unmarked
+    for p in &mut planned {
+        let existing_source = get_action_source_code(&flow, p.action_index)?;
+        p.existing_hash = extract_hash_marker(&existing_source);
+        p.field_changes = p.fields.changes(&flow["actions"][p.action_index]);
+        match &p.existing_hash {
+            Some(existing_hash) if *existing_hash == p.hash => {
+                if !p.field_changes.is_empty() {
+                    eprintln!("Updating fields of action {}", p.action.label());
+                }
             }
-
             // Hash differs → this is a normal promotion update
-            eprintln!("Updating action: {} → {}", existing_hash, hash);
-        } else {
+            Some(existing_hash) => eprintln!(
+                "Updating action {}: {} → {}",
+                p.action.label(),
+                existing_hash,
+                p.hash
+            ),
             // No marker = unknown origin
-            if !force {
-                bail!(
-                    "Refusing to overwrite action.\n\
-                    \n\
-                    Reason: The target CUSTOM_CODE action does not appear to be managed by hsemulator\n\
-                    (missing hsemulator-sha marker).\n\
-                    \n\
-                    This usually means the action was:\n\
-                    • Created manually in HubSpot, or\n\
-                    • Managed by another tool or user\n\
-                    \n\
-                    To take ownership anyway, re-run with:\n\
-                    hsemulate promote <target> --force"
+            None => {
+                if !force {
+                    bail!(
+                        "Refusing to overwrite action {}.\n\
+                        \n\
+                        Reason: The target CUSTOM_CODE action does not appear to be managed by hsemulator\n\
+                        (missing hsemulator-sha marker).\n\
+                        \n\
+                        This usually means the action was:\n\
+                        • Created manually in HubSpot, or\n\
+                        • Managed by another tool or user\n\
+                        \n\
+                        To take ownership anyway, re-run with:\n\
+                        hsemulate promote <target> --force",
+                        p.action.label()
+                    );
+                }
+                eprintln!(
+                    "WARNING: Overwriting action {} with no hash marker due to --force.",
+                    p.action.label()
                 );
             }
-            eprintln!("WARNING: Overwriting action with no hash marker due to --force.");
         }
     }
 
+    let changed: Vec<&PlannedAction> = planned.iter().filter(|p| p.is_changed()).collect();
+    if changed.is_empty() {
+        match planned.as_slice() {
+            [p] => eprintln!(
+                "Action already up to date (hash {}). No changes required.",
+                p.hash
+            ),
+            _ => eprintln!(
+                "All {} actions already up to date. No changes required.",
+                planned.len()
+            ),
+        }
+        return Ok(());
+    }
+
+    // Secrets must exist before an action can reference them
+    check_secrets(&cicd, &flow, &changed, force)?;
+
     // 9) Apply mutation (sourceCode [+ runtime if specified]) and PUT with revision guard
     let dry_run = t.deploy.as_ref().and_then(|d| d.dry_run).unwrap_or(false);
 
-    let runtime_to_set = t.runtime.clone(); // optional (force mode does not require runtime)
+    if opts.diff {
+        for p in &changed {
+            print_diff(&flow, &t.workflow_id, p)?;
+        }
+        if !dry_run && !opts.yes && !confirm(&format!("Promote to '{}'?", target))? {
+            bail!("Promotion cancelled");
+        }
+    }
 
-    let updated_flow = build_updated_flow_payload(
-        &flow,
-        action_index,
-        &promoted_source,
-        runtime_to_set.as_deref(),
-    )?;
+    // Every changed action goes out in the same PUT
+    let updates: Vec<ActionUpdate> = changed
+        .iter()
+        .map(|p| ActionUpdate {
+            action_index: p.action_index,
+            source: &p.source,
+            runtime: p.action.runtime,
+            fields: Some(&p.fields),
+        })
+        .collect();
+
+    let actions_report: Vec<JsonValue> = planned.iter().map(PlannedAction::report).collect();
+    // Single-action targets keep reporting their hash at the top level
+    let new_hash = match planned.as_slice() {
+        [p] => Some(p.hash.clone()),
+        _ => None,
+    };
 
     if dry_run {
+        // Still catches payload errors
+        build_updated_flow_payload(&flow, &updates)?;
         eprintln!(
             "Dry-run enabled (cicd.yaml deploy.dry_run: true). No changes will be sent to HubSpot."
         );
@@ -171,52 +233,139 @@ pub async fn handle(target: String, force: bool, config_path: PathBuf) -> Result
                 "dry_run": true,
                 "target": target,
                 "workflow_id": t.workflow_id,
-                "selector": {
-                    "type": t.selector.selector_type,
-                    "value": t.selector.value,
-                },
-                "new_hash": hash,
-                "action_index": action_index,
+                "new_hash": new_hash,
+                "actions": actions_report,
             }))?
         );
         return Ok(());
     }
 
-    let put_result = hubspot_put_flow(&client, &headers, &t.workflow_id, &updated_flow).await?;
-
-    // 10) Output success summary (machine readable)
+    // Re-applied on top of concurrent edits elsewhere in the workflow
+    let update =
+        hubspot_update_actions(&client, &headers, &base_url, &t.workflow_id, flow, &updates)
+            .await?;
+
+    // 10) Keep the replaced flow for `hsemulate rollback`
+    let history_id = history::record_or_warn(&history::Deployment {
+        kind: history::Kind::Promote,
+        target: &target,
+        workflow_id: &t.workflow_id,
+        action_indices: &update.action_indices,
+        flow_before: &update.flow_before,
+        flow_after: &update.flow_after,
+        put_result: &update.put_result,
+    });
+
+    // 11) Audit trail (who / what / from which commit)
+    let audited = audit::record(&audit::Event {
+        origin: audit::Origin::Cli,
+        kind: history::Kind::Promote,
+        target: Some(&target),
+        workflow_id: &t.workflow_id,
+        actions: changed
+            .iter()
+            .zip(&update.action_indices)
+            .map(|(p, &action_index)| audit::AuditAction {
+                action_index,
+                old_hash: p.existing_hash.clone(),
+                new_hash: Some(p.hash.clone()),
+            })
+            .collect(),
+        revision_id_before: update
+            .flow_before
+            .get("revisionId")
+            .cloned()
+            .unwrap_or(JsonValue::Null),
+        revision_id_after: update
+            .put_result
+            .get("revisionId")
+            .cloned()
+            .unwrap_or(JsonValue::Null),
+        force,
+        tests: test_summary(),
+        user: None,
+        claimed_actor: None,
+        history_id: history_id.as_deref(),
+    });
+
+    // 12) Output success summary (machine readable), even when the audit
+    // failed: the workflow has changed either way
     println!(
         "{}",
         serde_json::to_string_pretty(&serde_json::json!({
             "ok": true,
             "target": target,
             "workflow_id": t.workflow_id,
-            "new_hash": hash,
-            "revision_id_before": flow.get("revisionId").cloned().unwrap_or(JsonValue::Null),
-            "revision_id_after": put_result.get("revisionId").cloned().unwrap_or(JsonValue::Null),
+            "new_hash": new_hash,
+            "actions": actions_report,
+            "revision_id_before": update.flow_before.get("revisionId").cloned().unwrap_or(JsonValue::Null),
+            "revision_id_after": update.put_result.get("revisionId").cloned().unwrap_or(JsonValue::Null),
+            "history_id": history_id,
+            "audit_failed": audited.is_err(),
         }))?
     );
 
-    Ok(())
+    audited.context("Promotion succeeded but the audit log could not be written")
+}
+
+/// One action of a promotion, once located in the flow.
+struct PlannedAction<'a> {
+    action: TargetAction<'a>,
+    /// Local hash (carried by the marker in `source`)
+    hash: String,
+    /// Code to upload, marker included
+    source: String,
+    /// Secrets and fields declared in the config
+    fields: ActionFields,
+    action_index: usize,
+    /// Hash marker of the deployed code
+    existing_hash: Option<String>,
+    /// Declared fields differing from the deployed action
+    field_changes: Vec<FieldChange>,
+}
+
+impl PlannedAction<'_> {
+    fn is_changed(&self) -> bool {
+        self.existing_hash.as_deref() != Some(self.hash.as_str()) || !self.field_changes.is_empty()
+    }
+
+    fn report(&self) -> JsonValue {
+        json!({
+            "config": self.action.config,
+            "selector": {
+                "type": self.action.selector.selector_type,
+                "value": self.action.selector.value,
+            },
+            "action_index": self.action_index,
+            "old_hash": self.existing_hash,
+            "new_hash": self.hash,
+            "changed": self.is_changed(),
+            "fields_changed": self.field_changes.iter().map(|c| c.key).collect::<Vec<_>>(),
+        })
+    }
 }
 
 /* ---------------- config models ---------------- */
 
 #[derive(Debug, Deserialize)]
-struct CicdConfig {
+pub(crate) struct CicdConfig {
     version: u32,
-    targets: BTreeMap<String, CicdTarget>,
+    pub(crate) targets: BTreeMap<String, CicdTarget>,
 
     #[serde(default)]
     hubspot: Option<CicdHubSpot>,
 }
 
 #[derive(Debug, Deserialize)]
-struct CicdTarget {
+pub(crate) struct CicdTarget {
     // Optional in schema but required for promotion always (both modes).
-    workflow_id: String,
+    pub(crate) workflow_id: String,
 
-    selector: CicdSelector,
+    // Either one selector (code from --config) or several `actions`
+    #[serde(default)]
+    selector: Option<PromoteSelector>,
+    #[serde(default)]
+    actions: Vec<CicdAction>,
 
     // Optional: in force mode user said "nothing else required"
     runtime: Option<String>,
@@ -224,16 +373,70 @@ struct CicdTarget {
     safety: Option<CicdSafety>,
     deploy: Option<CicdDeploy>,
 
-    #[allow(dead_code)]
-    portal: Option<String>,
+    // Overrides hubspot.base_url for this target
+    base_url: Option<String>,
 }
 
+/// One of several custom code actions promoted together.
 #[derive(Debug, Deserialize)]
-struct CicdSelector {
-    #[serde(rename = "type")]
-    selector_type: String,
-    value: String,
-    require_unique: Option<bool>,
+struct CicdAction {
+    selector: PromoteSelector,
+    /// Action config.yaml providing the code
+    config: PathBuf,
+    /// Overrides the target's `runtime`
+    runtime: Option<String>,
+}
+
+/// A custom code action of a target, with the config providing its code.
+#[derive(Debug)]
+pub(crate) struct TargetAction<'a> {
+    pub(crate) selector: &'a PromoteSelector,
+    pub(crate) config: PathBuf,
+    pub(crate) runtime: Option<&'a str>,
+}
+
+impl TargetAction<'_> {
+    /// e.g. `marker 'enrich-deal' (actions/enrich/config.yaml)`
+    pub(crate) fn label(&self) -> String {
+        format!(
+            "{} ({})",
+            selector::describe(self.selector),
+            self.config.display()
+        )
+    }
+}
+
+impl CicdTarget {
+    /// Selector and runtime, unless the target has several `actions`.
+    pub(crate) fn single_selector(&self) -> Option<(&PromoteSelector, Option<&str>)> {
+        self.selector.as_ref().map(|s| (s, self.runtime.as_deref()))
+    }
+
+    /// The target's own `base_url`, if set.
+    pub(crate) fn base_url(&self) -> Option<&str> {
+        self.base_url.as_deref()
+    }
+
+    /// The target's actions; a single `selector` takes its code from
+    /// `default_config` (`--config`).
+    pub(crate) fn actions(&self, default_config: &Path) -> Vec<TargetAction<'_>> {
+        match &self.selector {
+            Some(selector) => vec![TargetAction {
+                selector,
+                config: default_config.to_path_buf(),
+                runtime: self.runtime.as_deref(),
+            }],
+            None => self
+                .actions
+                .iter()
+                .map(|a| TargetAction {
+                    selector: &a.selector,
+                    config: a.config.clone(),
+                    runtime: a.runtime.as_deref().or(self.runtime.as_deref()),
+                })
+                .collect(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -242,6 +445,7 @@ struct CicdSafety {
     require_snapshot_match: Option<bool>,
     max_duration_ms: Option<u64>,
     max_memory_mb: Option<u64>,
+    min_coverage: Option<f64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -256,14 +460,137 @@ struct CicdDeploy {
 struct LastTestResult {
     ok: bool,
     snapshots_ok: bool,
+
+    // Older last-test.json files predate flakiness analysis
+    #[serde(default)]
+    flaky: bool,
+
     max_duration_ms: Option<u128>,
     max_memory_kb: Option<u64>,
+
+    // Only present when the last run used `hsemulate test --coverage`
+    #[serde(default)]
+    coverage_pct: Option<f64>,
+
+    // Older last-test.json files do not say what was tested
+    #[serde(default)]
+    configs: Vec<TestedConfig>,
+
     run_at: String,
 }
 
+/// A config covered by a test run, with the hash its code would promote as.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct TestedConfig {
+    pub(crate) config: String,
+    pub(crate) hash: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct CicdHubSpot {
     token: Option<String>,
+    base_url: Option<String>,
+
+    // Secret names known to exist in the portal (HubSpot has no API to list them)
+    #[serde(default)]
+    secrets: Vec<String>,
+}
+
+/* ---------------- cicd.yaml ---------------- */
+
+/// Load and version-check `.hsemulator/cicd.yaml`.
+pub(crate) fn load_cicd() -> Result<CicdConfig> {
+    let cicd = load_cicd_config(Path::new(".hsemulator/cicd.yaml"))
+        .context("Failed to load .hsemulator/cicd.yaml")?;
+
+    if cicd.version != 1 {
+        bail!(
+            "Unsupported cicd.yaml version: {} (expected 1)",
+            cicd.version
+        );
+    }
+
+    Ok(cicd)
+}
+
+/// HubSpot token from `HUBSPOT_TOKEN`, else (with a warning) from cicd.yaml.
+pub(crate) fn resolve_token(cicd: &CicdConfig) -> Result<String> {
+    let token_from_env = std::env::var("HUBSPOT_TOKEN").ok();
+
+    let token = match token_from_env {
+        Some(v) => v,
+        None => {
+            let t = cicd
+                .hubspot
+                .as_ref()
+                .and_then(|h| h.token.as_ref())
+                .map(|s| s.to_string())
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "No HubSpot token available.\n\
+                        \n\
+                        Talking to HubSpot requires a private app token.\n\
+                        Provide it using ONE of the following:\n\
+                        • Environment variable (recommended):\n\
+                            export HUBSPOT_TOKEN=pat-...\n\
+                        • cicd.yaml (local only):\n\
+                            hubspot:\n\
+                                token: pat-...\n"
+                    )
+                })?;
+
+            eprintln!(
+                "WARNING: Using HubSpot token from cicd.yaml. This is insecure and should only be used locally."
+            );
+            t
+        }
+    };
+
+    Ok(token)
+}
+
+/// HubSpot API base URL for a target: its `base_url`, then `hubspot.base_url`,
+/// then the `HUBSPOT_BASE_URL` environment variable, then the public API.
+pub(crate) fn resolve_base_url(cicd: &CicdConfig, t: &CicdTarget) -> Result<String> {
+    if let Some(url) = &t.base_url {
+        return normalize_base_url(url).context("Invalid cicd.yaml target.base_url");
+    }
+    if let Some(url) = cicd.hubspot.as_ref().and_then(|h| h.base_url.as_ref()) {
+        return normalize_base_url(url).context("Invalid cicd.yaml hubspot.base_url");
+    }
+    default_base_url()
+}
+
+/// Base URL when no cicd.yaml applies (`HUBSPOT_BASE_URL` or the public API).
+pub(crate) fn default_base_url() -> Result<String> {
+    match std::env::var("HUBSPOT_BASE_URL") {
+        Ok(url) if !url.trim().is_empty() => {
+            normalize_base_url(&url).context("Invalid HUBSPOT_BASE_URL")
+        }
+        _ => Ok(DEFAULT_HUBSPOT_BASE_URL.to_string()),
+    }
+}
+
+pub(crate) fn normalize_base_url(url: &str) -> Result<String> {
+    let url = url.trim().trim_end_matches('/');
+    let parsed =
+        reqwest::Url::parse(url).with_context(|| format!("'{}' is not a valid URL", url))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        bail!("'{}' must be an http:// or https:// URL", url);
+    }
+    Ok(url.to_string())
+}
+
+/// Look up a promotion target by name.
+pub(crate) fn get_target<'a>(cicd: &'a CicdConfig, target: &str) -> Result<&'a CicdTarget> {
+    cicd.targets.get(target).with_context(|| {
+        let available = cicd.targets.keys().cloned().collect::<Vec<_>>().join(", ");
+        format!(
+            "Target '{}' not found in cicd.yaml.\n\
+                Available targets: {}",
+            target, available
+        )
+    })
 }
 
 /* ---------------- file loading ---------------- */
@@ -275,6 +602,18 @@ fn load_cicd_config(path: &Path) -> Result<CicdConfig> {
     Ok(cfg)
 }
 
+/// Summary of the last test run for the audit log, if there is one.
+pub(crate) fn test_summary() -> Option<JsonValue> {
+    let last = load_last_test(Path::new(".hsemulator/last-test.json")).ok()?;
+    Some(json!({
+        "ok": last.ok,
+        "snapshots_ok": last.snapshots_ok,
+        "flaky": last.flaky,
+        "coverage_pct": last.coverage_pct,
+        "run_at": last.run_at,
+    }))
+}
+
 fn load_last_test(path: &Path) -> Result<LastTestResult> {
     let raw = std::fs::read_to_string(path)
         .with_context(|| format!("Failed to read last test result at {:?}", path))?;
@@ -283,14 +622,53 @@ fn load_last_test(path: &Path) -> Result<LastTestResult> {
     Ok(parsed)
 }
 
+/// Local action code to promote.
+pub(crate) struct ActionSource {
+    /// Source as written, or the bundle of a multi-file JavaScript action
+    /// (covered by the hash marker)
+    pub(crate) source: String,
+    /// Compiled (and bundled) JavaScript for TypeScript actions (what gets
+    /// uploaded)
+    compiled: Option<String>,
+    /// Secrets and input/output fields declared next to `action.entry`
+    pub(crate) fields: ActionFields,
+}
+
+impl ActionSource {
+    /// Hash carried by the `hsemulator-sha` marker once promoted.
+    pub(crate) fn hash(&self) -> String {
+        sha256_hex(strip_hash_marker(&self.source).as_bytes())
+    }
+}
+
 /// Loads action source via config.yaml's `action.entry`.
-fn load_action_source(config_path: &Path) -> Result<String> {
+pub(crate) fn load_action_source(config_path: &Path) -> Result<ActionSource> {
     let cfg = Config::load(config_path)?;
     let action = cfg.action.as_ref().expect("config validated");
     let entry = PathBuf::from(&action.entry);
-    let code = read_to_string(&entry)
+    let mut source = read_to_string(&entry)
         .with_context(|| format!("Failed to read action.entry at {:?}", entry))?;
-    Ok(code)
+
+    // HubSpot takes a single file: local requires are inlined
+    let compiled = if typescript::is_typescript(&entry) {
+        let dir = tempfile::tempdir().context("Failed to create temp dir")?;
+        let out = typescript::compile(&cfg.runtime.typescript, &entry, dir.path(), false)?;
+        match bundle::build(&out)? {
+            Some(bundle) => Some(bundle.code),
+            None => Some(read_to_string(&out)?),
+        }
+    } else {
+        if let Some(bundle) = bundle::build(&entry)? {
+            source = bundle.code;
+        }
+        None
+    };
+
+    Ok(ActionSource {
+        source,
+        compiled,
+        fields: ActionFields::from_action(action),
+    })
 }
 
 /* ---------------- validation ---------------- */
@@ -301,14 +679,18 @@ fn validate_target_minimum(t: &CicdTarget, force: bool) -> Result<()> {
     }
 
     // Selector requirements (always required)
-    if t.selector.selector_type.trim().is_empty() || t.selector.value.trim().is_empty() {
-        bail!("cicd.yaml target.selector.type and target.selector.value must be set");
-    }
-    if t.selector.selector_type != "secret" {
-        bail!(
-            "Unsupported selector type '{}'. Only 'secret' is supported currently.",
-            t.selector.selector_type
-        );
+    match (&t.selector, t.actions.is_empty()) {
+        (Some(selector), true) => {
+            selector::validate(selector).context("Invalid cicd.yaml target.selector")?
+        }
+        (None, false) => {
+            for (i, action) in t.actions.iter().enumerate() {
+                selector::validate(&action.selector)
+                    .with_context(|| format!("Invalid cicd.yaml target.actions[{}].selector", i))?;
+            }
+        }
+        (Some(_), false) => bail!("cicd.yaml target must set either selector or actions, not both"),
+        (None, true) => bail!("cicd.yaml target.selector (or target.actions) must be set"),
     }
 
     // In non-force mode, require safety gates to be consistent if set
@@ -326,6 +708,33 @@ fn validate_target_minimum(t: &CicdTarget, force: bool) -> Result<()> {
     Ok(())
 }
 
+fn enforce_tested_code(last: &LastTestResult, planned: &[PlannedAction<'_>]) -> Result<()> {
+    let untested: Vec<String> = planned
+        .iter()
+        .filter(|p| !last.configs.iter().any(|c| c.hash == p.hash))
+        .map(|p| format!("  - {}", p.action.label()))
+        .collect();
+    if untested.is_empty() {
+        return Ok(());
+    }
+
+    bail!(
+        "Promotion blocked by safety gate.\n\
+ \n\
+ The last test run did not cover the current code of:\n\
+ {}\n\
+ Run time: {}\n\
+ \n\
+ Test every action of the target:\n\
+   hsemulate test\n\
+ \n\
+ Or bypass safety checks explicitly:\n\
+   hsemulate promote <target> --force",
+        untested.join("\n"),
+        last.run_at
+    );
+}
+
 fn enforce_last_test(last: &LastTestResult, t: &CicdTarget) -> Result<()> {
     let safety = t.safety.as_ref();
 
@@ -337,6 +746,22 @@ fn enforce_last_test(last: &LastTestResult, t: &CicdTarget) -> Result<()> {
         .and_then(|s| s.require_snapshot_match)
         .unwrap_or(true);
 
+    if require_clean && last.flaky {
+        bail!(
+            "Promotion blocked by safety gate.\n\
+ \n\
+ Last test run was FLAKY (repeated runs disagreed).\n\
+ Run time: {}\n\
+ \n\
+ Inspect the flakiness report from:\n\
+   hsemulate test\n\
+ \n\
+ Or bypass safety checks explicitly:\n\
+   hsemulate promote <target> --force",
+            last.run_at
+        );
+    }
+
     if require_clean && !last.ok {
         bail!(
             "Promotion blocked by safety gate.\n\
@@ -385,12 +810,30 @@ fn enforce_last_test(last: &LastTestResult, t: &CicdTarget) -> Result<()> {
         }
     }
 
+    if let Some(min_pct) = safety.and_then(|s| s.min_coverage) {
+        match last.coverage_pct {
+            None => bail!(
+                "Refusing to promote: safety min_coverage is {}% but the last test run has no coverage data\n\
+ \n\
+ Run:\n\
+   hsemulate test --coverage",
+                min_pct
+            ),
+            Some(actual) if actual < min_pct => bail!(
+                "Refusing to promote: line coverage {:.1}% is below safety min_coverage {}%",
+                actual,
+                min_pct
+            ),
+            Some(_) => {}
+        }
+    }
+
     Ok(())
 }
 
 /* ---------------- HubSpot HTTP ---------------- */
 
-fn hubspot_headers(token: &str) -> Result<HeaderMap> {
+pub(crate) fn hubspot_headers(token: &str) -> Result<HeaderMap> {
     let mut headers = HeaderMap::new();
 
     let auth_val = format!("Bearer {}", token);
@@ -403,119 +846,193 @@ fn hubspot_headers(token: &str) -> Result<HeaderMap> {
     Ok(headers)
 }
 
-async fn hubspot_get_flow(
+pub(crate) async fn hubspot_get_flow(
     client: &reqwest::Client,
     headers: &HeaderMap,
+    base_url: &str,
     workflow_id: &str,
 ) -> Result<JsonValue> {
-    let url = format!("{}/automation/v4/flows/{}", HUBSPOT_BASE_URL, workflow_id);
-    let resp = client
-        .get(url)
-        .headers(headers.clone())
-        .timeout(std::time::Duration::from_secs(30))
-        .send()
-        .await
-        .context("Failed to call HubSpot GET flow")?;
-
-    let status = resp.status();
-    let text = resp.text().await.unwrap_or_default();
+    hubspot_find_flow(client, headers, base_url, workflow_id)
+        .await?
+        .with_context(|| format!("HubSpot GET flow failed: workflow {} not found", workflow_id))
+}
 
-    if !status.is_success() {
-        bail!("HubSpot GET flow failed: {} {}", status, text);
-    }
+/// Like `hubspot_get_flow`, but `None` when the workflow does not exist.
+pub(crate) async fn hubspot_find_flow(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    base_url: &str,
+    workflow_id: &str,
+) -> Result<Option<JsonValue>> {
+    let url = format!("{}/automation/v4/flows/{}", base_url, workflow_id);
+    let result = hubspot::send("GET flow", || {
+        client
+            .get(&url)
+            .headers(headers.clone())
+            .timeout(std::time::Duration::from_secs(30))
+    })
+    .await;
+
+    let text = match result {
+        Ok(text) => text,
+        Err(e) if hubspot::error_kind(&e) == Some(hubspot::ErrorKind::NotFound) => return Ok(None),
+        Err(e) => return Err(e),
+    };
 
     let flow: JsonValue =
         serde_json::from_str(&text).context("HubSpot GET flow returned invalid JSON")?;
-    Ok(flow)
+    Ok(Some(flow))
 }
 
-async fn hubspot_put_flow(
+pub(crate) async fn hubspot_put_flow(
     client: &reqwest::Client,
     headers: &HeaderMap,
+    base_url: &str,
     workflow_id: &str,
     payload: &JsonValue,
 ) -> Result<JsonValue> {
-    let url = format!("{}/automation/v4/flows/{}", HUBSPOT_BASE_URL, workflow_id);
-    let resp = client
-        .put(url)
-        .headers(headers.clone())
-        .json(payload)
-        .timeout(std::time::Duration::from_secs(60))
-        .send()
-        .await
-        .context("Failed to call HubSpot PUT flow")?;
-
-    let status = resp.status();
-    let text = resp.text().await.unwrap_or_default();
-
-    if !status.is_success() {
-        bail!("HubSpot PUT flow failed: {} {}", status, text);
-    }
+    let url = format!("{}/automation/v4/flows/{}", base_url, workflow_id);
+    let text = hubspot::send("PUT flow", || {
+        client
+            .put(&url)
+            .headers(headers.clone())
+            .json(payload)
+            .timeout(std::time::Duration::from_secs(60))
+    })
+    .await?;
 
     let out: JsonValue =
         serde_json::from_str(&text).context("HubSpot PUT flow returned invalid JSON")?;
     Ok(out)
 }
 
-/* ---------------- action selection ---------------- */
+/// Conflicting PUTs retried before giving up.
+const MAX_CONFLICT_RETRIES: u32 = 3;
+
+/// Result of `hubspot_update_actions`.
+pub(crate) struct FlowUpdate {
+    /// Flow the successful PUT was based on
+    pub(crate) flow_before: JsonValue,
+    /// Payload that was PUT
+    pub(crate) flow_after: JsonValue,
+    /// HubSpot's response to the PUT
+    pub(crate) put_result: JsonValue,
+    /// Where the updated actions are in `flow_before`
+    pub(crate) action_indices: Vec<usize>,
+}
 
-fn find_target_action_index(flow: &JsonValue, selector: &CicdSelector) -> Result<usize> {
-    let actions = flow
-        .get("actions")
-        .and_then(|v| v.as_array())
-        .ok_or_else(|| anyhow::anyhow!("Workflow JSON missing 'actions' array"))?;
+/// PUT `updates` onto `flow`.
+///
+/// When the PUT hits a revision conflict (someone saved the workflow since
+/// it was fetched), the flow is re-fetched and the updates re-applied, but
+/// only if none of the updated actions changed in the meantime. Changes
+/// elsewhere in the workflow are kept.
+pub(crate) async fn hubspot_update_actions(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    base_url: &str,
+    workflow_id: &str,
+    flow: JsonValue,
+    updates: &[ActionUpdate<'_>],
+) -> Result<FlowUpdate> {
+    let original = flow;
+    let mut flow_before = original.clone();
+    let mut updates = updates.to_vec();
+    let mut conflicts = 0;
+
+    loop {
+        let flow_after = build_updated_flow_payload(&flow_before, &updates)?;
+        let err = match hubspot_put_flow(client, headers, base_url, workflow_id, &flow_after).await
+        {
+            Ok(put_result) => {
+                return Ok(FlowUpdate {
+                    flow_before,
+                    flow_after,
+                    action_indices: updates.iter().map(|u| u.action_index).collect(),
+                    put_result,
+                })
+            }
+            Err(e) => e,
+        };
 
-    let mut matches: Vec<usize> = Vec::new();
-    for (idx, a) in actions.iter().enumerate() {
-        let typ_ok = a.get("type").and_then(|v| v.as_str()) == Some("CUSTOM_CODE");
-        if !typ_ok {
-            continue;
+        if hubspot::error_kind(&err) != Some(hubspot::ErrorKind::Conflict)
+            || conflicts >= MAX_CONFLICT_RETRIES
+        {
+            return Err(err);
+        }
+        conflicts += 1;
+
+        let latest = hubspot_get_flow(client, headers, base_url, workflow_id).await?;
+        let mut applied = true;
+        for update in &mut updates {
+            let old_index = update.action_index;
+            let new_index = relocate_action(&original, &latest, old_index)?;
+            let before = &original["actions"][old_index];
+            let now = &latest["actions"][new_index];
+
+            // An earlier attempt may have gone through despite the error:
+            // every key the update writes must already have its value
+            let mut updated = now.clone();
+            apply_update(&mut updated, update)?;
+            let holds_update = updated == *now;
+            applied &= holds_update;
+            if now != before && !holds_update {
+                bail!(
+                    "Revision conflict: action {} of workflow {} was modified in HubSpot during the update.\n\
+                    Nothing was changed. Re-run to review the new state.",
+                    new_index,
+                    workflow_id
+                );
+            }
+            update.action_index = new_index;
         }
 
-        let empty: Vec<JsonValue> = Vec::new();
-
-        let secret_names = a
-            .get("secretNames")
-            .and_then(|v| v.as_array())
-            .unwrap_or(&empty)
-            .iter()
-            .filter_map(|v| v.as_str())
-            .collect::<Vec<_>>();
-
-        if secret_names.iter().any(|s| *s == selector.value.as_str()) {
-            matches.push(idx);
+        if applied {
+            eprintln!("Workflow {} already holds the update.", workflow_id);
+            return Ok(FlowUpdate {
+                flow_after: latest.clone(),
+                put_result: latest.clone(),
+                action_indices: updates.iter().map(|u| u.action_index).collect(),
+                flow_before: original,
+            });
         }
-    }
 
-    if matches.is_empty() {
-        bail!(
-            "No CUSTOM_CODE action found with secretNames containing '{}'",
-            selector.value
+        let revision = |flow: &JsonValue| match flow.get("revisionId") {
+            Some(JsonValue::String(id)) => id.clone(),
+            Some(id) => id.to_string(),
+            None => "?".to_string(),
+        };
+        eprintln!(
+            "Workflow {} changed since it was fetched (revision {} → {}); re-applying the update.",
+            workflow_id,
+            revision(&flow_before),
+            revision(&latest)
         );
+        flow_before = latest;
     }
+}
 
-    let require_unique = selector.require_unique.unwrap_or(true);
-    if require_unique && matches.len() != 1 {
-        bail!(
-            "Selector '{}' matched {} actions (require_unique=true). Refusing to proceed.",
-            selector.value,
-            matches.len()
-        );
-    }
+/// Index in `latest` of the action at `index` in `original`: same
+/// `actionId`, or the same index for actions without one.
+fn relocate_action(original: &JsonValue, latest: &JsonValue, index: usize) -> Result<usize> {
+    let latest_actions = latest
+        .get("actions")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| anyhow::anyhow!("Workflow JSON missing 'actions' array"))?;
 
-    // If require_unique=false, we still refuse for now (safety-first).
-    if matches.len() != 1 {
-        bail!(
-            "Selector '{}' matched {} actions. Refusing to proceed (must be exactly 1).",
-            selector.value,
-            matches.len()
-        );
+    match original.pointer(&format!("/actions/{}/actionId", index)) {
+        Some(id) => latest_actions
+            .iter()
+            .position(|a| a.get("actionId") == Some(id))
+            .ok_or_else(|| anyhow::anyhow!("Action {} was removed from the workflow", id)),
+        None if index < latest_actions.len() => Ok(index),
+        None => bail!("Action index {} was removed from the workflow", index),
     }
-
-    Ok(matches[0])
 }
 
-fn get_action_source_code(flow: &JsonValue, action_index: usize) -> Result<String> {
+/* ---------------- action selection ---------------- */
+
+pub(crate) fn get_action_source_code(flow: &JsonValue, action_index: usize) -> Result<String> {
     let actions = flow
         .get("actions")
         .and_then(|v| v.as_array())
@@ -533,41 +1050,170 @@ fn get_action_source_code(flow: &JsonValue, action_index: usize) -> Result<Strin
     Ok(source.to_string())
 }
 
+/* ---------------- diff ---------------- */
+
+/// Print what promotion would change in the target action, to STDERR
+/// (STDOUT stays machine readable).
+fn print_diff(flow: &JsonValue, workflow_id: &str, planned: &PlannedAction) -> Result<()> {
+    let use_color = std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal();
+    let action_index = planned.action_index;
+    let existing_source = get_action_source_code(flow, action_index)?;
+
+    eprintln!("Action {}:", planned.action.label());
+    let patch = diff::unified(
+        &existing_source,
+        &planned.source,
+        &format!("hubspot (workflow {})", workflow_id),
+        &format!("local ({})", planned.action.config.display()),
+        use_color,
+    );
+    if patch.is_empty() {
+        eprintln!("Source code: no changes");
+    } else {
+        eprint!("{}", patch);
+    }
+
+    if let Some(new_runtime) = planned.action.runtime {
+        let current = flow
+            .pointer(&format!("/actions/{}/runtime", action_index))
+            .and_then(|v| v.as_str())
+            .unwrap_or("(unset)");
+        if current != new_runtime {
+            eprintln!("Runtime: {} → {}", current, new_runtime);
+        }
+    }
+
+    for change in &planned.field_changes {
+        eprint!(
+            "{}",
+            diff::unified(
+                &change.deployed,
+                &change.declared,
+                &format!("hubspot {}", change.key),
+                &format!("local {}", change.key),
+                use_color,
+            )
+        );
+    }
+
+    Ok(())
+}
+
+/// Refuse to reference secrets the portal is not known to have: those used
+/// by any custom code action of the flow, or listed in `hubspot.secrets`.
+fn check_secrets(
+    cicd: &CicdConfig,
+    flow: &JsonValue,
+    changed: &[&PlannedAction],
+    force: bool,
+) -> Result<()> {
+    let mut known = fields::flow_secrets(flow);
+    if let Some(hubspot) = &cicd.hubspot {
+        known.extend(hubspot.secrets.iter().cloned());
+    }
+
+    let missing: BTreeSet<&str> = changed
+        .iter()
+        .flat_map(|p| p.fields.secrets())
+        .filter(|name| !known.contains(*name))
+        .map(|name| name.as_str())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let names = missing.into_iter().collect::<Vec<_>>().join(", ");
+    if !force {
+        bail!(
+            "Secrets not found in the portal: {}\n\
+            \n\
+            Create them in HubSpot (workflow custom code action → Secrets), then either\n\
+            list them under hubspot.secrets in cicd.yaml or re-run with --force.",
+            names
+        );
+    }
+    eprintln!(
+        "WARNING: Referencing secrets not known to exist in the portal due to --force: {}",
+        names
+    );
+    Ok(())
+}
+
+/// New code (and optionally runtime) for one action of a flow.
+#[derive(Clone, Copy)]
+pub(crate) struct ActionUpdate<'a> {
+    pub(crate) action_index: usize,
+    pub(crate) source: &'a str,
+    pub(crate) runtime: Option<&'a str>,
+    /// Secrets and input/output fields to write, if any
+    pub(crate) fields: Option<&'a ActionFields>,
+}
+
+/// Ask a yes/no question on the terminal; defaults to no.
+fn confirm(question: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        bail!(
+            "Confirmation required but STDIN is not a terminal.\n\
+            Re-run with --yes to promote without prompting."
+        );
+    }
+
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read confirmation")?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
 /* ---------------- payload build ---------------- */
 
-fn build_updated_flow_payload(
+/// Write the keys of `update` into a HubSpot action object.
+fn apply_update(action: &mut JsonValue, update: &ActionUpdate<'_>) -> Result<()> {
+    let Some(obj) = action.as_object_mut() else {
+        bail!("Target action is not an object");
+    };
+
+    // Replace source code
+    obj.insert(
+        "sourceCode".to_string(),
+        JsonValue::String(update.source.to_string()),
+    );
+
+    // Only set runtime if specified in cicd.yaml (optional in force mode)
+    if let Some(rt) = update.runtime {
+        obj.insert("runtime".to_string(), JsonValue::String(rt.to_string()));
+    }
+
+    // Only the keys declared in config.yaml
+    if let Some(fields) = update.fields {
+        fields.apply(obj);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn build_updated_flow_payload(
     flow: &JsonValue,
-    action_index: usize,
-    new_source: &str,
-    runtime_override: Option<&str>,
+    updates: &[ActionUpdate],
 ) -> Result<JsonValue> {
     // Clone full flow first (we will sanitize afterward)
     let mut flow_mut = flow.clone();
 
-    // Update the action within actions[]
+    // Update each action within actions[]
     {
         let actions = flow_mut
             .get_mut("actions")
             .and_then(|v| v.as_array_mut())
             .ok_or_else(|| anyhow::anyhow!("Workflow JSON missing 'actions' array"))?;
 
-        let a = actions
-            .get_mut(action_index)
-            .ok_or_else(|| anyhow::anyhow!("Action index {} out of bounds", action_index))?;
-
-        // Replace source code
-        if let Some(obj) = a.as_object_mut() {
-            obj.insert(
-                "sourceCode".to_string(),
-                JsonValue::String(new_source.to_string()),
-            );
-
-            // Only set runtime if specified in cicd.yaml (optional in force mode)
-            if let Some(rt) = runtime_override {
-                obj.insert("runtime".to_string(), JsonValue::String(rt.to_string()));
-            }
-        } else {
-            bail!("Target action is not an object");
+        for update in updates {
+            let a = actions.get_mut(update.action_index).ok_or_else(|| {
+                anyhow::anyhow!("Action index {} out of bounds", update.action_index)
+            })?;
+            apply_update(a, update)?;
         }
     }
 
@@ -623,7 +1269,7 @@ fn build_updated_flow_payload(
 
 /* ---------------- hashing marker ---------------- */
 
-fn sha256_hex(bytes: &[u8]) -> String {
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
     let mut hasher = Sha256::new();
     hasher.update(bytes);
     let out = hasher.finalize();
@@ -653,7 +1299,18 @@ fn inject_hash_marker(source: &str, hash: &str) -> String {
     format!("{}{}", comment, source)
 }
 
-fn extract_hash_marker(source: &str) -> Option<String> {
+/// Compilers drop ordinary comments (esbuild always does), so carry the
+/// TypeScript source's `hsemulator-id` marker over to the compiled code.
+fn keep_id_marker(compiled: String, source: &str) -> String {
+    match selector::marker_of(source) {
+        Some(id) if selector::marker_of(&compiled).is_none() => {
+            format!("// hsemulator-id: {}\n{}", id, compiled)
+        }
+        _ => compiled,
+    }
+}
+
+pub(crate) fn extract_hash_marker(source: &str) -> Option<String> {
     for line in source.lines().take(10) {
         let line = line.trim();
         if let Some(rest) = line.strip_prefix("# hsemulator-sha: ") {
@@ -666,7 +1323,7 @@ fn extract_hash_marker(source: &str) -> Option<String> {
     None
 }
 
-fn strip_hash_marker(source: &str) -> String {
+pub(crate) fn strip_hash_marker(source: &str) -> String {
     source
         .lines()
         .filter(|line| {
@@ -698,40 +1355,50 @@ fn replace_hash_marker(source: &str, new_marker_line: &str) -> String {
     out.trim_end().to_string()
 }
 
+/// Workflow action an inline promotion deploys to.
+#[derive(Clone, Copy)]
+pub struct InlineTarget<'a> {
+    pub token: &'a str,
+    pub base_url: &'a str,
+    pub workflow_id: &'a str,
+    pub selector: &'a PromoteSelector,
+}
+
 pub async fn promote_inline(
-    token: &str,
-    workflow_id: &str,
-    selector: &PromoteSelector,
+    target: &InlineTarget<'_>,
     source_code: &str,
     runtime_override: Option<&str>,
     force: bool,
     dry_run: bool,
 ) -> Result<serde_json::Value> {
+    let InlineTarget {
+        token,
+        base_url,
+        workflow_id,
+        selector,
+    } = *target;
+
     let client = reqwest::Client::new();
     let headers = hubspot_headers(token)?;
 
+    selector::check_source(selector, source_code)?;
+
     // Canonicalize + hash
     let canonical = strip_hash_marker(source_code);
     let hash = sha256_hex(canonical.as_bytes());
     let promoted_source = inject_hash_marker(&canonical, &hash);
 
     // Fetch workflow
-    let flow = hubspot_get_flow(&client, &headers, workflow_id).await?;
+    let flow = hubspot_get_flow(&client, &headers, base_url, workflow_id).await?;
 
     // Locate action
-    let action_index = find_target_action_index(
-        &flow,
-        &CicdSelector {
-            selector_type: selector.selector_type.clone(),
-            value: selector.value.clone(),
-            require_unique: Some(true),
-        },
-    )?;
+    let action_index = selector::find(&flow, selector)?;
 
     // Drift guard
     let existing = get_action_source_code(&flow, action_index)?;
-    if let Some(existing_hash) = extract_hash_marker(&existing) {
-        if existing_hash == hash {
+    let previous_hash = extract_hash_marker(&existing);
+    if let Some(existing_hash) = &previous_hash {
+        if *existing_hash == hash {
             return Ok(json!({
                 "ok": true,
                 "status": "noop",
@@ -743,12 +1410,13 @@ pub async fn promote_inline(
     }
 
     // Build payload
-    let updated_flow = build_updated_flow_payload(
-        &flow,
+    let updates = [ActionUpdate {
         action_index,
-        &promoted_source,
-        runtime_override,
-    )?;
+        source: &promoted_source,
+        runtime: runtime_override,
+        fields: None,
+    }];
+    build_updated_flow_payload(&flow, &updates)?;
 
     if dry_run {
         return Ok(json!({
@@ -760,12 +1428,16 @@ pub async fn promote_inline(
         }));
     }
 
-    let result = hubspot_put_flow(&client, &headers, workflow_id, &updated_flow).await?;
+    let update =
+        hubspot_update_actions(&client, &headers, base_url, workflow_id, flow, &updates).await?;
 
     Ok(json!({
         "ok": true,
         "workflow_id": workflow_id,
         "hash": hash,
-        "revision_id": result.get("revisionId")
+        "previous_hash": previous_hash,
+        "action_index": update.action_indices[0],
+        "revision_id_before": update.flow_before.get("revisionId"),
+        "revision_id": update.put_result.get("revisionId")
     }))
 }
@@ -0,0 +1,365 @@
+// src/protocol.rs
+
+//! Shim protocol (version 1).
+//!
+//! Shims report to the runner over a dedicated channel instead of STDOUT,
+//! so an action writing to `process.stdout` / `sys.__stdout__` can no longer
+//! corrupt its result. The channel carries one JSON message per line:
+//!
+//! ```text
+//! {"v":1,"type":"log","level":"warn","ts":1718000000000,"message":"..."}
+//! {"v":1,"type":"callback","payload":{"outputFields":{}}}
+//! {"v":1,"type":"error","error":{"type":"action","message":"...","stack":"..."}}
+//! {"v":1,"type":"phase","name":"execute","duration_ms":12.4}
+//! {"v":1,"type":"result","output":{"ok":true,"language":"node","outputFields":{}}}
+//! ```
+//!
+//! `result` is always the last message of an invocation. Warm workers add
+//! the request `id` to every message.
+//!
+//! On Unix the channel is file descriptor 3, advertised to the shim as
+//! `HSE_PROTOCOL_FD`. Elsewhere shims fall back to STDOUT, and lines that
+//! are not protocol messages are treated as action output.
+//!
+//! Whatever the action writes straight to STDOUT / STDERR is kept as
+//! untagged log lines.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{ChildStderr, ChildStdout, Command as TokioCommand};
+
+use crate::logs::{LogLevel, LogLine};
+
+/// Protocol version spoken by the bundled shims.
+pub const PROTOCOL_VERSION: u64 = 1;
+
+/// Env var telling the shim which fd to write protocol messages to.
+const PROTOCOL_FD_ENV: &str = "HSE_PROTOCOL_FD";
+
+#[cfg(unix)]
+const PROTOCOL_FD: i32 = 3;
+
+/* ---------------- messages ---------------- */
+
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(flatten)]
+    message: Message,
+}
+
+/// One typed message from a shim.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Message {
+    Log {
+        level: LogLevel,
+        #[serde(default)]
+        ts: Option<u64>,
+        message: String,
+    },
+    /// Node actions calling `callback(payload)`
+    Callback {
+        payload: Value,
+    },
+    /// Action exception or runtime failure (failed load, missing `main`)
+    Error {
+        error: Value,
+    },
+    /// Time spent in one phase of the invocation (`load`, `execute`)
+    Phase {
+        name: String,
+        duration_ms: f64,
+    },
+    Result {
+        output: Value,
+    },
+}
+
+/// One line read from the protocol channel.
+#[derive(Debug)]
+pub enum Line {
+    Message {
+        id: Option<u64>,
+        message: Message,
+    },
+    /// Not a protocol message (STDOUT fallback only)
+    Raw(String),
+}
+
+/// Parse one line from the protocol channel.
+pub fn parse(line: &str) -> Result<Line> {
+    let value = match serde_json::from_str::<Value>(line) {
+        Ok(value @ Value::Object(_)) if value.get("v").is_some() => value,
+        _ => return Ok(Line::Raw(line.to_string())),
+    };
+
+    let version = value.get("v").and_then(Value::as_u64);
+    if version != Some(PROTOCOL_VERSION) {
+        bail!(
+            "Unsupported shim protocol version {} (expected {})",
+            value["v"],
+            PROTOCOL_VERSION
+        );
+    }
+
+    let envelope: Envelope =
+        serde_json::from_value(value).context("Invalid shim protocol message")?;
+
+    Ok(Line::Message {
+        id: envelope.id,
+        message: envelope.message,
+    })
+}
+
+/* ---------------- transcript ---------------- */
+
+/// Everything a shim reported for one invocation.
+#[derive(Debug, Default)]
+pub struct Transcript {
+    pub logs: Vec<LogLine>,
+    /// Phase durations in milliseconds
+    pub phases: BTreeMap<String, f64>,
+    callback: Option<Value>,
+    error: Option<Value>,
+    result: Option<Value>,
+}
+
+impl Transcript {
+    /// Record one message; returns the new log line for `log` messages.
+    pub fn apply(&mut self, message: Message) -> Option<&LogLine> {
+        match message {
+            Message::Log { level, ts, message } => {
+                self.logs.push(LogLine::new(level, ts, message));
+                return self.logs.last();
+            }
+            Message::Callback { payload } => self.callback = Some(payload),
+            Message::Error { error } => self.error = Some(error),
+            Message::Phase { name, duration_ms } => {
+                self.phases.insert(name, duration_ms);
+            }
+            Message::Result { output } => self.result = Some(output),
+        }
+        None
+    }
+
+    /// Record a line written outside the protocol.
+    pub fn push_raw(&mut self, message: String) -> &LogLine {
+        self.logs.push(LogLine::raw(message));
+        &self.logs[self.logs.len() - 1]
+    }
+
+    /// Whether the `result` message has arrived.
+    pub fn is_complete(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// The shim's output in the shape used by assertions and snapshots:
+    /// the `result` payload plus `error` (and `callback` for Node).
+    ///
+    /// `None` if the shim never sent a result.
+    pub fn output(&self) -> Option<Value> {
+        let mut output = self.result.clone()?;
+
+        if let Some(obj) = output.as_object_mut() {
+            obj.insert(
+                "error".to_string(),
+                self.error.clone().unwrap_or(Value::Null),
+            );
+            if obj.get("language").and_then(Value::as_str) == Some("node") {
+                obj.insert(
+                    "callback".to_string(),
+                    self.callback.clone().unwrap_or(Value::Null),
+                );
+            }
+        }
+
+        Some(output)
+    }
+}
+
+/* ---------------- channel ---------------- */
+
+/// The protocol channel of one runtime process.
+pub struct Channel {
+    #[cfg(unix)]
+    reader: tokio::net::unix::pipe::Receiver,
+    #[cfg(unix)]
+    writer: std::os::fd::OwnedFd,
+}
+
+impl Channel {
+    /// Create the channel and hand it to `cmd` (as fd 3 on Unix).
+    #[cfg(unix)]
+    pub fn attach(cmd: &mut TokioCommand) -> Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let (sender, reader) =
+            tokio::net::unix::pipe::pipe().context("Failed to create shim protocol pipe")?;
+        let writer = sender
+            .into_blocking_fd()
+            .context("Failed to create shim protocol pipe")?;
+        let raw = writer.as_raw_fd();
+
+        // SAFETY: dup2 / fcntl are async-signal-safe and `raw` stays open
+        // until `open` runs after spawn.
+        unsafe {
+            cmd.pre_exec(move || {
+                if raw == PROTOCOL_FD {
+                    // Already in place; only the close-on-exec flag has to go
+                    let flags = libc::fcntl(raw, libc::F_GETFD);
+                    if flags < 0 || libc::fcntl(raw, libc::F_SETFD, flags & !libc::FD_CLOEXEC) < 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                } else if libc::dup2(raw, PROTOCOL_FD) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        cmd.env(PROTOCOL_FD_ENV, PROTOCOL_FD.to_string());
+
+        Ok(Self { reader, writer })
+    }
+
+    /// No dedicated fd: shims write protocol messages to STDOUT.
+    #[cfg(not(unix))]
+    pub fn attach(cmd: &mut TokioCommand) -> Result<Self> {
+        cmd.env_remove(PROTOCOL_FD_ENV);
+        Ok(Self {})
+    }
+
+    /// Start reading once the runtime has been spawned.
+    ///
+    /// Returns the protocol lines and the runtime's raw STDOUT, which is
+    /// `None` when STDOUT itself carries the protocol.
+    #[cfg(unix)]
+    pub fn open(self, stdout: ChildStdout) -> (Lines, Option<ChildStdout>) {
+        // Our copy of the write end must go, or EOF never arrives
+        drop(self.writer);
+        (Lines::new(self.reader), Some(stdout))
+    }
+
+    #[cfg(not(unix))]
+    pub fn open(self, stdout: ChildStdout) -> (Lines, Option<ChildStdout>) {
+        (Lines::new(stdout), None)
+    }
+}
+
+/// Line reader that keeps partial lines across cancelled reads, so it can
+/// be polled from `select!`.
+pub struct Lines {
+    reader: BufReader<Box<dyn AsyncRead + Unpin + Send>>,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl Lines {
+    pub fn new(reader: impl AsyncRead + Unpin + Send + 'static) -> Self {
+        Self {
+            reader: BufReader::new(Box::new(reader)),
+            buf: Vec::new(),
+            eof: false,
+        }
+    }
+
+    /// Next line without its line ending; `None` at EOF.
+    pub async fn next_line(&mut self) -> Option<String> {
+        if self.eof {
+            return None;
+        }
+
+        match self.reader.read_until(b'\n', &mut self.buf).await {
+            Ok(0) | Err(_) => {
+                self.eof = true;
+                None
+            }
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&self.buf)
+                    .trim_end_matches(['\r', '\n'])
+                    .to_string();
+                self.buf.clear();
+                Some(line)
+            }
+        }
+    }
+}
+
+/* ---------------- reading ---------------- */
+
+/// Read a cold runtime's protocol channel, STDOUT and STDERR until all of
+/// them close.
+///
+/// `on_log` sees every log line as it arrives.
+pub async fn collect(
+    channel: Channel,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+    mut on_log: impl FnMut(&LogLine),
+) -> Result<Transcript> {
+    let (mut messages, stdout) = channel.open(stdout);
+    let mut stdout = match stdout {
+        Some(stdout) => Lines::new(stdout),
+        None => Lines::new(tokio::io::empty()),
+    };
+    let mut stderr = Lines::new(stderr);
+    let mut transcript = Transcript::default();
+
+    loop {
+        tokio::select! {
+            Some(line) = messages.next_line() => match parse(&line)? {
+                Line::Message { message, .. } => {
+                    if let Some(log) = transcript.apply(message) {
+                        on_log(log);
+                    }
+                }
+                Line::Raw(text) => on_log(transcript.push_raw(text)),
+            },
+            Some(line) = stdout.next_line() => on_log(transcript.push_raw(line)),
+            Some(line) = stderr.next_line() => on_log(transcript.push_raw(line)),
+            else => break,
+        }
+    }
+
+    Ok(transcript)
+}
+
+/// Read a warm worker's protocol channel until the result for request `id`.
+///
+/// Log lines from earlier requests (e.g. timers still running) are kept;
+/// their other messages are stale and dropped.
+pub async fn collect_request(
+    messages: &mut Lines,
+    id: u64,
+    mut on_log: impl FnMut(&LogLine),
+) -> Result<Transcript> {
+    let mut transcript = Transcript::default();
+
+    while !transcript.is_complete() {
+        let line = messages
+            .next_line()
+            .await
+            .context("Warm worker exited before returning a result")?;
+
+        match parse(&line)? {
+            Line::Message {
+                id: msg_id,
+                message,
+            } => {
+                if msg_id != Some(id) && !matches!(message, Message::Log { .. }) {
+                    continue;
+                }
+                if let Some(log) = transcript.apply(message) {
+                    on_log(log);
+                }
+            }
+            Line::Raw(text) => on_log(transcript.push_raw(text)),
+        }
+    }
+
+    Ok(transcript)
+}
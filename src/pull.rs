@@ -0,0 +1,165 @@
+// src/pull.rs
+
+//! `hsemulate pull <target>`.
+//!
+//! Brings the action deployed in HubSpot back into the local tree, e.g.
+//! after a hot-fix made in the HubSpot UI. Each of the target's actions is
+//! located with its selector (as in `promote`), its `hsemulator-sha` marker
+//! is stripped, and the source is written to the `action.entry` of the
+//! matching config.
+//!
+//! Local edits are never lost silently: pulling over an entry with
+//! uncommitted changes requires `--force`, and a refused entry means no
+//! file is written.
+
+use crate::bundle;
+use crate::config::Config;
+use crate::promote;
+use crate::selector;
+use crate::typescript;
+use crate::util::read_to_string;
+
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Entry point for `hsemulate pull <target> [--force]`.
+pub async fn handle(target: String, force: bool, config_path: PathBuf) -> Result<()> {
+    let cicd = promote::load_cicd()?;
+    let token = promote::resolve_token(&cicd)?;
+    let t = promote::get_target(&cicd, &target)?;
+    let base_url = promote::resolve_base_url(&cicd, t)?;
+    let actions = t.actions(&config_path);
+
+    let mut entries = Vec::new();
+    for action in &actions {
+        let cfg = Config::load(&action.config)?;
+        let entry = PathBuf::from(&cfg.action.as_ref().expect("config validated").entry);
+
+        // The deployed code is build output for these, not the source
+        if typescript::is_typescript(&entry) {
+            bail!(
+                "Cannot pull into {}: HubSpot holds the compiled JavaScript of TypeScript actions,\n\
+                not the TypeScript source. Port the deployed change by hand.",
+                entry.display()
+            );
+        }
+        if entry.is_file() && bundle::build(&entry)?.is_some() {
+            bail!(
+                "Cannot pull into {}: it is a multi-file action and HubSpot holds the bundle.\n\
+                Port the deployed change by hand.",
+                entry.display()
+            );
+        }
+        entries.push(entry);
+    }
+
+    let client = reqwest::Client::new();
+    let headers = promote::hubspot_headers(&token)?;
+    let flow = promote::hubspot_get_flow(&client, &headers, &base_url, &t.workflow_id).await?;
+
+    // Work out every file first, so nothing is written if one is refused
+    let mut pulled = Vec::new();
+    for (action, entry) in actions.iter().zip(entries) {
+        let action_index = selector::find(&flow, action.selector)?;
+        let deployed = promote::get_action_source_code(&flow, action_index)?;
+
+        if bundle::is_bundle(&deployed) {
+            bail!(
+                "Cannot pull into {}: the deployed action is a bundle of a multi-file action.\n\
+                Port the deployed change by hand.",
+                entry.display()
+            );
+        }
+
+        let deployed_hash = promote::extract_hash_marker(&deployed);
+        let source = format!("{}\n", promote::strip_hash_marker(&deployed).trim_end());
+
+        let local = if entry.is_file() {
+            Some(read_to_string(&entry)?)
+        } else {
+            None
+        };
+        let changed = local.as_deref() != Some(source.as_str());
+
+        if changed && !force && has_local_edits(&entry)? {
+            bail!(
+                "Refusing to overwrite {}: it has uncommitted changes.\n\
+                \n\
+                Commit or stash them first, or overwrite anyway with:\n\
+                hsemulate pull {} --force",
+                entry.display(),
+                target
+            );
+        }
+
+        pulled.push((action, entry, source, changed, deployed_hash));
+    }
+
+    let mut report = Vec::new();
+    for (action, entry, source, changed, deployed_hash) in pulled {
+        if !changed {
+            eprintln!("{} already matches the deployed action.", entry.display());
+        } else {
+            if let Some(parent) = entry.parent() {
+                if !parent.as_os_str().is_empty() {
+                    std::fs::create_dir_all(parent)
+                        .with_context(|| format!("Failed to create directory {:?}", parent))?;
+                }
+            }
+            std::fs::write(&entry, &source)
+                .with_context(|| format!("Failed to write action.entry at {:?}", entry))?;
+            eprintln!("Pulled deployed action into {}", entry.display());
+        }
+
+        report.push(json!({
+            "config": action.config,
+            "entry": entry,
+            "changed": changed,
+            "deployed_hash": deployed_hash,
+        }));
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json!({
+            "ok": true,
+            "target": target,
+            "workflow_id": t.workflow_id,
+            "actions": report,
+        }))?
+    );
+
+    Ok(())
+}
+
+/// Whether `entry` has changes git does not have (modified, staged or
+/// untracked).
+///
+/// Outside a git work tree every existing file counts as a local edit.
+fn has_local_edits(entry: &Path) -> Result<bool> {
+    if !entry.exists() {
+        return Ok(false);
+    }
+
+    let dir = match entry.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file = entry.file_name().context("Action entry has no file name")?;
+
+    let output = Command::new("git")
+        .arg("status")
+        .arg("--porcelain")
+        .arg("--")
+        .arg(file)
+        .current_dir(dir)
+        .output();
+
+    match output {
+        Ok(out) if out.status.success() => Ok(!out.stdout.is_empty()),
+        // git missing or not a repository
+        _ => Ok(true),
+    }
+}
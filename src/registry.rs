@@ -0,0 +1,232 @@
+// src/registry.rs
+
+//! Project and target registry of the HTTP runtime.
+//!
+//! Loaded from the file given to `hsemulate runtime --registry`, so clients
+//! can call `POST /projects/{project}/targets/{target}/promote` with only the
+//! code: the workflow, selector and runtime come from the registry, and the
+//! HubSpot token from an environment variable of the runtime.
+//!
+//! ```yaml
+//! version: 1
+//! projects:
+//!   crm:
+//!     token_env: CRM_HUBSPOT_TOKEN
+//!     targets:
+//!       production:
+//!         workflow_id: "123456789"
+//!         selector: { type: secret, value: HS_ACTION__CONTACT_RENAME__PROD }
+//!         runtime: NODE20X
+//! ```
+//!
+//! Targets use the cicd.yaml target schema; only single-selector targets can
+//! be promoted over HTTP. Requests may only pass `force` to targets with
+//! `allow_force: true`.
+
+use crate::promote::{self, CicdTarget, InlineTarget};
+use crate::selector;
+use crate::types::PromoteSelector;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Token variable of projects that do not set `token_env`.
+const DEFAULT_TOKEN_ENV: &str = "HUBSPOT_TOKEN";
+
+#[derive(Debug, Deserialize)]
+struct RegistryFile {
+    version: u32,
+    projects: BTreeMap<String, ProjectFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProjectFile {
+    #[serde(default = "default_token_env")]
+    token_env: String,
+
+    // Like hubspot.base_url in cicd.yaml; targets may override it
+    base_url: Option<String>,
+
+    targets: BTreeMap<String, TargetFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetFile {
+    #[serde(flatten)]
+    target: CicdTarget,
+
+    // Lets requests take over actions without a hash marker
+    #[serde(default)]
+    allow_force: bool,
+}
+
+fn default_token_env() -> String {
+    DEFAULT_TOKEN_ENV.to_string()
+}
+
+/// Loaded and validated registry.
+#[derive(Debug)]
+pub struct Registry {
+    projects: BTreeMap<String, Project>,
+}
+
+#[derive(Debug)]
+pub struct Project {
+    /// Environment variable holding the HubSpot token (never the token itself)
+    token_env: String,
+    targets: BTreeMap<String, Target>,
+}
+
+#[derive(Debug)]
+pub struct Target {
+    workflow_id: String,
+    selector: PromoteSelector,
+    runtime: Option<String>,
+    base_url: String,
+    allow_force: bool,
+}
+
+impl Registry {
+    /// Load and validate a registry file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read registry at {:?}", path))?;
+        let file: RegistryFile = serde_yaml::from_str(&raw)
+            .with_context(|| format!("Failed to parse registry {:?}", path))?;
+
+        if file.version != 1 {
+            bail!(
+                "Unsupported registry version: {} (expected 1)",
+                file.version
+            );
+        }
+
+        let mut projects = BTreeMap::new();
+        for (name, project) in file.projects {
+            let project = Project::from_file(project)
+                .with_context(|| format!("Invalid registry project '{}'", name))?;
+            projects.insert(name, project);
+        }
+
+        Ok(Registry { projects })
+    }
+
+    /// Project and target by name; the error says which one is unknown.
+    pub fn find(&self, project: &str, target: &str) -> Result<(&Project, &Target)> {
+        let p = self.projects.get(project).with_context(|| {
+            format!(
+                "Project '{}' not found. Available projects: {}",
+                project,
+                names(&self.projects)
+            )
+        })?;
+        let t = p.targets.get(target).with_context(|| {
+            format!(
+                "Target '{}' not found in project '{}'. Available targets: {}",
+                target,
+                project,
+                names(&p.targets)
+            )
+        })?;
+        Ok((p, t))
+    }
+
+    /// Number of projects and targets, for the startup log.
+    pub fn counts(&self) -> (usize, usize) {
+        let targets = self.projects.values().map(|p| p.targets.len()).sum();
+        (self.projects.len(), targets)
+    }
+}
+
+impl Project {
+    fn from_file(file: ProjectFile) -> Result<Self> {
+        if file.token_env.trim().is_empty() {
+            bail!("token_env must not be empty");
+        }
+        // Fail at startup rather than on the first request
+        if std::env::var(&file.token_env).map_or(true, |v| v.trim().is_empty()) {
+            bail!(
+                "environment variable {} (token_env) is not set",
+                file.token_env
+            );
+        }
+
+        let shared_base_url = match &file.base_url {
+            Some(url) => Some(promote::normalize_base_url(url).context("Invalid base_url")?),
+            None => None,
+        };
+
+        let mut targets = BTreeMap::new();
+        for (name, t) in file.targets {
+            let target = Target::from_file(&t, shared_base_url.as_deref())
+                .with_context(|| format!("Invalid target '{}'", name))?;
+            targets.insert(name, target);
+        }
+
+        Ok(Project {
+            token_env: file.token_env,
+            targets,
+        })
+    }
+
+    /// The project's HubSpot token, read from its `token_env`.
+    pub fn token(&self) -> Result<String> {
+        match std::env::var(&self.token_env) {
+            Ok(token) if !token.trim().is_empty() => Ok(token),
+            _ => bail!("Environment variable {} is not set", self.token_env),
+        }
+    }
+}
+
+impl Target {
+    fn from_file(file: &TargetFile, shared_base_url: Option<&str>) -> Result<Self> {
+        let t = &file.target;
+        if t.workflow_id.trim().is_empty() {
+            bail!("workflow_id must be set");
+        }
+
+        let Some((selector, runtime)) = t.single_selector() else {
+            bail!("targets with several actions cannot be promoted over HTTP; use selector");
+        };
+        selector::validate(selector).context("Invalid selector")?;
+
+        let base_url = match (t.base_url(), shared_base_url) {
+            (Some(url), _) => promote::normalize_base_url(url).context("Invalid base_url")?,
+            (None, Some(url)) => url.to_string(),
+            (None, None) => promote::default_base_url()?,
+        };
+
+        Ok(Target {
+            workflow_id: t.workflow_id.clone(),
+            selector: selector.clone(),
+            runtime: runtime.map(|r| r.to_string()),
+            base_url,
+            allow_force: file.allow_force,
+        })
+    }
+
+    /// The target as an inline promotion target, with `token`.
+    pub fn inline<'a>(&'a self, token: &'a str) -> InlineTarget<'a> {
+        InlineTarget {
+            token,
+            base_url: &self.base_url,
+            workflow_id: &self.workflow_id,
+            selector: &self.selector,
+        }
+    }
+
+    pub fn runtime(&self) -> Option<&str> {
+        self.runtime.as_deref()
+    }
+
+    /// Whether requests may pass `force` (set per target in the registry).
+    pub fn allow_force(&self) -> bool {
+        self.allow_force
+    }
+}
+
+fn names<V>(map: &BTreeMap<String, V>) -> String {
+    map.keys().cloned().collect::<Vec<_>>().join(", ")
+}
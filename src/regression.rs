@@ -0,0 +1,206 @@
+// src/regression.rs
+
+//! Performance regression gate against stored metrics.
+//!
+//! Budgets only enforce absolute limits, so an action that becomes 3× slower
+//! but stays under budget would pass. This module stores per-fixture
+//! duration / memory baselines next to snapshots and compares each test run
+//! against them:
+//!
+//! snapshots/<action>.<fixture>.perf.json
+//!
+//! Baselines are written on first run (like snapshots) and refreshed with
+//! `hsemulate test --update-perf-baseline`.
+
+use crate::config::{RegressionAction, RegressionConfig};
+use crate::stats::percentile;
+use crate::util::{ensure_dir, snapshot_key};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Median metrics for one fixture across all of its runs.
+#[derive(Debug, Clone, Serialize)]
+pub struct FixtureMetrics {
+    pub fixture: String,
+    pub duration_ms: u64,
+    pub memory_kb: Option<u64>,
+}
+
+impl FixtureMetrics {
+    /// Build from raw per-run samples (median of each).
+    pub fn from_runs(fixture: &str, durations: &[u64], memory: &[u64]) -> Self {
+        let mut d = durations.to_vec();
+        d.sort_unstable();
+        let mut m = memory.to_vec();
+        m.sort_unstable();
+
+        Self {
+            fixture: fixture.to_string(),
+            duration_ms: percentile(&d, 50.0),
+            memory_kb: if m.is_empty() {
+                None
+            } else {
+                Some(percentile(&m, 50.0))
+            },
+        }
+    }
+}
+
+/// Stored baseline for one action + fixture.
+#[derive(Debug, Serialize, Deserialize)]
+struct PerfBaseline {
+    duration_ms: u64,
+    memory_kb: Option<u64>,
+    recorded_at: String,
+}
+
+/// Outcome of comparing one fixture against its baseline.
+#[derive(Debug, Clone, Serialize)]
+pub struct PerfCheck {
+    pub fixture: String,
+    pub baseline_path: String,
+
+    /// recorded | updated | ok | regressed
+    pub status: &'static str,
+
+    pub duration_ms: u64,
+    pub baseline_duration_ms: Option<u64>,
+    pub memory_kb: Option<u64>,
+    pub baseline_memory_kb: Option<u64>,
+
+    /// Human-readable regression descriptions (empty unless regressed)
+    pub regressions: Vec<String>,
+}
+
+/// Compare (or record) baselines for every fixture of one action.
+pub fn check(
+    action_file: &Path,
+    fixtures: &[FixtureMetrics],
+    cfg: &RegressionConfig,
+    update: bool,
+) -> Result<Vec<PerfCheck>> {
+    let mut out = Vec::new();
+
+    for m in fixtures {
+        let path = baseline_path(Path::new("snapshots"), &snapshot_key(action_file, &m.fixture));
+        let existing = if path.exists() {
+            Some(load_baseline(&path)?)
+        } else {
+            None
+        };
+
+        let mut result = PerfCheck {
+            fixture: m.fixture.clone(),
+            baseline_path: path.display().to_string(),
+            status: "ok",
+            duration_ms: m.duration_ms,
+            baseline_duration_ms: existing.as_ref().map(|b| b.duration_ms),
+            memory_kb: m.memory_kb,
+            baseline_memory_kb: existing.as_ref().and_then(|b| b.memory_kb),
+            regressions: Vec::new(),
+        };
+
+        match existing {
+            Some(_) if update => {
+                write_baseline(&path, m)?;
+                result.status = "updated";
+            }
+            None => {
+                write_baseline(&path, m)?;
+                result.status = "recorded";
+            }
+            Some(base) => {
+                if let Some(limit) = cfg.duration_pct {
+                    if let Some(pct) = increase_pct(base.duration_ms, m.duration_ms) {
+                        if pct > limit {
+                            result.regressions.push(format!(
+                                "duration {}ms → {}ms (+{:.0}%, limit +{}%)",
+                                base.duration_ms, m.duration_ms, pct, limit
+                            ));
+                        }
+                    }
+                }
+
+                if let (Some(limit), Some(old), Some(new)) =
+                    (cfg.memory_pct, base.memory_kb, m.memory_kb)
+                {
+                    if let Some(pct) = increase_pct(old, new) {
+                        if pct > limit {
+                            result.regressions.push(format!(
+                                "memory {}kb → {}kb (+{:.0}%, limit +{}%)",
+                                old, new, pct, limit
+                            ));
+                        }
+                    }
+                }
+
+                if !result.regressions.is_empty() {
+                    result.status = "regressed";
+                }
+            }
+        }
+
+        out.push(result);
+    }
+
+    Ok(out)
+}
+
+/// Split regressions into (failures, warnings) according to config.
+pub fn classify(checks: &[PerfCheck], cfg: &RegressionConfig) -> (Vec<String>, Vec<String>) {
+    let messages = checks
+        .iter()
+        .flat_map(|c| {
+            c.regressions
+                .iter()
+                .map(move |r| format!("[{}] Performance regression: {}", c.fixture, r))
+        })
+        .collect::<Vec<_>>();
+
+    match cfg.on_regression {
+        RegressionAction::Fail => (messages, Vec::new()),
+        RegressionAction::Warn => (Vec::new(), messages),
+    }
+}
+
+/* ---------------- helpers ---------------- */
+
+fn baseline_path(base_dir: &Path, key: &str) -> PathBuf {
+    base_dir.join(format!("{}.perf.json", key))
+}
+
+fn increase_pct(old: u64, new: u64) -> Option<f64> {
+    if old == 0 {
+        return None;
+    }
+    Some((new as f64 - old as f64) / old as f64 * 100.0)
+}
+
+fn load_baseline(path: &Path) -> Result<PerfBaseline> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read performance baseline {:?}", path))?;
+    serde_json::from_str(&raw)
+        .with_context(|| format!("Performance baseline is not valid JSON: {:?}", path))
+}
+
+fn write_baseline(path: &Path, m: &FixtureMetrics) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        ensure_dir(parent)?;
+    }
+
+    let baseline = PerfBaseline {
+        duration_ms: m.duration_ms,
+        memory_kb: m.memory_kb,
+        recorded_at: Utc::now().to_rfc3339(),
+    };
+
+    let bytes =
+        serde_json::to_vec_pretty(&baseline).context("Failed to serialise performance baseline")?;
+    std::fs::write(path, bytes)
+        .with_context(|| format!("Failed to write performance baseline {:?}", path))?;
+
+    Ok(())
+}
@@ -1,26 +1,52 @@
 // src/runner.rs
 
-use crate::checks::{assert_json, check_budgets, BudgetsResolved};
+use crate::audit;
+use crate::bench::{self, BenchOptions};
+use crate::bundle;
+use crate::checks::{
+    assert_json, assert_requests, check_budgets, check_max_requests, BudgetsResolved,
+};
 use crate::cicd;
-use crate::cli::{Cli, Command};
-use crate::config::{Assertion, Budgets, Config, Mode, OutputMode};
+use crate::cli::{AuditCommand, Cli, Command};
+use crate::config::{Assertion, Budgets, Config, Mode, OutputMode, RuntimeMode};
+use crate::coverage::{self, CoverageMap};
+use crate::debug::{self, DebugOptions};
 use crate::engine;
-use crate::metrics::{InvocationMetrics, MemoryTracker};
+use crate::flaky::{self, FlakyReport, RunSample, Verdict};
+use crate::history;
+use crate::http_capture::{self, take_requests, CapturedRequest};
+use crate::limits::{
+    apply_memory_limit, is_memory_error, killed_by_memory_limit, memory_exceeded_message,
+    memory_exceeded_output, Language,
+};
+use crate::logs::{self, LogLine};
+use crate::metrics::{InvocationMetrics, ResourceMonitor};
+use crate::network::{self, NetworkSandbox};
 use crate::promote;
+use crate::protocol::{self, Channel};
+use crate::pull;
+use crate::regression::{self, FixtureMetrics};
 use crate::shim::{node_shim, python_shim};
-use crate::snapshot::{compare_snapshot, load_snapshot, snapshot_path, write_snapshot};
+use crate::snapshot::{
+    compare_snapshot, load_snapshot, log_snapshot_path, snapshot_path, write_snapshot,
+};
+use crate::status;
+use crate::typescript;
 use crate::util::{ensure_dir, read_to_string, snapshot_key};
+use crate::worker;
 
 use anyhow::{bail, Context, Result};
 use chrono::Utc;
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use regex::Regex;
 use serde_json::Value;
 use std::collections::BTreeMap;
 use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::mpsc::channel;
-use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::time::Instant;
 use tempfile::tempdir;
 use tokio::process::Command as TokioCommand;
 
@@ -32,14 +58,46 @@ pub(crate) struct ExecSummary {
     pub max_duration_ms: Option<u128>,
     pub max_memory_kb: Option<u64>,
     pub snapshots_ok: bool,
+    /// Per-fixture flakiness reports (only populated when repeat > 1)
+    pub flakiness: Vec<FlakyReport>,
+    /// Per-fixture median metrics (used by the regression gate)
+    pub fixture_metrics: Vec<FixtureMetrics>,
+    /// Action coverage merged across every run (only with `--coverage`)
+    pub coverage: CoverageMap,
+}
+
+impl ExecSummary {
+    /// True when any fixture produced a `flaky` verdict.
+    pub fn is_flaky(&self) -> bool {
+        self.flakiness
+            .iter()
+            .any(|r| r.verdict == Verdict::Flaky)
+    }
+
+    /// Overall verdict: flaky wins over plain failure.
+    pub fn verdict(&self) -> Verdict {
+        if self.is_flaky() {
+            Verdict::Flaky
+        } else if self.ok {
+            Verdict::Pass
+        } else {
+            Verdict::Fail
+        }
+    }
 }
 
 #[derive(Debug, serde::Serialize)]
 struct LastTestResult {
     ok: bool,
     snapshots_ok: bool,
+    flaky: bool,
+    verdict: Verdict,
     max_duration_ms: Option<u128>,
     max_memory_kb: Option<u64>,
+    /// Overall line coverage (only with `--coverage`)
+    coverage_pct: Option<f64>,
+    /// Configs run, with the hash `promote` would upload their code as
+    configs: Vec<promote::TestedConfig>,
     run_at: String,
 }
 
@@ -59,7 +117,9 @@ pub async fn run(cli: Cli) -> Result<()> {
     match cli.command {
         Command::Init { language } => init_scaffold(language),
 
-        Command::Runtime { listen } => crate::runtime::serve(&listen).await,
+        Command::Runtime { listen, registry } => {
+            crate::runtime::serve(&listen, registry.as_deref()).await
+        }
 
         Command::Validate { config } => {
             let cfg = Config::load(&config)?;
@@ -81,10 +141,61 @@ pub async fn run(cli: Cli) -> Result<()> {
         Command::Promote {
             target,
             force,
+            diff,
+            yes,
+            config,
+        } => {
+            let opts = promote::PromoteOptions { force, diff, yes };
+            promote::handle(target, opts, config).await
+        }
+
+        Command::Status { json, config } => status::handle(json, config).await,
+
+        Command::Rollback {
+            target,
+            to,
+            list,
+            force,
+        } => history::rollback(target, to, list, force).await,
+
+        Command::Pull {
+            target,
+            force,
+            config,
+        } => pull::handle(target, force, config).await,
+
+        Command::Audit {
+            command: AuditCommand::Verify { log },
+        } => audit::verify(log),
+
+        Command::Test {
             config,
-        } => promote::handle(target, force, config).await,
+            update_perf_baseline,
+            coverage,
+        } => run_test_mode(config, update_perf_baseline, coverage).await,
 
-        Command::Test { config } => run_test_mode(config).await,
+        Command::Bench {
+            config,
+            fixture,
+            iterations,
+            warmup,
+            save_baseline,
+            compare_baseline,
+            threshold,
+            warm,
+        } => {
+            bench::handle(BenchOptions {
+                config,
+                fixtures: fixture,
+                iterations,
+                warmup,
+                save_baseline,
+                compare_baseline,
+                threshold_pct: threshold,
+                warm,
+            })
+            .await
+        }
 
         Command::Run {
             config,
@@ -96,6 +207,10 @@ pub async fn run(cli: Cli) -> Result<()> {
             repeat,
             budget_time,
             budget_mem,
+            enforce_mem,
+            debug,
+            debug_port,
+            warm,
         } => {
             let mut cfg = Config::load(&config)?;
 
@@ -120,23 +235,37 @@ pub async fn run(cli: Cli) -> Result<()> {
             if let Some(r) = repeat {
                 cfg.repeat = r;
             }
-            if budget_time.is_some() || budget_mem.is_some() {
+            if budget_time.is_some() || budget_mem.is_some() || enforce_mem {
                 cfg.budgets = Some(resolve_budgets(
                     cfg.budgets.clone(),
                     budget_time,
                     budget_mem,
+                    enforce_mem,
                 ));
             }
 
+            let opts = InvokeOptions {
+                debug: debug.then_some(DebugOptions { port: debug_port }),
+                warm,
+                ..InvokeOptions::default()
+            };
+            if debug {
+                // Time spent paused in the debugger is not action time
+                cfg.budgets = None;
+            }
+
             if cfg.watch {
-                execute_with_watch(config, assert).await
+                execute_with_watch(config, assert, opts).await
             } else {
-                let summary = execute(cfg, assert).await?;
+                let summary = execute(cfg, assert, &opts).await?;
 
                 if !summary.ok {
                     for f in &summary.failures {
                         eprintln!("✖ {}", f);
                     }
+                    if summary.is_flaky() {
+                        bail!("Run flaky");
+                    }
                     bail!("Run failed");
                 }
                 Ok(())
@@ -147,31 +276,85 @@ pub async fn run(cli: Cli) -> Result<()> {
 
 /* ---------------- test mode (CI-first) ---------------- */
 
-async fn run_test_mode(config_arg: PathBuf) -> Result<()> {
+async fn run_test_mode(
+    config_arg: PathBuf,
+    update_perf_baseline: bool,
+    collect_coverage: bool,
+) -> Result<()> {
     // If the user explicitly passed a non-default config path, just run that config.
     // If they left it as default `config.yaml`, discover all configs recursively.
-    let configs = if config_arg == PathBuf::from("config.yaml") {
+    let configs = if config_arg == Path::new("config.yaml") {
         discover_configs()?
     } else {
         vec![config_arg]
     };
 
     let mut any_fail = false;
+    let mut any_flaky = false;
     let mut all_snapshots_ok = true;
     let mut max_duration_ms: Option<u128> = None;
     let mut max_memory_kb: Option<u64> = None;
+    let mut coverage = CoverageMap::default();
+
+    let invoke_opts = InvokeOptions {
+        coverage: collect_coverage,
+        ..InvokeOptions::default()
+    };
 
     let mut results: Vec<Value> = Vec::new();
+    let mut tested = Vec::new();
 
     for cfg_path in configs {
+        // Hashed before running, so the gate matches the code that was tested
+        if let Ok(source) = promote::load_action_source(&cfg_path) {
+            tested.push(promote::TestedConfig {
+                config: cfg_path.to_string_lossy().into_owned(),
+                hash: source.hash(),
+            });
+        }
+
         let mut cfg = Config::load(&cfg_path)?;
         cfg.mode = Mode::Ci;
         cfg.snapshots.enabled = true;
 
-        let summary = execute(cfg, None).await?;
+        let regression_cfg = cfg.regression.clone();
+        let action_file = cfg
+            .action
+            .as_ref()
+            .map(|a| PathBuf::from(&a.entry))
+            .context("Missing action configuration")?;
+
+        let mut summary = execute(cfg, None, &invoke_opts).await?;
+
+        // Regression gate: only meaningful when every fixture ran cleanly
+        let mut perf = Vec::new();
+        let mut warnings = Vec::new();
+        if let Some(rc) = &regression_cfg {
+            if summary.ok {
+                perf = regression::check(
+                    &action_file,
+                    &summary.fixture_metrics,
+                    rc,
+                    update_perf_baseline,
+                )?;
+                let (failures, warns) = regression::classify(&perf, rc);
+                if !failures.is_empty() {
+                    summary.ok = false;
+                    summary.failures.extend(failures);
+                }
+                for w in &warns {
+                    eprintln!("WARNING: {}", w);
+                }
+                warnings = warns;
+            }
+        }
+
         if !summary.ok {
             any_fail = true;
         }
+        if summary.is_flaky() {
+            any_flaky = true;
+        }
         if !summary.snapshots_ok {
             all_snapshots_ok = false;
         }
@@ -193,18 +376,54 @@ async fn run_test_mode(config_arg: PathBuf) -> Result<()> {
         results.push(serde_json::json!({
             "config": cfg_path.to_string_lossy(),
             "ok": summary.ok,
+            "verdict": summary.verdict(),
             "snapshots_ok": summary.snapshots_ok,
             "max_duration_ms": summary.max_duration_ms,
             "max_memory_kb": summary.max_memory_kb,
             "runs": summary.runs,
             "failures": summary.failures,
+            "warnings": warnings,
+            "flakiness": summary.flakiness,
+            "perf": perf,
         }));
+
+        coverage.extend(std::mem::take(&mut summary.coverage));
+    }
+
+    // Coverage report: lcov for tooling, summary on STDERR (STDOUT is the CI JSON)
+    let mut coverage_pct = None;
+    let mut coverage_json = Value::Null;
+    if collect_coverage {
+        let (per_file, total) = coverage.summarize();
+        if !coverage.is_empty() {
+            coverage.write_lcov(Path::new(coverage::LCOV_PATH))?;
+            eprintln!("{}", coverage::format_summary(&per_file, &total));
+            eprintln!("lcov written to {}", coverage::LCOV_PATH);
+        }
+        coverage_pct = total.lines.pct();
+        coverage_json = serde_json::json!({
+            "lcov": (!coverage.is_empty()).then_some(coverage::LCOV_PATH),
+            "lines": total.lines,
+            "branches": total.branches,
+            "functions": total.functions,
+            "line_pct": coverage_pct,
+        });
     }
 
     // CI JSON emitter: always print one stable JSON blob in test mode.
+    let verdict = if any_flaky {
+        Verdict::Flaky
+    } else if any_fail {
+        Verdict::Fail
+    } else {
+        Verdict::Pass
+    };
+
     let out = serde_json::json!({
         "ok": !any_fail,
-        "results": results
+        "verdict": verdict,
+        "results": results,
+        "coverage": coverage_json
     });
     println!("{}", serde_json::to_string(&out)?);
 
@@ -213,12 +432,19 @@ async fn run_test_mode(config_arg: PathBuf) -> Result<()> {
     let last = LastTestResult {
         ok: !any_fail,
         snapshots_ok: all_snapshots_ok,
+        flaky: any_flaky,
+        verdict,
         max_duration_ms,
         max_memory_kb,
+        coverage_pct,
+        configs: tested,
         run_at: Utc::now().to_rfc3339(),
     };
     write_last_test_result(&last)?;
 
+    if any_flaky {
+        bail!("One or more configs are flaky");
+    }
     if any_fail {
         bail!("One or more configs failed");
     }
@@ -248,7 +474,11 @@ fn discover_configs() -> Result<Vec<PathBuf>> {
 
 /* ---------------- watch mode ---------------- */
 
-async fn execute_with_watch(config_path: PathBuf, assertion_file: Option<PathBuf>) -> Result<()> {
+async fn execute_with_watch(
+    config_path: PathBuf,
+    assertion_file: Option<PathBuf>,
+    opts: InvokeOptions,
+) -> Result<()> {
     let (tx, rx) = channel();
 
     let mut watcher: RecommendedWatcher =
@@ -262,6 +492,12 @@ async fn execute_with_watch(config_path: PathBuf, assertion_file: Option<PathBuf
 
     let action = cfg0.action.as_ref().expect("config validated");
     watcher.watch(Path::new(&action.entry), RecursiveMode::NonRecursive)?;
+    // Local modules of a multi-file action; new requires need a restart
+    if let Ok(Some(bundle)) = bundle::build(Path::new(&action.entry)) {
+        for module in bundle.modules.iter().skip(1) {
+            watcher.watch(module, RecursiveMode::NonRecursive)?;
+        }
+    }
 
     for f in &cfg0.fixtures {
         watcher.watch(Path::new(f), RecursiveMode::NonRecursive)?;
@@ -271,9 +507,12 @@ async fn execute_with_watch(config_path: PathBuf, assertion_file: Option<PathBuf
         clear_screen();
 
         // Reload config each run so edits to config.yaml apply immediately
-        let cfg = Config::load(&config_path)?;
+        let mut cfg = Config::load(&config_path)?;
+        if opts.debug.is_some() {
+            cfg.budgets = None;
+        }
 
-        match execute(cfg, assertion_file.clone()).await {
+        match execute(cfg, assertion_file.clone(), &opts).await {
             Ok(summary) => {
                 if summary.ok {
                     eprintln!("OK");
@@ -301,7 +540,11 @@ fn clear_screen() {
 
 /* ---------------- core execution ---------------- */
 
-pub(crate) async fn execute(cfg: Config, assertion_file: Option<PathBuf>) -> Result<ExecSummary> {
+pub(crate) async fn execute(
+    cfg: Config,
+    assertion_file: Option<PathBuf>,
+    opts: &InvokeOptions,
+) -> Result<ExecSummary> {
     let action = cfg
         .action
         .as_ref()
@@ -311,6 +554,13 @@ pub(crate) async fn execute(cfg: Config, assertion_file: Option<PathBuf>) -> Res
         .canonicalize()
         .context("Unable to resolve action entry")?;
 
+    // Compile TypeScript once, not once per fixture and repeat
+    let compiled = typescript::Compiled::for_action(&cfg.runtime.typescript, &action_file)?;
+    let opts = &InvokeOptions {
+        compiled: compiled.map(Arc::new),
+        ..opts.clone()
+    };
+
     let assertions_override = if let Some(path) = assertion_file {
         Some(load_external_assertions(&path)?)
     } else if let Some(path) = cfg.assertions_file.as_ref() {
@@ -339,6 +589,33 @@ pub(crate) async fn execute(cfg: Config, assertion_file: Option<PathBuf>) -> Res
     let mut max_duration_ms: Option<u128> = None;
     let mut max_memory_kb: Option<u64> = None;
     let mut snapshots_ok = true;
+    let mut flakiness: Vec<FlakyReport> = Vec::new();
+    let mut fixture_metrics: Vec<FixtureMetrics> = Vec::new();
+    let mut coverage = CoverageMap::default();
+    if opts.coverage && typescript::is_typescript(&action_file) {
+        eprintln!(
+            "WARNING: coverage is not collected for TypeScript actions ({})",
+            action_file.display()
+        );
+    } else if opts.coverage && bundle::build(&action_file)?.is_some() {
+        eprintln!(
+            "WARNING: coverage is not collected for multi-file actions ({})",
+            action_file.display()
+        );
+    }
+
+    let log_ignore = cfg
+        .logs
+        .as_ref()
+        .map(|l| {
+            l.snapshot_ignore
+                .iter()
+                .map(|p| Regex::new(p))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .context("Invalid logs.snapshot_ignore pattern")?
+        .unwrap_or_default();
 
     for fixture in &cfg.fixtures {
         let event: Value = serde_json::from_str(&read_to_string(Path::new(fixture))?)
@@ -355,8 +632,32 @@ pub(crate) async fn execute(cfg: Config, assertion_file: Option<PathBuf>) -> Res
             None
         };
 
+        // Log snapshots live next to output snapshots
+        let log_snap_path = log_snapshot_path(Path::new("snapshots"), &snap_key);
+        let log_snapshots = cfg.logs.as_ref().is_some_and(|l| l.snapshot);
+        let mut log_baseline = if log_snapshots && log_snap_path.exists() {
+            Some(load_snapshot(&log_snap_path)?)
+        } else {
+            None
+        };
+
+        // Every run of this fixture, kept for flakiness analysis
+        let mut samples: Vec<RunSample> = Vec::new();
+        let mut durations: Vec<u64> = Vec::new();
+        let mut memory: Vec<u64> = Vec::new();
+
         for run_idx in 0..runs {
-            let (output, metrics) = invoke_once(&cfg, &action_file, &event).await?;
+            let Invocation {
+                mut output,
+                metrics,
+                logs,
+                coverage: run_coverage,
+            } = invoke_once(&cfg, &action_file, &event, opts).await?;
+            let requests = take_requests(&mut output);
+
+            if let Some(cov) = run_coverage {
+                coverage.add(&action_file, cov);
+            }
 
             // Aggregate max duration/memory for CICD gating
             max_duration_ms = Some(
@@ -365,14 +666,23 @@ pub(crate) async fn execute(cfg: Config, assertion_file: Option<PathBuf>) -> Res
                     .unwrap_or(metrics.duration_ms),
             );
 
-            if let Some(mem) = metrics.max_rss_kb {
+            if let Some(mem) = metrics.peak_memory_kb() {
                 max_memory_kb = Some(max_memory_kb.map(|m| m.max(mem)).unwrap_or(mem));
+                memory.push(mem);
             }
+            durations.push(u64::try_from(metrics.duration_ms).unwrap_or(u64::MAX));
 
             let mut failures = Vec::new();
 
             if !output.get("ok").and_then(|v| v.as_bool()).unwrap_or(false) {
-                failures.push("Action returned ok=false".to_string());
+                match memory_limit_failure(&cfg, &output) {
+                    Some(msg) => failures.push(msg),
+                    None => failures.push("Action returned ok=false".to_string()),
+                }
+            }
+
+            for destination in &metrics.network_blocked {
+                failures.push(network::blocked_message(destination));
             }
 
             // Assertions: CLI override wins, else config assertions
@@ -384,11 +694,48 @@ pub(crate) async fn execute(cfg: Config, assertion_file: Option<PathBuf>) -> Res
                 }
             }
 
+            // Outbound requests
+            if !cfg.requests.is_empty() {
+                if let Err(e) = assert_requests(&requests, &cfg.requests) {
+                    failures.push(format!("Request assertion failed: {}", e));
+                }
+            }
+            if let Some(max) = cfg.max_requests {
+                if let Err(e) = check_max_requests(requests.len(), max) {
+                    failures.push(format!("Request assertion failed: {}", e));
+                }
+            }
+
+            // Logs
+            if let Some(log_cfg) = &cfg.logs {
+                failures.extend(logs::check(&logs, log_cfg));
+
+                if log_cfg.snapshot {
+                    let actual = logs::snapshot_value(&logs, &log_ignore);
+                    match &log_baseline {
+                        None => {
+                            write_snapshot(&log_snap_path, &actual)?;
+                            log_baseline = Some(actual);
+                        }
+                        Some(expected) => {
+                            if let Err(e) = compare_snapshot(expected, &actual) {
+                                snapshots_ok = false;
+                                failures.push(format!(
+                                    "Log snapshot mismatch ({}): {}",
+                                    log_snap_path.display(),
+                                    e
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+
             // Budgets
             if let Some(b) = &cfg.budgets {
                 if let Err(e) = check_budgets(
                     metrics.duration_ms,
-                    metrics.max_rss_kb,
+                    metrics.peak_memory_kb(),
                     &BudgetsResolved {
                         duration_ms: b.duration_ms,
                         memory_kb: b.memory_mb.map(|mb| mb * 1024),
@@ -423,6 +770,8 @@ pub(crate) async fn execute(cfg: Config, assertion_file: Option<PathBuf>) -> Res
                 runs,
                 output: &output,
                 metrics: &metrics,
+                requests: &requests,
+                logs: &logs,
                 failures: &failures,
             };
             let envelope = build_output_envelope(&render_ctx);
@@ -436,23 +785,72 @@ pub(crate) async fn execute(cfg: Config, assertion_file: Option<PathBuf>) -> Res
                 file_outputs.push(envelope);
             }
 
-            if !failures.is_empty() {
-                // Include fixture context for diagnostics
-                for f in failures {
-                    failures_all.push(format!("[{}] {}", fixture, f));
-                }
+            if runs > 1 {
+                samples.push(RunSample {
+                    output: output.clone(),
+                    passed: failures.is_empty(),
+                    duration_ms: metrics.duration_ms,
+                    max_rss_kb: metrics.peak_memory_kb(),
+                });
+            }
 
-                // Fail fast in CI
-                if matches!(cfg.mode, Mode::Ci) {
-                    return Ok(ExecSummary {
-                        ok: false,
-                        failures: failures_all,
-                        runs,
-                        max_duration_ms,
-                        max_memory_kb,
-                        snapshots_ok,
-                    });
-                }
+            // Include fixture context for diagnostics
+            for f in failures {
+                failures_all.push(format!("[{}] {}", fixture, f));
+            }
+
+            // Fail fast in CI (single runs only; repeats must finish to be compared)
+            if matches!(cfg.mode, Mode::Ci) && runs == 1 && !failures_all.is_empty() {
+                return Ok(ExecSummary {
+                    ok: false,
+                    failures: failures_all,
+                    runs,
+                    max_duration_ms,
+                    max_memory_kb,
+                    snapshots_ok,
+                    flakiness,
+                    fixture_metrics,
+                    coverage,
+                });
+            }
+        }
+
+        fixture_metrics.push(FixtureMetrics::from_runs(fixture, &durations, &memory));
+
+        if runs > 1 {
+            let report = flaky::analyze(fixture, &samples, &cfg.snapshots.ignore);
+
+            if report.verdict == Verdict::Flaky && report.failed == 0 {
+                // Every run passed on its own, but they disagree with each other
+                failures_all.push(format!(
+                    "[{}] Nondeterministic output across {} runs: {}",
+                    fixture,
+                    report.runs,
+                    report.nondeterministic_paths.join(", ")
+                ));
+            }
+
+            if emit_stdout {
+                println!(
+                    "{}",
+                    render_flaky_report(&cfg.output.mode, &report, use_color)?
+                );
+            }
+
+            flakiness.push(report);
+
+            if matches!(cfg.mode, Mode::Ci) && !failures_all.is_empty() {
+                return Ok(ExecSummary {
+                    ok: false,
+                    failures: failures_all,
+                    runs,
+                    max_duration_ms,
+                    max_memory_kb,
+                    snapshots_ok,
+                    flakiness,
+                    fixture_metrics,
+                    coverage,
+                });
             }
         }
     }
@@ -473,23 +871,47 @@ pub(crate) async fn execute(cfg: Config, assertion_file: Option<PathBuf>) -> Res
         max_duration_ms,
         max_memory_kb,
         snapshots_ok,
+        flakiness,
+        fixture_metrics,
+        coverage,
     })
 }
 
 /* ---------------- invocation ---------------- */
 
-async fn invoke_once(
+/// Per-invocation knobs that differ between commands (`run`, `test`, `bench`).
+#[derive(Debug, Default, Clone)]
+pub(crate) struct InvokeOptions {
+    /// Do not stream action logs to the terminal (they are still collected)
+    pub quiet: bool,
+    /// Collect coverage of the action file
+    pub coverage: bool,
+    /// Start the runtime paused, waiting for a debugger
+    pub debug: Option<DebugOptions>,
+    /// Use warm workers even when `runtime.mode` is cold
+    pub warm: bool,
+    /// TypeScript output compiled once for the whole execution
+    pub compiled: Option<Arc<typescript::Compiled>>,
+}
+
+/// Result of a single action invocation.
+#[derive(Debug)]
+pub(crate) struct Invocation {
+    /// JSON emitted by the shim
+    pub output: Value,
+    pub metrics: InvocationMetrics,
+    /// Lines the action logged, in order
+    pub logs: Vec<LogLine>,
+    /// Coverage of the action file (only with `InvokeOptions::coverage`)
+    pub coverage: Option<coverage::FileCoverage>,
+}
+
+pub(crate) async fn invoke_once(
     cfg: &Config,
     action_file: &Path,
     event: &Value,
-) -> Result<(Value, InvocationMetrics)> {
-    let tmp = tempdir().context("Failed to create temp dir")?;
-
-    // Write event.json for shim
-    let event_path = tmp.path().join("event.json");
-    std::fs::write(&event_path, serde_json::to_vec_pretty(event)?)
-        .context("Failed to write event.json")?;
-
+    opts: &InvokeOptions,
+) -> Result<Invocation> {
     // Select runtime + shim by extension
     let ext = action_file
         .extension()
@@ -497,51 +919,176 @@ async fn invoke_once(
         .unwrap_or("")
         .to_lowercase();
 
-    let (runtime, shim_name, shim_code) = match ext.as_str() {
-        "py" => (&cfg.runtime.python, "hs_python_runner.py", python_shim()),
-        "js" | "mjs" | "cjs" => (&cfg.runtime.node, "hs_node_runner.mjs", node_shim()),
+    let (language, runtime, shim_name, shim_code) = match ext.as_str() {
+        "py" => (
+            Language::Python,
+            &cfg.runtime.python,
+            "hs_python_runner.py",
+            python_shim(),
+        ),
+        "js" | "mjs" | "cjs" | "ts" => (
+            Language::Node,
+            &cfg.runtime.node,
+            "hs_node_runner.mjs",
+            node_shim(),
+        ),
         _ => bail!("Unsupported action file extension: {}", ext),
     };
 
-    let shim_path = tmp.path().join(shim_name);
+    // TypeScript and multi-file actions are built into the invocation's temp
+    // dir, so they have no stable file for coverage or a warm worker to track
+    let is_typescript = typescript::is_typescript(action_file);
+    let bundle = match language {
+        Language::Node if !is_typescript => bundle::build(action_file)?,
+        _ => None,
+    };
+    let is_built = is_typescript || bundle.is_some();
+    let collect_coverage = opts.coverage && !is_built;
+
+    // Coverage and the debugger need a process of their own
+    let warm = (opts.warm || cfg.runtime.mode == RuntimeMode::Warm)
+        && opts.debug.is_none()
+        && !opts.coverage
+        && !is_built
+        && worker::supported(cfg);
+    if warm {
+        let served =
+            worker::invoke(cfg, language, runtime, action_file, event, !opts.quiet).await?;
+        return Ok(Invocation {
+            output: served.output,
+            metrics: served.metrics,
+            logs: served.logs,
+            coverage: None,
+        });
+    }
+
+    let tmp = tempdir().context("Failed to create temp dir")?;
+
+    // Write event.json for shim
+    let event_path = tmp.path().join("event.json");
+    std::fs::write(&event_path, serde_json::to_vec_pretty(event)?)
+        .context("Failed to write event.json")?;
+
+    let shim_dir = match opts.debug {
+        Some(_) => debug::shim_dir()?,
+        None => tmp.path().to_path_buf(),
+    };
+    let shim_path = shim_dir.join(shim_name);
     std::fs::write(&shim_path, shim_code).context("Failed to write runner shim")?;
 
     let mut cmd = TokioCommand::new(runtime);
+    let debug_port = opts.debug.map(|d| d.port_for(language));
+    if let Some(port) = debug_port {
+        debug::apply(language, runtime, port, &mut cmd)?;
+    }
+    let run_file = if is_typescript {
+        let compiled = match &opts.compiled {
+            Some(compiled) => compiled.clone(),
+            None => Arc::new(typescript::Compiled::new(&cfg.runtime.typescript, action_file)?),
+        };
+        typescript::prepare(&compiled, action_file, &mut cmd)?
+    } else if let Some(bundle) = &bundle {
+        let out_dir = tmp.path().join("bundle");
+        ensure_dir(&out_dir)?;
+        bundle::prepare(bundle, action_file, &out_dir, &mut cmd)?
+    } else {
+        action_file.to_path_buf()
+    };
     cmd.arg(&shim_path)
-        .arg(action_file)
+        .arg(&run_file)
         .arg(&event_path)
         .stdout(Stdio::piped())
-        .stderr(Stdio::inherit());
+        .stderr(Stdio::piped());
 
     for (k, v) in &cfg.env {
         cmd.env(k, v);
     }
 
+    let sandbox = match &cfg.network {
+        Some(network) => Some(NetworkSandbox::start(network).await?),
+        None => None,
+    };
+    if let Some(sandbox) = &sandbox {
+        sandbox.apply(&mut cmd);
+    }
+
+    // Not importable, so it cannot shadow Python's `coverage` package
+    let coverage_dir = tmp.path().join("coverage-data");
+    if collect_coverage {
+        coverage::prepare(language, &coverage_dir, &mut cmd)?;
+    }
+
+    let mut monitor = ResourceMonitor::new(&cfg.metrics);
+    let memory_limit_mb = apply_memory_limit(cfg, language, &mut cmd, &mut monitor);
+    monitor.prepare(&mut cmd)?;
+
+    if let Some(port) = debug_port {
+        debug::announce(language, port, action_file);
+    }
+
+    let channel = Channel::attach(&mut cmd)?;
+
     let start = Instant::now();
-    let child = cmd.spawn().context("Failed to spawn runtime")?;
+    let mut child = cmd.spawn().context("Failed to spawn runtime")?;
 
     let pid = child.id().context("Failed to get child PID")?;
-    let mem = MemoryTracker::start(pid, Duration::from_millis(20));
+    monitor.start(pid);
+
+    let stdout_pipe = child
+        .stdout
+        .take()
+        .context("Failed to capture runtime stdout")?;
+    let stderr_pipe = child
+        .stderr
+        .take()
+        .context("Failed to capture runtime stderr")?;
+
+    let echo = !opts.quiet;
+    let transcript = protocol::collect(channel, stdout_pipe, stderr_pipe, |log| {
+        if echo {
+            eprintln!("{}", log.message);
+        }
+    })
+    .await
+    .context("Failed while waiting for action to complete")?;
 
-    let output = child
-        .wait_with_output()
+    // Collect accounting before the child is reaped
+    monitor.wait_for_exit().await;
+    let duration_ms = start.elapsed().as_millis();
+    let mut metrics = monitor.finish(duration_ms);
+    if let Some(sandbox) = &sandbox {
+        metrics.network_blocked = sandbox.blocked();
+    }
+    metrics.phases = transcript.phases.clone();
+
+    let status = child
+        .wait()
         .await
         .context("Failed while waiting for action to complete")?;
 
-    let duration_ms = start.elapsed().as_millis();
-    let max_rss_kb = mem.stop_and_take();
+    let parsed = match transcript.output() {
+        Some(output) => output,
+        None => match memory_limit_mb {
+            Some(mb) if killed_by_memory_limit(&metrics, &transcript.logs) => {
+                memory_exceeded_output(language, mb)
+            }
+            _ => bail!("Shim did not emit a result ({})", status),
+        },
+    };
+    let logs = transcript.logs;
 
-    let stdout = String::from_utf8(output.stdout).context("stdout not valid UTF-8")?;
-    let parsed: Value =
-        serde_json::from_str(stdout.trim()).context("Shim did not emit valid JSON")?;
+    let coverage = if collect_coverage {
+        coverage::collect(language, &coverage_dir, action_file)
+    } else {
+        None
+    };
 
-    Ok((
-        parsed,
-        InvocationMetrics {
-            duration_ms,
-            max_rss_kb,
-        },
-    ))
+    Ok(Invocation {
+        output: parsed,
+        metrics,
+        logs,
+        coverage,
+    })
 }
 
 /* ---------------- utilities ---------------- */
@@ -553,11 +1100,22 @@ fn load_external_assertions(path: &Path) -> Result<BTreeMap<String, Assertion>>
     Ok(map)
 }
 
-fn resolve_budgets(base: Option<Budgets>, dur: Option<u64>, mem: Option<u64>) -> Budgets {
-    let mut b = base.unwrap_or(Budgets {
-        duration_ms: None,
-        memory_mb: None,
-    });
+/// Failure message for an output killed by the enforced memory limit.
+fn memory_limit_failure(cfg: &Config, output: &Value) -> Option<String> {
+    if !is_memory_error(output) {
+        return None;
+    }
+    let mb = cfg.budgets.as_ref().and_then(|b| b.enforced_memory_mb())?;
+    Some(memory_exceeded_message(mb))
+}
+
+fn resolve_budgets(
+    base: Option<Budgets>,
+    dur: Option<u64>,
+    mem: Option<u64>,
+    enforce_mem: bool,
+) -> Budgets {
+    let mut b = base.unwrap_or_default();
 
     if let Some(d) = dur {
         b.duration_ms = Some(d);
@@ -565,6 +1123,9 @@ fn resolve_budgets(base: Option<Budgets>, dur: Option<u64>, mem: Option<u64>) ->
     if let Some(m) = mem {
         b.memory_mb = Some(m);
     }
+    if enforce_mem {
+        b.enforce_memory = true;
+    }
 
     b
 }
@@ -576,6 +1137,8 @@ struct RenderContext<'a> {
     runs: u64,
     output: &'a Value,
     metrics: &'a InvocationMetrics,
+    requests: &'a [CapturedRequest],
+    logs: &'a [LogLine],
     failures: &'a [String],
 }
 
@@ -606,10 +1169,36 @@ fn build_output_envelope(ctx: &RenderContext<'_>) -> Value {
         .unwrap_or(Value::Null);
     meta.insert("max_rss_kb".to_string(), mem_value);
 
+    let opt = |v: Option<u64>| v.map(Value::from).unwrap_or(Value::Null);
+    meta.insert(
+        "tree_max_rss_kb".to_string(),
+        opt(ctx.metrics.tree_max_rss_kb),
+    );
+    meta.insert(
+        "cgroup_peak_kb".to_string(),
+        opt(ctx.metrics.cgroup_peak_kb),
+    );
+    meta.insert("cpu_user_ms".to_string(), opt(ctx.metrics.cpu_user_ms));
+    meta.insert("cpu_system_ms".to_string(), opt(ctx.metrics.cpu_system_ms));
+    if !ctx.metrics.phases.is_empty() {
+        meta.insert(
+            "phases_ms".to_string(),
+            serde_json::to_value(&ctx.metrics.phases).unwrap_or(Value::Null),
+        );
+    }
+
     let mut envelope = serde_json::Map::new();
     envelope.insert("ok".to_string(), Value::Bool(ctx.failures.is_empty()));
     envelope.insert("meta".to_string(), Value::Object(meta));
     envelope.insert("output".to_string(), ctx.output.clone());
+    envelope.insert(
+        "requests".to_string(),
+        serde_json::to_value(ctx.requests).unwrap_or(Value::Null),
+    );
+    envelope.insert(
+        "logs".to_string(),
+        serde_json::to_value(ctx.logs).unwrap_or(Value::Null),
+    );
     if !ctx.failures.is_empty() {
         let failures = ctx
             .failures
@@ -623,7 +1212,7 @@ fn build_output_envelope(ctx: &RenderContext<'_>) -> Value {
     Value::Object(envelope)
 }
 
-fn select_simple_output<'a>(output: &'a Value) -> &'a Value {
+fn select_simple_output(output: &Value) -> &Value {
     if output.get("ok").and_then(|v| v.as_bool()) == Some(false) {
         if let Some(err) = output.get("error") {
             if !err.is_null() {
@@ -682,13 +1271,27 @@ fn format_simple_output(ctx: &RenderContext<'_>, use_color: bool) -> Result<Stri
         .unwrap_or_else(|_| ctx.metrics.duration_ms.to_string());
     out.push_str(&format!("time: {}ms\n", duration_ms));
 
-    let mem = ctx
+    let mut mem = ctx
         .metrics
-        .max_rss_kb
+        .peak_memory_kb()
         .map(|v| format!("{}kb", v))
         .unwrap_or_else(|| "n/a".to_string());
+    if let Some(tree) = ctx.metrics.tree_max_rss_kb {
+        mem.push_str(&format!(" (process tree sum {}kb)", tree));
+    }
     out.push_str(&format!("memory: {}\n", mem));
 
+    if let (Some(user), Some(system)) = (ctx.metrics.cpu_user_ms, ctx.metrics.cpu_system_ms) {
+        out.push_str(&format!("cpu: {}ms user / {}ms system\n", user, system));
+    }
+
+    if !ctx.requests.is_empty() {
+        out.push_str("requests:\n");
+        for request in ctx.requests {
+            out.push_str(&format!("- {}\n", http_capture::describe(request)));
+        }
+    }
+
     if !ctx.failures.is_empty() {
         out.push_str("failures:\n");
         for failure in ctx.failures {
@@ -708,6 +1311,60 @@ fn format_simple_output(ctx: &RenderContext<'_>, use_color: bool) -> Result<Stri
     Ok(out.trim_end().to_string())
 }
 
+fn render_flaky_report(mode: &OutputMode, report: &FlakyReport, use_color: bool) -> Result<String> {
+    match mode {
+        OutputMode::Stdout => serde_json::to_string(&serde_json::json!({ "flakiness": report }))
+            .context("Failed to format flakiness report as JSON"),
+        OutputMode::Pretty => {
+            serde_json::to_string_pretty(&serde_json::json!({ "flakiness": report }))
+                .context("Failed to format flakiness report as pretty JSON")
+        }
+        OutputMode::Simple => Ok(format_flaky_report(report, use_color)),
+        OutputMode::File => bail!("output.mode = file should be handled separately"),
+    }
+}
+
+fn format_flaky_report(report: &FlakyReport, use_color: bool) -> String {
+    let color = match report.verdict {
+        Verdict::Pass => "32",
+        Verdict::Flaky => "33",
+        Verdict::Fail => "31",
+    };
+    let verdict = paint(&report.verdict.as_str().to_uppercase(), color, use_color);
+
+    let mut out = String::new();
+    out.push_str(&format!("{} {} (repeat analysis)\n", verdict, report.fixture));
+    out.push_str(&format!(
+        "passed: {}/{} ({:.0}%)\n",
+        report.passed,
+        report.runs,
+        report.pass_ratio() * 100.0
+    ));
+
+    if let Some(d) = &report.duration_ms {
+        out.push_str(&format!(
+            "time: min {}ms / median {}ms / p95 {}ms / max {}ms\n",
+            d.min, d.median, d.p95, d.max
+        ));
+    }
+    match &report.memory_kb {
+        Some(m) => out.push_str(&format!(
+            "memory: min {}kb / median {}kb / p95 {}kb / max {}kb\n",
+            m.min, m.median, m.p95, m.max
+        )),
+        None => out.push_str("memory: n/a\n"),
+    }
+
+    if !report.nondeterministic_paths.is_empty() {
+        out.push_str("nondeterministic paths:\n");
+        for p in &report.nondeterministic_paths {
+            out.push_str(&format!("- {}\n", p));
+        }
+    }
+
+    out.trim_end().to_string()
+}
+
 fn should_use_color() -> bool {
     if std::env::var_os("NO_COLOR").is_some() {
         return false;
@@ -1,5 +1,5 @@
 use crate::{
-    auth::api_key_auth,
+    auth::{api_key_auth, ApiKeyId},
     config::Config,
     engine::{
         run::{run_execution, run_inline_execution},
@@ -8,14 +8,18 @@ use crate::{
     },
     inline::InlineConfig,
 };
-use crate::types::PromoteRequest;
 use serde_json::json;
-use crate::promote::promote_inline;
+use crate::promote::{self, promote_inline, InlineTarget};
+use crate::registry::Registry;
+use crate::selector;
+use crate::audit;
+use crate::history::Kind;
 use crate::engine::events::ExecutionEvent;
 
 use axum::{
     body::Body,
     debug_handler,
+    extract::{Extension, Path, State},
     http::{Request, StatusCode},
     middleware,
     response::{IntoResponse, Response},
@@ -24,7 +28,7 @@ use axum::{
 };
 
 use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::net::TcpListener;
 use tower_http::{cors::{Any, CorsLayer}, trace::TraceLayer};
 use tracing::Span;
@@ -34,7 +38,17 @@ use axum::extract::DefaultBodyLimit;
 
 /* ---------------- server ---------------- */
 
-pub async fn serve(addr: &str) -> anyhow::Result<()> {
+pub async fn serve(addr: &str, registry: Option<&std::path::Path>) -> anyhow::Result<()> {
+    let registry = match registry {
+        Some(path) => {
+            let registry = Registry::load(path)?;
+            let (projects, targets) = registry.counts();
+            tracing::info!(projects, targets, "registry loaded from {:?}", path);
+            Some(registry)
+        }
+        None => None,
+    };
+
     /*
     FIX: configure CORS properly from Config
     */
@@ -47,6 +61,11 @@ pub async fn serve(addr: &str) -> anyhow::Result<()> {
         .route("/execute", post(execute))
         .route("/validate", post(validate))
     .route("/promote", post(promote))
+        .route(
+            "/projects/:project/targets/:target/promote",
+            post(promote_target),
+        )
+        .with_state(Arc::new(registry))
         .layer(middleware::from_fn(api_key_auth));
 
     let app = Router::new()
@@ -104,15 +123,16 @@ async fn health() -> &'static str {
 
 #[debug_handler]
 async fn promote(
+    Extension(key): Extension<ApiKeyId>,
     Json(req): Json<crate::types::PromoteRequest>
 ) -> impl IntoResponse {
     // 1. Validate selector
-    if req.selector.selector_type != "secret" {
+    if let Err(e) = selector::validate(&req.selector) {
         return (
             StatusCode::BAD_REQUEST,
             Json(json!({
                 "ok": false,
-                "error": "Only selector.type = 'secret' is supported"
+                "error": e.to_string()
             })),
         )
         .into_response();
@@ -130,18 +150,156 @@ async fn promote(
         .into_response();
     }
 
+    let base_url = match promote::default_base_url() {
+        Ok(url) => url,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "ok": false,
+                    "error": format!("{:#}", e)
+                })),
+            )
+            .into_response();
+        }
+    };
+
     // 3. Promote
-    match promote_inline(
-        &req.hubspot_token,
-        &req.workflow_id,
-        &req.selector,
+    let target = InlineTarget {
+        token: &req.hubspot_token,
+        base_url: &base_url,
+        workflow_id: &req.workflow_id,
+        selector: &req.selector,
+    };
+    promote_and_audit(
+        &target,
+        None,
         &req.source_code,
         req.runtime.as_deref(),
         req.force,
         req.dry_run,
+        &Caller {
+            key: &key,
+            claimed_actor: req.actor.as_deref(),
+        },
     )
     .await
-    {
+}
+
+#[debug_handler]
+async fn promote_target(
+    State(registry): State<Arc<Option<Registry>>>,
+    Extension(key): Extension<ApiKeyId>,
+    Path((project, target)): Path<(String, String)>,
+    Json(req): Json<crate::types::TargetPromoteRequest>,
+) -> impl IntoResponse {
+    // 1. Resolve the target from the registry
+    let Some(registry) = registry.as_ref() else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "ok": false,
+                "error": "No registry loaded; start the runtime with --registry <file>"
+            })),
+        )
+        .into_response();
+    };
+
+    let (p, t) = match registry.find(&project, &target) {
+        Ok(found) => found,
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "ok": false,
+                    "error": e.to_string()
+                })),
+            )
+            .into_response();
+        }
+    };
+
+    // 2. Token from the runtime's environment
+    let token = match p.token() {
+        Ok(token) => token,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "ok": false,
+                    "error": e.to_string()
+                })),
+            )
+            .into_response();
+        }
+    };
+
+    // 3. Overwriting unmarked actions is the registry's decision
+    if req.force && !t.allow_force() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "ok": false,
+                "error": format!(
+                    "Target '{}/{}' does not allow force; set allow_force: true for it in the registry",
+                    project, target
+                )
+            })),
+        )
+        .into_response();
+    }
+
+    // 4. Promote
+    let audit_target = format!("{}/{}", project, target);
+    promote_and_audit(
+        &t.inline(&token),
+        Some(&audit_target),
+        &req.source_code,
+        t.runtime(),
+        req.force,
+        req.dry_run,
+        &Caller {
+            key: &key,
+            claimed_actor: req.actor.as_deref(),
+        },
+    )
+    .await
+}
+
+/// Who sent a promote request, for the audit log.
+struct Caller<'a> {
+    /// The API key the request authenticated with
+    key: &'a ApiKeyId,
+    /// `actor` from the request body, unverified
+    claimed_actor: Option<&'a str>,
+}
+
+/// Run an inline promotion and audit it if it reached HubSpot.
+async fn promote_and_audit(
+    target: &InlineTarget<'_>,
+    audit_target: Option<&str>,
+    source_code: &str,
+    runtime: Option<&str>,
+    force: bool,
+    dry_run: bool,
+    caller: &Caller<'_>,
+) -> Response {
+    match promote_inline(target, source_code, runtime, force, dry_run).await {
+        // Audit deployments that reached HubSpot
+        Ok(result) if result.get("revision_id").is_some() => {
+            match audit_promotion(audit_target, target.workflow_id, force, caller, &result) {
+                Ok(()) => (StatusCode::OK, Json(result)).into_response(),
+                Err(e) => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({
+                        "ok": false,
+                        "error": format!("Promotion succeeded but the audit log could not be written: {:#}", e),
+                        "result": result
+                    })),
+                )
+                .into_response(),
+            }
+        }
         Ok(result) => (StatusCode::OK, Json(result)).into_response(),
         Err(e) => (
             StatusCode::BAD_REQUEST,
@@ -211,3 +369,42 @@ async fn validate(Json(cfg): Json<Config>) -> impl IntoResponse {
 
     response
 }
+
+/// Audit log entry for an HTTP promotion that updated the workflow.
+fn audit_promotion(
+    target: Option<&str>,
+    workflow_id: &str,
+    force: bool,
+    caller: &Caller<'_>,
+    result: &serde_json::Value,
+) -> anyhow::Result<()> {
+    let field = |key: &str| result.get(key).cloned().unwrap_or(serde_json::Value::Null);
+    let hash = |key: &str| {
+        result
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+
+    audit::record(&audit::Event {
+        origin: audit::Origin::Http,
+        kind: Kind::Promote,
+        target,
+        workflow_id,
+        actions: vec![audit::AuditAction {
+            action_index: result
+                .get("action_index")
+                .and_then(|v| v.as_u64())
+                .unwrap_or_default() as usize,
+            old_hash: hash("previous_hash"),
+            new_hash: hash("hash"),
+        }],
+        revision_id_before: field("revision_id_before"),
+        revision_id_after: field("revision_id"),
+        force,
+        tests: None,
+        user: Some(&caller.key.0),
+        claimed_actor: caller.claimed_actor,
+        history_id: None,
+    })
+}
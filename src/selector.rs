@@ -0,0 +1,178 @@
+// src/selector.rs
+
+//! Locating the target CUSTOM_CODE action inside a workflow.
+//!
+//! Selector types:
+//! - `secret`:    the action's `secretNames` contains `value`
+//! - `action_id`: the action's HubSpot `actionId` is `value`
+//! - `marker`:    the action's source has a `// hsemulator-id: <value>`
+//!   (or `# hsemulator-id: <value>`) comment
+//! - `position`:  the `value`-th CUSTOM_CODE action of the flow (1-based, in
+//!   the order of the flow's `actions` array)
+//!
+//! With `require_unique` (the default) a selector matching several actions
+//! is an error. Without it the first match is used.
+
+use crate::types::PromoteSelector;
+
+use anyhow::{bail, Result};
+use serde_json::Value as JsonValue;
+
+/// Names accepted in `selector.type`.
+const SELECTOR_TYPES: &[&str] = &["secret", "action_id", "marker", "position"];
+
+const MARKER_PREFIXES: &[&str] = &["// hsemulator-id:", "# hsemulator-id:"];
+
+/// Check the selector's shape (type known, value usable).
+pub fn validate(selector: &PromoteSelector) -> Result<()> {
+    if selector.selector_type.trim().is_empty() || selector.value.trim().is_empty() {
+        bail!("selector.type and selector.value must be set");
+    }
+
+    match selector.selector_type.as_str() {
+        "secret" | "action_id" => {}
+        "marker" => {
+            if selector.value.chars().any(char::is_whitespace) {
+                bail!(
+                    "Marker selector '{}' must not contain whitespace",
+                    selector.value
+                );
+            }
+        }
+        "position" => {
+            if !matches!(selector.value.parse::<usize>(), Ok(n) if n >= 1) {
+                bail!(
+                    "Position selector '{}' must be a positive number (1 = first CUSTOM_CODE action)",
+                    selector.value
+                );
+            }
+        }
+        other => bail!(
+            "Unsupported selector type '{}'. Supported types: {}",
+            other,
+            SELECTOR_TYPES.join(", ")
+        ),
+    }
+
+    Ok(())
+}
+
+/// Human-readable form for error messages, e.g. `marker 'enrich-deal'`.
+pub fn describe(selector: &PromoteSelector) -> String {
+    format!("{} '{}'", selector.selector_type, selector.value)
+}
+
+/// Indices (into `actions`) of every CUSTOM_CODE action the selector matches.
+pub fn matches(flow: &JsonValue, selector: &PromoteSelector) -> Result<Vec<usize>> {
+    validate(selector)?;
+
+    let actions = flow
+        .get("actions")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| anyhow::anyhow!("Workflow JSON missing 'actions' array"))?;
+
+    let mut custom_code = actions
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| a.get("type").and_then(|v| v.as_str()) == Some("CUSTOM_CODE"));
+
+    let value = selector.value.as_str();
+    let matched = match selector.selector_type.as_str() {
+        "secret" => custom_code
+            .filter(|(_, a)| {
+                a.get("secretNames")
+                    .and_then(|v| v.as_array())
+                    .is_some_and(|names| names.iter().any(|n| n.as_str() == Some(value)))
+            })
+            .map(|(idx, _)| idx)
+            .collect(),
+        "action_id" => custom_code
+            .filter(|(_, a)| match a.get("actionId") {
+                Some(JsonValue::String(id)) => id == value,
+                Some(JsonValue::Number(id)) => id.to_string() == value,
+                _ => false,
+            })
+            .map(|(idx, _)| idx)
+            .collect(),
+        "marker" => custom_code
+            .filter(|(_, a)| {
+                a.get("sourceCode")
+                    .and_then(|v| v.as_str())
+                    .and_then(marker_of)
+                    .as_deref()
+                    == Some(value)
+            })
+            .map(|(idx, _)| idx)
+            .collect(),
+        "position" => {
+            let n: usize = value.parse()?;
+            custom_code
+                .nth(n - 1)
+                .map(|(idx, _)| idx)
+                .into_iter()
+                .collect()
+        }
+        _ => unreachable!("validated above"),
+    };
+
+    Ok(matched)
+}
+
+/// Index of the single action to update.
+pub fn find(flow: &JsonValue, selector: &PromoteSelector) -> Result<usize> {
+    let matched = matches(flow, selector)?;
+
+    match matched.as_slice() {
+        [] => bail!(
+            "No CUSTOM_CODE action matches selector {}",
+            describe(selector)
+        ),
+        [idx] => Ok(*idx),
+        [first, ..] => {
+            if selector.require_unique.unwrap_or(true) {
+                bail!(
+                    "Selector {} matched {} actions (require_unique=true). Refusing to proceed.",
+                    describe(selector),
+                    matched.len()
+                );
+            }
+            eprintln!(
+                "WARNING: Selector {} matched {} actions; using the first (require_unique=false).",
+                describe(selector),
+                matched.len()
+            );
+            Ok(*first)
+        }
+    }
+}
+
+/// The `hsemulator-id` marker of a source, if it has one.
+pub fn marker_of(source: &str) -> Option<String> {
+    source.lines().find_map(|line| {
+        let line = line.trim();
+        MARKER_PREFIXES
+            .iter()
+            .find_map(|prefix| line.strip_prefix(prefix))
+            .map(|rest| rest.trim().to_string())
+    })
+}
+
+/// Marker selectors find the action by a comment in its source, so the code
+/// being promoted must carry the same comment or the next lookup fails.
+pub fn check_source(selector: &PromoteSelector, source: &str) -> Result<()> {
+    if selector.selector_type != "marker" {
+        return Ok(());
+    }
+
+    if marker_of(source).as_deref() != Some(selector.value.as_str()) {
+        bail!(
+            "The code being promoted has no '// hsemulator-id: {}' comment.\n\
+            The marker selector finds the action by this comment, so it must stay in the source\n\
+            (use '# hsemulator-id: {}' for Python).",
+            selector.value,
+            selector.value
+        );
+    }
+
+    Ok(())
+}
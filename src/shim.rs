@@ -8,115 +8,393 @@
 //! Responsibilities of a shim:
 //! - Load the action file exactly as written (no modification)
 //! - Call the correct HubSpot entrypoint
-//! - Stream all action logs to STDERR (so they show in the terminal)
-//! - Emit ONE clean JSON object to STDOUT at the very end
+//! - Report logs, callback payloads, errors, phase timings and the final
+//!   result as protocol messages (see `protocol.rs`)
 //!
-//! Keeping STDOUT clean is critical so the Rust side can safely parse
-//! the final result for assertions, snapshots, and flaky detection.
+//! Protocol messages go to a dedicated fd (`HSE_PROTOCOL_FD`), so anything
+//! the action writes to STDOUT / STDERR is just more log output and cannot
+//! corrupt the result.
+//!
+//! With `HSE_WORKER=1` the same shims run as warm workers instead: they
+//! read one request per line from STDIN and tag every message with the
+//! request id (see `worker.rs`).
+
+use crate::limits::Language;
+
+/// Shim file name and source for a language.
+pub fn shim_for(language: Language) -> (&'static str, &'static str) {
+    match language {
+        Language::Node => ("hs_node_runner.mjs", node_shim()),
+        Language::Python => ("hs_python_runner.py", python_shim()),
+    }
+}
 
 /// Node.js shim (ESM-compatible).
 ///
 /// Usage (internal):
 /// node hs_node_runner.mjs <actionFile> <event.json>
+/// HSE_WORKER=1 node hs_node_runner.mjs
 ///
 /// Expected action shape:
 /// exports.main = async (event, callback) => { ... }
 pub fn node_shim() -> &'static str {
     r#"
 import fs from "node:fs";
+import http from "node:http";
+import https from "node:https";
+import { createRequire, syncBuiltinESMExports } from "node:module";
 import path from "node:path";
+import readline from "node:readline";
+import tls from "node:tls";
 import { pathToFileURL } from "node:url";
 
-// Arguments passed by the Rust runner
+// Arguments passed by the Rust runner (cold mode)
 const [, , actionFile, eventPath] = process.argv;
 
-function fatal(message, error = null) {
-  process.stdout.write(JSON.stringify({
-    ok: false,
-    language: "node",
-    callback: null,
-    outputFields: null,
+// Warm worker mode: serve invocations from STDIN until it closes
+const workerMode = process.env.HSE_WORKER === "1";
+
+// Protocol messages go to HSE_PROTOCOL_FD (STDOUT if unset)
+const PROTOCOL_VERSION = 1;
+const protocolFd = process.env.HSE_PROTOCOL_FD ? Number(process.env.HSE_PROTOCOL_FD) : 1;
+
+// Request id of the invocation being served (warm mode)
+let currentId = null;
+
+function send(type, body) {
+  const message = { v: PROTOCOL_VERSION, type, ...body };
+  if (currentId !== null) message.id = currentId;
+  let buf = Buffer.from(JSON.stringify(message) + "\n");
+  while (buf.length) {
+    buf = buf.subarray(fs.writeSync(protocolFd, buf));
+  }
+}
+
+function sendLog(level, message) {
+  send("log", { level, ts: Date.now(), message });
+}
+
+// Set when the action could not run at all (cold mode exits non-zero)
+let runtimeFailed = false;
+
+function runtimeError(message, error = null) {
+  runtimeFailed = true;
+  send("error", {
     error: {
       type: "runtime",
       message,
       stack: error?.stack || null
     }
-  }));
+  });
+  return { ok: false, language: "node", outputFields: null };
+}
+
+function fatal(message, error = null) {
+  send("result", { output: runtimeError(message, error) });
   process.exit(1);
 }
 
-if (!actionFile || !eventPath) {
+if (!workerMode && (!actionFile || !eventPath)) {
   fatal("Usage: node hs_node_runner.mjs <actionFile> <event.json>");
 }
 
-let event;
-try {
-  event = JSON.parse(fs.readFileSync(eventPath, "utf8"));
-} catch (e) {
-  fatal("Failed to read or parse event.json", e);
+// Console methods become log messages with a level
+const consoleLevels = {
+  debug: "debug",
+  trace: "debug",
+  log: "info",
+  info: "info",
+  warn: "warn",
+  error: "error"
+};
+for (const [method, level] of Object.entries(consoleLevels)) {
+  console[method] = (...args) => sendLog(level, args.join(" "));
 }
 
-// Route logs to STDERR so STDOUT stays clean JSON
-const stderr = console.error;
-const rawStderr = console.error;
+// Network sandbox: force http(s) agents and fetch through the runner's proxy
+if (process.env.HSE_NETWORK_PROXY) {
+  installNetworkSandbox(new URL(process.env.HSE_NETWORK_PROXY));
+}
 
-console.log = (...args) => {
-  rawStderr("__HSE_LOG__ " + args.join(" "));
-};
+function installNetworkSandbox(proxy) {
+  const proxyHost = proxy.hostname;
+  const proxyPort = Number(proxy.port);
+  const isProxy = (host, port) => host === proxyHost && Number(port) === proxyPort;
+
+  // Bound now so request capture (installed later) does not record tunnels
+  const connectRequest = http.request;
+
+  const tunnel = (host, port, cb) => {
+    const req = connectRequest({
+      host: proxyHost,
+      port: proxyPort,
+      method: "CONNECT",
+      path: `${host}:${port}`,
+      headers: { host: `${host}:${port}` },
+      agent: false
+    });
+    req.once("connect", (res, socket) => {
+      if (res.statusCode === 200) return cb(null, socket);
+      socket.destroy();
+      cb(new Error(`hsemulate network sandbox blocked ${host}:${port}`));
+    });
+    req.once("error", cb);
+    req.end();
+  };
 
-console.error = (...args) => {
-  rawStderr("__HSE_ERR__ " + args.join(" "));
-};
+  const directHttp = http.Agent.prototype.createConnection;
+  http.Agent.prototype.createConnection = function (options, cb) {
+    const host = options.host || options.hostname || "localhost";
+    const port = options.port || 80;
+    if (options.socketPath || isProxy(host, port)) {
+      return directHttp.call(this, options, cb);
+    }
+    tunnel(host, port, cb);
+  };
+
+  https.Agent.prototype.createConnection = function (options, cb) {
+    const host = options.host || options.hostname || "localhost";
+    const port = options.port || 443;
+    tunnel(host, port, (err, socket) => {
+      if (err) return cb(err);
+      cb(null, tls.connect({ ...options, socket, servername: options.servername || host }));
+    });
+  };
+
+  // fetch (undici) ignores agents; re-implement it on top of http(s).request
+  const requestFns = { "http:": http.request, "https:": https.request };
+
+  globalThis.fetch = async function sandboxedFetch(input, init) {
+    let request = new Request(input, init);
+    const body = ["GET", "HEAD"].includes(request.method)
+      ? null
+      : Buffer.from(await request.arrayBuffer());
+
+    for (let redirects = 0; ; redirects++) {
+      const url = new URL(request.url);
+      const res = await new Promise((resolve, reject) => {
+        const doRequest = requestFns[url.protocol] || requestFns["http:"];
+        const req = doRequest(url, {
+          method: request.method,
+          headers: Object.fromEntries(request.headers)
+        }, resolve);
+        req.once("error", (e) => reject(new TypeError("fetch failed", { cause: e })));
+        if (body) req.write(body);
+        req.end();
+      });
+
+      const location = res.headers.location;
+      if ([301, 302, 303, 307, 308].includes(res.statusCode) && location
+          && request.redirect === "follow" && redirects < 20) {
+        res.resume();
+        const method = res.statusCode === 303 ? "GET" : request.method;
+        request = new Request(new URL(location, url), {
+          method,
+          headers: request.headers,
+          body: method === "GET" || method === "HEAD" ? null : body
+        });
+        continue;
+      }
+
+      const chunks = [];
+      for await (const chunk of res) chunks.push(chunk);
+
+      const headers = new Headers();
+      for (const [k, v] of Object.entries(res.headers)) {
+        for (const value of [].concat(v)) headers.append(k, value);
+      }
+      const nullBody = [204, 205, 304].includes(res.statusCode) || request.method === "HEAD";
+      return new Response(nullBody ? null : Buffer.concat(chunks), {
+        status: res.statusCode,
+        statusText: res.statusMessage,
+        headers
+      });
+    }
+  };
+}
 
+// Record every outbound request (reported as `requests` in the result)
+const capturedRequests = [];
+installRequestCapture();
+
+function parseCapturedBody(text) {
+  if (text === null || text === undefined || text === "") return null;
+  try { return JSON.parse(text); } catch { return text; }
+}
 
-let callbackPayload = null;
-const callback = (payload) => { callbackPayload = payload; };
-
-// Normalize path, then convert to file:// URL (Windows-safe)
-const resolvedPath = path.resolve(actionFile);
-const actionUrl = pathToFileURL(resolvedPath).href;
-
-// IMPORTANT:
-// - Do NOT fall back to `require()` here.
-// - If the file has a syntax error, `import()` will throw a SyntaxError,
-//   which is exactly what you want to see.
-// - Falling back to require on Windows can introduce the EISDIR 'C:' issue
-//   when paths include \\?\ prefixes.
-let mod;
-try {
-  mod = await import(actionUrl);
-} catch (e) {
-  fatal("Failed to load action file", e);
+function installRequestCapture() {
+  for (const [mod, scheme] of [[http, "http:"], [https, "https:"]]) {
+    const original = mod.request;
+
+    mod.request = function capturedRequest(...args) {
+      const req = original.apply(this, args);
+      const chunks = [];
+      const hostHeader = req.getHeader("host") || req.host;
+      const entry = {
+        method: req.method,
+        url: /^https?:\/\//.test(req.path) ? req.path : `${scheme}//${hostHeader}${req.path}`,
+        headers: req.getHeaders(),
+        body: null,
+        status: null
+      };
+      capturedRequests.push(entry);
+
+      const write = req.write;
+      const end = req.end;
+      req.write = function (chunk, ...rest) {
+        if (chunk && typeof chunk !== "function") chunks.push(Buffer.from(chunk));
+        return write.call(this, chunk, ...rest);
+      };
+      req.end = function (chunk, ...rest) {
+        if (chunk && typeof chunk !== "function") chunks.push(Buffer.from(chunk));
+        return end.call(this, chunk, ...rest);
+      };
+
+      req.once("finish", () => {
+        entry.headers = req.getHeaders();
+        entry.body = parseCapturedBody(Buffer.concat(chunks).toString("utf8"));
+      });
+      req.once("response", (res) => { entry.status = res.statusCode; });
+      return req;
+    };
+
+    mod.get = function capturedGet(...args) {
+      const req = mod.request(...args);
+      req.end();
+      return req;
+    };
+  }
+  syncBuiltinESMExports();
+
+  const innerFetch = globalThis.fetch;
+  globalThis.fetch = async function capturedFetch(input, init) {
+    const request = new Request(input, init);
+    const entry = {
+      method: request.method,
+      url: request.url,
+      headers: Object.fromEntries(request.headers),
+      body: ["GET", "HEAD"].includes(request.method)
+        ? null
+        : parseCapturedBody(await request.clone().text()),
+      status: null
+    };
+    capturedRequests.push(entry);
+
+    const res = await innerFetch(request);
+    entry.status = res.status;
+    return res;
+  };
 }
 
-// HubSpot expects `main` to be exported
-const fn = mod?.main || mod?.default?.main;
-if (typeof fn !== "function") {
-  fatal("Action file must export main(event, callback)");
+// Loaded action modules by path (warm workers reuse them while unchanged)
+const loadedActions = new Map();
+const cjsRequire = createRequire(import.meta.url);
+
+async function loadAction(file) {
+  // Normalize path, then convert to file:// URL (Windows-safe)
+  const resolvedPath = path.resolve(file);
+  const actionUrl = pathToFileURL(resolvedPath).href;
+
+  if (!workerMode) {
+    return import(actionUrl);
+  }
+
+  // Re-import when the file changes on disk. CommonJS actions are cached by
+  // filename, so that cache entry has to go too.
+  const version = fs.statSync(resolvedPath).mtimeMs;
+  const cached = loadedActions.get(resolvedPath);
+  if (cached && cached.version === version) {
+    return cached.mod;
+  }
+  delete cjsRequire.cache[resolvedPath];
+  const mod = await import(`${actionUrl}?v=${version}`);
+  loadedActions.set(resolvedPath, { version, mod });
+  return mod;
 }
 
-let ok = true;
-let error = null;
-let actionResult = null;
-
-try {
-  actionResult = await fn(event, callback);
-} catch (e) {
-  ok = false;
-  error = {
-    type: "action",
-    message: e?.message || String(e),
-    stack: e?.stack || null
+async function runAction(file, event) {
+  capturedRequests.length = 0;
+
+  const callback = (payload) => { send("callback", { payload }); };
+
+  // IMPORTANT:
+  // - Do NOT fall back to `require()` here.
+  // - If the file has a syntax error, `import()` will throw a SyntaxError,
+  //   which is exactly what you want to see.
+  // - Falling back to require on Windows can introduce the EISDIR 'C:' issue
+  //   when paths include \\?\ prefixes.
+  let mod;
+  const loadStart = performance.now();
+  try {
+    mod = await loadAction(file);
+  } catch (e) {
+    return runtimeError("Failed to load action file", e);
+  }
+  send("phase", { name: "load", duration_ms: performance.now() - loadStart });
+
+  // HubSpot expects `main` to be exported
+  const fn = mod?.main || mod?.default?.main;
+  if (typeof fn !== "function") {
+    return runtimeError("Action file must export main(event, callback)");
+  }
+
+  let ok = true;
+  let actionResult = null;
+
+  // `hsemulate run --debug`: stop here, then "step into" to enter main()
+  if (process.env.HSE_DEBUG) {
+    debugger;
+  }
+
+  const executeStart = performance.now();
+  try {
+    actionResult = await fn(event, callback);
+  } catch (e) {
+    ok = false;
+    send("error", {
+      error: {
+        type: "action",
+        message: e?.message || String(e),
+        stack: e?.stack || null
+      }
+    });
+  }
+  send("phase", { name: "execute", duration_ms: performance.now() - executeStart });
+
+  return {
+    ok,
+    language: "node",
+    outputFields: actionResult?.outputFields || null,
+    requests: [...capturedRequests]
   };
 }
 
-process.stdout.write(JSON.stringify({
-  ok,
-  language: "node",
-  callback: callbackPayload,
-  outputFields: actionResult?.outputFields || null,
-  error
-}));
+if (workerMode) {
+  // One JSON request per line: { id, action, event }
+  // Every message sent while serving it carries its id; `result` is last.
+  const lines = readline.createInterface({ input: process.stdin });
+  for await (const line of lines) {
+    if (!line.trim()) continue;
+    const request = JSON.parse(line);
+    currentId = request.id;
+    const output = await runAction(request.action, request.event);
+    send("result", { output });
+  }
+  process.exit(0);
+} else {
+  let event;
+  try {
+    event = JSON.parse(fs.readFileSync(eventPath, "utf8"));
+  } catch (e) {
+    fatal("Failed to read or parse event.json", e);
+  }
+
+  const output = await runAction(actionFile, event);
+  send("result", { output });
+  if (runtimeFailed) {
+    process.exit(1);
+  }
+}
 "#
 }
 
@@ -124,6 +402,7 @@ process.stdout.write(JSON.stringify({
 ///
 /// Usage (internal):
 /// python hs_python_runner.py <actionFile.py> <event.json>
+/// HSE_WORKER=1 python hs_python_runner.py
 ///
 /// Expected action shape:
 /// def main(event): ...
@@ -133,35 +412,87 @@ pub fn python_shim() -> &'static str {
 # Runtime shim for executing HubSpot Custom Code Actions (Python).
 #
 # Contract:
-# - User code may print/log freely
-# - ALL user output is redirected to STDERR
-# - STDOUT emits EXACTLY ONE JSON object at the end
-# - Any deviation is a hard error
+# - User code may print/log freely; printed lines become log messages
+# - Everything the runner needs is sent as line-delimited JSON protocol
+#   messages on HSE_PROTOCOL_FD (STDOUT if unset), ending with "result"
 
+import http.client
 import importlib.util
+import io
 import json
+import os
 import sys
+import time
 import traceback
 from contextlib import redirect_stdout
 from io import StringIO
 
 
+# Warm worker mode: serve invocations from STDIN until it closes
+WORKER_MODE = os.environ.get("HSE_WORKER") == "1"
+
+PROTOCOL_VERSION = 1
+PROTOCOL_FD = int(os.environ.get("HSE_PROTOCOL_FD") or 1)
+
+# Request id of the invocation being served (warm mode)
+CURRENT_ID = None
+
+
+def send(type_, **body):
+    message = {"v": PROTOCOL_VERSION, "type": type_, **body}
+    if CURRENT_ID is not None:
+        message["id"] = CURRENT_ID
+    data = (json.dumps(message) + "\n").encode("utf-8")
+    while data:
+        data = data[os.write(PROTOCOL_FD, data):]
+
+
+def send_log(level, line):
+    send("log", level=level, ts=int(time.time() * 1000), message=line)
+
+
+class LogWriter(io.TextIOBase):
+    # Turns everything printed by the action into log messages, line by line
+
+    def __init__(self, level):
+        self.level = level
+        self.pending = ""
+
+    def writable(self):
+        return True
+
+    def write(self, text):
+        self.pending += text
+        while "\n" in self.pending:
+            line, self.pending = self.pending.split("\n", 1)
+            if line.strip():
+                send_log(self.level, line)
+        return len(text)
+
+    def close_line(self):
+        if self.pending.strip():
+            send_log(self.level, self.pending)
+        self.pending = ""
+
+
+# Set when the action could not run at all (cold mode exits non-zero)
+RUNTIME_FAILED = False
+
+
+def runtime_error(message, stack=None):
+    global RUNTIME_FAILED
+    RUNTIME_FAILED = True
+    send("error", error={"type": "runtime", "message": message, "stack": stack})
+    return {
+        "ok": False,
+        "language": "python",
+        "result": None,
+        "outputFields": None,
+    }
+
+
 def fatal(message, stack=None):
-    sys.stdout.write(
-        json.dumps(
-            {
-                "ok": False,
-                "language": "python",
-                "result": None,
-                "outputFields": None,
-                "error": {
-                    "type": "runtime",
-                    "message": message,
-                    "stack": stack,
-                },
-            }
-        )
-    )
+    send("result", output=runtime_error(message, stack))
     sys.exit(1)
 
 
@@ -175,50 +506,197 @@ def import_python_file(file_path: str):
     return module
 
 
-def emit_log(line: str):
-    print("__HSE_LOG__", line, file=sys.stderr)
+CAPTURED_REQUESTS = []
+
+
+def parse_captured_body(data):
+    if not data:
+        return None
+    if isinstance(data, str):
+        text = data
+    elif isinstance(data, (bytes, bytearray)):
+        text = bytes(data).decode("utf-8", errors="replace")
+    else:
+        return "<stream>"
+    try:
+        return json.loads(text)
+    except ValueError:
+        return text
+
+
+def install_request_capture():
+    # urllib, requests / urllib3 and most SDKs build on http.client
+    conn = http.client.HTTPConnection
+    orig_putrequest = conn.putrequest
+    orig_putheader = conn.putheader
+    orig_endheaders = conn.endheaders
+    orig_send = conn.send
+    orig_getresponse = conn.getresponse
+
+    def putrequest(self, method, url, *args, **kwargs):
+        https = isinstance(self, http.client.HTTPSConnection)
+        if url.startswith(("http://", "https://")):
+            full = url
+        else:
+            host = getattr(self, "_tunnel_host", None) or self.host
+            port = getattr(self, "_tunnel_port", None) or self.port
+            scheme = "https" if https else "http"
+            default = 443 if https else 80
+            netloc = host if port in (None, default) else f"{host}:{port}"
+            full = f"{scheme}://{netloc}{url}"
+
+        self._hse_request = {
+            "method": method,
+            "url": full,
+            "headers": {},
+            "body": None,
+            "status": None,
+        }
+        self._hse_body = None
+        CAPTURED_REQUESTS.append(self._hse_request)
+        return orig_putrequest(self, method, url, *args, **kwargs)
+
+    def putheader(self, header, *values):
+        entry = getattr(self, "_hse_request", None)
+        if entry is not None:
+            name = header.decode("latin-1") if isinstance(header, bytes) else str(header)
+            entry["headers"][name.lower()] = ", ".join(
+                v.decode("latin-1") if isinstance(v, bytes) else str(v) for v in values
+            )
+        return orig_putheader(self, header, *values)
+
+    def endheaders(self, message_body=None, *args, **kwargs):
+        self._hse_body = None
+        result = orig_endheaders(self, message_body, *args, **kwargs)
+        # Body passed here, or streamed afterwards via send()
+        self._hse_body = [message_body] if message_body is not None else []
+        return result
+
+    def send(self, data):
+        body = getattr(self, "_hse_body", None)
+        if body is not None:
+            body.append(data)
+        return orig_send(self, data)
+
+    def getresponse(self, *args, **kwargs):
+        entry = getattr(self, "_hse_request", None)
+        body = getattr(self, "_hse_body", None)
+        self._hse_body = None
+        if entry is not None and body:
+            if all(isinstance(b, (bytes, bytearray, str)) for b in body):
+                joined = b"".join(b.encode("utf-8") if isinstance(b, str) else bytes(b) for b in body)
+                entry["body"] = parse_captured_body(joined)
+            else:
+                entry["body"] = "<stream>"
+
+        response = orig_getresponse(self, *args, **kwargs)
+        if entry is not None:
+            entry["status"] = response.status
+            self._hse_request = None
+        return response
+
+    conn.putrequest = putrequest
+    conn.putheader = putheader
+    conn.endheaders = endheaders
+    conn.send = send
+    conn.getresponse = getresponse
+
+
+def start_coverage(action_file: str):
+    # Enabled by `hsemulate test --coverage`; needs the `coverage` package
+    if not os.environ.get("HSE_COVERAGE_FILE"):
+        return None
+    try:
+        import coverage
+    except ImportError:
+        return None
+
+    cov = coverage.Coverage(
+        data_file=None,
+        branch=True,
+        include=[os.path.abspath(action_file)],
+    )
+    cov.start()
+    return cov
+
+
+def stop_coverage(cov):
+    if cov is None:
+        return
+    cov.stop()
+    try:
+        with redirect_stdout(StringIO()):
+            cov.json_report(outfile=os.environ["HSE_COVERAGE_FILE"])
+    except Exception:
+        emit_err("coverage: failed to write report: " + traceback.format_exc())
 
 
 def emit_err(line: str):
-    print("__HSE_ERR__", line, file=sys.stderr)
+    send_log("error", line)
 
 
-def main():
-    if len(sys.argv) < 3:
-        fatal("Usage: python hs_python_runner.py <actionFile.py> <event.json>")
+# Loaded action modules by path (warm workers reuse them while unchanged)
+LOADED_ACTIONS = {}
 
-    action_file = sys.argv[1]
-    event_path = sys.argv[2]
 
-    # Load event
-    try:
-        with open(event_path, "r", encoding="utf-8") as f:
-            event = json.load(f)
-    except Exception:
-        fatal("Failed to read or parse event.json", traceback.format_exc())
+def load_action(action_file: str):
+    if not WORKER_MODE:
+        return import_python_file(action_file)
+
+    # Re-import when the file changes on disk
+    version = os.stat(action_file).st_mtime_ns
+    cached = LOADED_ACTIONS.get(action_file)
+    if cached is not None and cached[0] == version:
+        return cached[1]
+
+    module = import_python_file(action_file)
+    LOADED_ACTIONS[action_file] = (version, module)
+    return module
+
+
+def run_action(action_file: str, event):
+    CAPTURED_REQUESTS.clear()
+
+    cov = start_coverage(action_file)
 
     # Import action module
+    load_start = time.perf_counter()
     try:
-        module = import_python_file(action_file)
+        module = load_action(action_file)
     except Exception:
-        fatal("Failed to load action file", traceback.format_exc())
+        return runtime_error("Failed to load action file", traceback.format_exc())
+    send("phase", name="load", duration_ms=(time.perf_counter() - load_start) * 1000)
 
     if not hasattr(module, "main") or not callable(module.main):
-        fatal("Action file must define: def main(event)")
+        return runtime_error("Action file must define: def main(event)")
 
     ok = True
     result = None
     output_fields = None
     error = None
 
-    # Capture ALL stdout produced by user code
-    stdout_buffer = StringIO()
+    # Stream ALL stdout produced by user code as log messages
+    stdout_writer = LogWriter("info")
     old_stdout = sys.stdout
 
+    # `hsemulate run --debug`: stop here, then "step into" to enter main()
+    if os.environ.get("HSE_DEBUG"):
+        import debugpy
+
+        debugpy.breakpoint()
+
+    execute_start = time.perf_counter()
     try:
-        sys.stdout = stdout_buffer
-        with redirect_stdout(stdout_buffer):
+        sys.stdout = stdout_writer
+        with redirect_stdout(stdout_writer):
             result = module.main(event)
+    except MemoryError:
+        ok = False
+        error = {
+            "type": "memory",
+            "message": "Memory limit exceeded",
+            "stack": traceback.format_exc(),
+        }
     except Exception as e:
         ok = False
         error = {
@@ -228,30 +706,68 @@ def main():
         }
     finally:
         sys.stdout = old_stdout
+        stdout_writer.close_line()
+    send("phase", name="execute", duration_ms=(time.perf_counter() - execute_start) * 1000)
 
-    # Flush captured stdout as structured logs
-    captured = stdout_buffer.getvalue()
-    if captured:
-        for line in captured.splitlines():
-            if line.strip():
-                emit_log(line)
+    if error is not None:
+        send("error", error=error)
+
+    stop_coverage(cov)
 
     # Extract outputFields from result if present
     if isinstance(result, dict):
         output_fields = result.get("outputFields")
 
-    # Emit final JSON — MUST be the only STDOUT output
-    sys.stdout.write(
-        json.dumps(
-            {
-                "ok": ok,
-                "language": "python",
-                "result": result,
-                "outputFields": output_fields,
-                "error": error,
-            }
-        )
-    )
+    return {
+        "ok": ok,
+        "language": "python",
+        "result": result,
+        "outputFields": output_fields,
+        "requests": list(CAPTURED_REQUESTS),
+    }
+
+
+def serve():
+    # One JSON request per line: {"id", "action", "event"}
+    # Every message sent while serving it carries its id; "result" is last.
+    global CURRENT_ID
+    while True:
+        line = sys.stdin.readline()
+        if not line:
+            break
+        if not line.strip():
+            continue
+
+        request = json.loads(line)
+        CURRENT_ID = request["id"]
+        send("result", output=run_action(request["action"], request["event"]))
+
+
+def main():
+    install_request_capture()
+
+    if WORKER_MODE:
+        serve()
+        return
+
+    if len(sys.argv) < 3:
+        fatal("Usage: python hs_python_runner.py <actionFile.py> <event.json>")
+
+    action_file = sys.argv[1]
+    event_path = sys.argv[2]
+
+    # Load event
+    try:
+        with open(event_path, "r", encoding="utf-8") as f:
+            event = json.load(f)
+    except Exception:
+        fatal("Failed to read or parse event.json", traceback.format_exc())
+
+    output = run_action(action_file, event)
+    send("result", output=output)
+
+    if RUNTIME_FAILED:
+        sys.exit(1)
 
 
 if __name__ == "__main__":
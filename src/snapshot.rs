@@ -13,6 +13,13 @@ pub fn snapshot_path(base_dir: &Path, key: &str) -> PathBuf {
     base_dir.join(format!("{}.snapshot.json", key))
 }
 
+/// Build the log snapshot file path for a given snapshot key.
+///
+/// snapshots/<key>.logs.json
+pub fn log_snapshot_path(base_dir: &Path, key: &str) -> PathBuf {
+    base_dir.join(format!("{}.logs.json", key))
+}
+
 /// Load a snapshot file from disk.
 pub fn load_snapshot(path: &Path) -> Result<Value> {
     let raw = std::fs::read_to_string(path)
@@ -0,0 +1,162 @@
+// src/stats.rs
+
+//! Small descriptive statistics helpers.
+//!
+//! Used when summarising repeated invocations:
+//! - flakiness analysis (`run --repeat N`)
+//! - benchmarking
+//!
+//! All helpers operate on raw samples and never panic on empty input.
+
+use serde::{Deserialize, Serialize};
+
+/// Min / median / p95 / max spread of a sample set.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Spread {
+    pub min: u64,
+    pub median: u64,
+    pub p95: u64,
+    pub max: u64,
+}
+
+impl Spread {
+    /// Build a spread from unsorted samples.
+    ///
+    /// Returns `None` when there are no samples.
+    pub fn from_samples(samples: &[u64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+
+        Some(Self {
+            min: sorted[0],
+            median: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+/// Nearest-rank percentile of an already sorted sample set.
+///
+/// `p` is in the range 0..=100. Returns 0 for empty input.
+pub fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+
+    let p = p.clamp(0.0, 100.0);
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    let idx = rank.saturating_sub(1).min(sorted.len() - 1);
+    sorted[idx]
+}
+
+/// Full descriptive summary of a sample set (used by benchmarking).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub n: u64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub min: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl Summary {
+    /// Build a summary from unsorted samples.
+    ///
+    /// `stddev` is the sample standard deviation (n - 1).
+    /// Returns `None` when there are no samples.
+    pub fn from_samples(samples: &[u64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+
+        let n = sorted.len() as f64;
+        let mean = sorted.iter().map(|v| *v as f64).sum::<f64>() / n;
+        let variance = if sorted.len() > 1 {
+            sorted
+                .iter()
+                .map(|v| (*v as f64 - mean).powi(2))
+                .sum::<f64>()
+                / (n - 1.0)
+        } else {
+            0.0
+        };
+
+        Some(Self {
+            n: sorted.len() as u64,
+            mean,
+            stddev: variance.sqrt(),
+            min: sorted[0],
+            p50: percentile(&sorted, 50.0),
+            p90: percentile(&sorted, 90.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+/// Welch's t-test statistic for `candidate` being larger than `baseline`.
+///
+/// Returns `(t, degrees_of_freedom)`, or `None` when either side has fewer
+/// than two samples. When both sides have zero variance, any difference in
+/// means is reported as infinitely significant.
+pub fn welch_t(baseline: &Summary, candidate: &Summary) -> Option<(f64, f64)> {
+    if baseline.n < 2 || candidate.n < 2 {
+        return None;
+    }
+
+    let va = baseline.stddev.powi(2) / baseline.n as f64;
+    let vb = candidate.stddev.powi(2) / candidate.n as f64;
+    let diff = candidate.mean - baseline.mean;
+
+    if va + vb == 0.0 {
+        let t = if diff == 0.0 {
+            0.0
+        } else {
+            diff.signum() * f64::INFINITY
+        };
+        return Some((t, (baseline.n + candidate.n - 2) as f64));
+    }
+
+    let t = diff / (va + vb).sqrt();
+
+    let df_num = (va + vb).powi(2);
+    let df_den = va.powi(2) / (baseline.n as f64 - 1.0) + vb.powi(2) / (candidate.n as f64 - 1.0);
+    let df = if df_den == 0.0 { 1.0 } else { df_num / df_den };
+
+    Some((t, df))
+}
+
+/// One-sided critical t value at the 95% confidence level.
+///
+/// Exact table up to 30 degrees of freedom, normal approximation beyond.
+pub fn t_critical_95(df: f64) -> f64 {
+    const TABLE: [f64; 30] = [
+        6.314, 2.920, 2.353, 2.132, 2.015, 1.943, 1.895, 1.860, 1.833, 1.812, 1.796, 1.782,
+        1.771, 1.761, 1.753, 1.746, 1.740, 1.734, 1.729, 1.725, 1.721, 1.717, 1.714, 1.711,
+        1.708, 1.706, 1.703, 1.701, 1.699, 1.697,
+    ];
+
+    if df < 1.0 {
+        return TABLE[0];
+    }
+
+    let idx = df.floor() as usize;
+    if idx <= TABLE.len() {
+        TABLE[idx - 1]
+    } else {
+        1.645
+    }
+}
@@ -0,0 +1,245 @@
+// src/status.rs
+
+//! `hsemulate status`.
+//!
+//! Checks every action of every target in `.hsemulator/cicd.yaml` against
+//! the local code promoted to it, using the `hsemulator-sha` marker left by
+//! `promote`:
+//! - `in_sync`:   the deployed marker matches the local hash
+//! - `drifted`:   the deployed marker differs (local changes not promoted,
+//!   or an older promotion), or the deployed secrets or input/output fields
+//!   differ from the ones declared in config.yaml
+//! - `unmanaged`: the deployed action has no marker
+//! - `missing`:   the workflow or the selected action does not exist
+//! - `error`:     the target could not be checked, its local config failed
+//!   to load, or it has no actions
+//!
+//! Any action not in sync makes the command exit non-zero, for nightly CI.
+
+use crate::fields::ActionFields;
+use crate::promote;
+use crate::selector;
+use crate::types::PromoteSelector;
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum SyncState {
+    InSync,
+    Drifted,
+    Unmanaged,
+    Missing,
+    Error,
+}
+
+impl SyncState {
+    fn label(self) -> &'static str {
+        match self {
+            SyncState::InSync => "in sync",
+            SyncState::Drifted => "drifted",
+            SyncState::Unmanaged => "unmanaged",
+            SyncState::Missing => "missing",
+            SyncState::Error => "error",
+        }
+    }
+}
+
+/// Sync state of one action of a promotion target.
+#[derive(Debug, Serialize)]
+struct TargetStatus {
+    target: String,
+    workflow_id: String,
+    /// The action's selector, e.g. `marker 'enrich-deal'`
+    action: String,
+    status: SyncState,
+    /// Hash of the local code promoted to this action
+    local_hash: Option<String>,
+    /// Hash from the deployed action's marker
+    deployed_hash: Option<String>,
+    /// Why the target is missing or could not be checked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+/// Entry point for `hsemulate status [--json]`.
+pub async fn handle(json_output: bool, config_path: PathBuf) -> Result<()> {
+    let cicd = promote::load_cicd()?;
+    let token = promote::resolve_token(&cicd)?;
+
+    let client = reqwest::Client::new();
+    let headers = promote::hubspot_headers(&token)?;
+
+    // Configs are often shared between targets
+    let mut local_actions: BTreeMap<PathBuf, Result<(String, ActionFields), String>> =
+        BTreeMap::new();
+
+    let mut statuses = Vec::new();
+    for (name, target) in &cicd.targets {
+        let flow = match promote::resolve_base_url(&cicd, target) {
+            Ok(base_url) => {
+                promote::hubspot_find_flow(&client, &headers, &base_url, &target.workflow_id).await
+            }
+            Err(e) => Err(e),
+        };
+
+        let actions = target.actions(&config_path);
+        if actions.is_empty() {
+            statuses.push(TargetStatus {
+                target: name.clone(),
+                workflow_id: target.workflow_id.clone(),
+                action: "-".to_string(),
+                status: SyncState::Error,
+                local_hash: None,
+                deployed_hash: None,
+                message: Some("Target has neither a selector nor actions".to_string()),
+            });
+            continue;
+        }
+
+        for action in actions {
+            let local = local_actions
+                .entry(action.config.clone())
+                .or_insert_with(|| {
+                    promote::load_action_source(&action.config)
+                        .with_context(|| {
+                            format!(
+                                "Failed to load action source via config at {:?}",
+                                action.config
+                            )
+                        })
+                        .map(|source| (source.hash(), source.fields))
+                        .map_err(|e| format!("{:#}", e))
+                })
+                .clone();
+
+            let (status, deployed_hash, message) = match (&local, &flow) {
+                (Err(e), _) => (SyncState::Error, None, Some(e.clone())),
+                (Ok((local_hash, fields)), Ok(Some(flow))) => {
+                    check_action(flow, action.selector, local_hash, fields)
+                        .unwrap_or_else(|e| (SyncState::Error, None, Some(format!("{:#}", e))))
+                }
+                (Ok(_), Ok(None)) => {
+                    let message = format!("Workflow {} not found", target.workflow_id);
+                    (SyncState::Missing, None, Some(message))
+                }
+                (Ok(_), Err(e)) => (SyncState::Error, None, Some(format!("{:#}", e))),
+            };
+            statuses.push(TargetStatus {
+                target: name.clone(),
+                workflow_id: target.workflow_id.clone(),
+                action: selector::describe(action.selector),
+                status,
+                local_hash: local.ok().map(|(hash, _)| hash),
+                deployed_hash,
+                message,
+            });
+        }
+    }
+
+    let in_sync = statuses.iter().all(|s| s.status == SyncState::InSync);
+
+    if json_output {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({
+                "ok": in_sync,
+                "targets": statuses,
+            }))?
+        );
+    } else {
+        print_table(&statuses);
+    }
+
+    if !in_sync {
+        let out_of_sync = statuses
+            .iter()
+            .filter(|s| s.status != SyncState::InSync)
+            .count();
+        bail!("{} of {} actions not in sync", out_of_sync, statuses.len());
+    }
+
+    Ok(())
+}
+
+fn check_action(
+    flow: &JsonValue,
+    selector: &PromoteSelector,
+    local_hash: &str,
+    fields: &ActionFields,
+) -> Result<(SyncState, Option<String>, Option<String>)> {
+    if selector::matches(flow, selector)?.is_empty() {
+        let message = "No CUSTOM_CODE action matches the selector".to_string();
+        return Ok((SyncState::Missing, None, Some(message)));
+    }
+
+    let action_index = selector::find(flow, selector)?;
+    let deployed = promote::get_action_source_code(flow, action_index)?;
+
+    // Declared secrets and fields are part of what `promote` deploys
+    let drifted_fields: Vec<&str> = flow
+        .pointer(&format!("/actions/{}", action_index))
+        .map(|action| fields.changes(action))
+        .unwrap_or_default()
+        .iter()
+        .map(|change| change.key)
+        .collect();
+    let message = (!drifted_fields.is_empty()).then(|| {
+        format!(
+            "Deployed {} differ from config.yaml",
+            drifted_fields.join(", ")
+        )
+    });
+
+    Ok(match promote::extract_hash_marker(&deployed) {
+        None => (SyncState::Unmanaged, None, message),
+        Some(hash) if hash == local_hash && message.is_none() => {
+            (SyncState::InSync, Some(hash), None)
+        }
+        Some(hash) => (SyncState::Drifted, Some(hash), message),
+    })
+}
+
+fn print_table(statuses: &[TargetStatus]) {
+    let width = statuses
+        .iter()
+        .map(|s| s.target.len())
+        .max()
+        .unwrap_or(0)
+        .max("TARGET".len());
+    let action_width = statuses
+        .iter()
+        .map(|s| s.action.len())
+        .max()
+        .unwrap_or(0)
+        .max("ACTION".len());
+
+    println!(
+        "{:<width$}  {:<action_width$}  {:<9}  {:<12}  DEPLOYED",
+        "TARGET", "ACTION", "STATUS", "LOCAL"
+    );
+    for s in statuses {
+        let local = s.local_hash.as_deref().map(short).unwrap_or("-");
+        let deployed = s.deployed_hash.as_deref().map(short).unwrap_or("-");
+        println!(
+            "{:<width$}  {:<action_width$}  {:<9}  {:<12}  {}",
+            s.target,
+            s.action,
+            s.status.label(),
+            local,
+            deployed
+        );
+        if let Some(message) = &s.message {
+            println!("{:<width$}  {}", "", message);
+        }
+    }
+}
+
+/// First 12 hex digits, as shown in tables.
+fn short(hash: &str) -> &str {
+    hash.get(..12).unwrap_or(hash)
+}
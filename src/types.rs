@@ -1,10 +1,15 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct PromoteSelector {
     #[serde(rename = "type")]
     pub selector_type: String,
+    /// Numbers are accepted too (`action_id` / `position`)
+    #[serde(deserialize_with = "string_or_number")]
     pub value: String,
+    /// Fail when several actions match (default); otherwise use the first
+    #[serde(default)]
+    pub require_unique: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -22,4 +27,39 @@ pub struct PromoteRequest {
 
     #[serde(default)]
     pub dry_run: bool,
-}
\ No newline at end of file
+
+    /// Who claims to be promoting; audited as `claimed_actor`, unverified
+    #[serde(default)]
+    pub actor: Option<String>,
+}
+
+/// Body of `POST /projects/{project}/targets/{target}/promote`; the rest
+/// comes from the runtime's registry.
+#[derive(Debug, Deserialize)]
+pub struct TargetPromoteRequest {
+    pub source_code: String,
+
+    #[serde(default)]
+    pub force: bool,
+
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Who claims to be promoting; audited as `claimed_actor`, unverified
+    #[serde(default)]
+    pub actor: Option<String>,
+}
+
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        String(String),
+        Number(serde_json::Number),
+    }
+
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => s,
+        Value::Number(n) => n.to_string(),
+    })
+}
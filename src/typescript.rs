@@ -0,0 +1,252 @@
+// src/typescript.rs
+
+//! TypeScript actions (`action.type: ts`).
+//!
+//! HubSpot runs plain JavaScript, so `.ts` entries are compiled with a local
+//! compiler (`runtime.typescript`) once per execution ([`Compiled`]), and the
+//! Node shim of every invocation runs the output:
+//! - tsc: type-checks and emits CommonJS (local imports are emitted too)
+//! - esbuild: bundles local imports into one CommonJS file (no type check)
+//! - swc: transpiles the entry file only (no type check)
+//!
+//! Output carries inline source maps and Node runs with
+//! `--enable-source-maps`, so stack traces and breakpoints point at the
+//! TypeScript source.
+//!
+//! Loading a config only checks that the compiler can be found
+//! ([`check_tool`]); type errors surface when the action is compiled, and
+//! from `hsemulate validate`.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+use tokio::process::Command as TokioCommand;
+
+use crate::bundle;
+use crate::config::{TsCompiler, TypeScriptConfig};
+
+/// Compiler options used by tsc, for compiling and for `typecheck`.
+const TSC_OPTIONS: &[&str] = &[
+    "--target",
+    "es2020",
+    "--module",
+    "commonjs",
+    "--moduleResolution",
+    "node",
+    "--esModuleInterop",
+    "--skipLibCheck",
+    "--strict",
+    "--pretty",
+    "false",
+];
+
+/// Whether `path` is a TypeScript action.
+pub fn is_typescript(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("ts"))
+}
+
+/// Type-check and compile `entry` into a throwaway directory.
+///
+/// Errors carry the compiler's diagnostics.
+pub fn check(cfg: &TypeScriptConfig, entry: &Path) -> Result<()> {
+    let dir = tempfile::tempdir().context("Failed to create temp dir")?;
+    compile(cfg, entry, dir.path(), false)?;
+    Ok(())
+}
+
+/// Check that the configured compiler (and tsc, with `typecheck`) can be
+/// found, without running it.
+pub fn check_tool(cfg: &TypeScriptConfig) -> Result<()> {
+    let compiler = cfg.compiler.as_str();
+    let mut tools = vec![(compiler, resolve_binary(compiler, cfg.path.as_deref()))];
+    if cfg.typecheck && cfg.compiler != TsCompiler::Tsc {
+        tools.push(("tsc", resolve_binary("tsc", None)));
+    }
+
+    for (name, binary) in tools {
+        if !is_runnable(&binary) {
+            bail!(
+                "TypeScript compiler '{}' not found ({})\n\n\
+                Install it in the project (npm install --save-dev {}) or set\n\
+                runtime.typescript.path in config.yaml",
+                name,
+                binary.display(),
+                install_hint(name)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Compiled output of an action, shared by every invocation of one
+/// execution; removed when dropped.
+#[derive(Debug)]
+pub struct Compiled {
+    _dir: TempDir,
+    file: PathBuf,
+}
+
+impl Compiled {
+    /// Compile `entry` (with inline source maps).
+    pub fn new(cfg: &TypeScriptConfig, entry: &Path) -> Result<Self> {
+        let dir = tempfile::tempdir().context("Failed to create compile dir")?;
+        let file = compile(cfg, entry, dir.path(), true)?;
+        Ok(Compiled { _dir: dir, file })
+    }
+
+    /// Compile `entry` if it is a TypeScript action.
+    pub fn for_action(cfg: &TypeScriptConfig, entry: &Path) -> Result<Option<Self>> {
+        if is_typescript(entry) {
+            Self::new(cfg, entry).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Compile `entry` into `out_dir`; returns the compiled entry file.
+///
+/// With `source_maps`, the output embeds an inline source map.
+pub fn compile(
+    cfg: &TypeScriptConfig,
+    entry: &Path,
+    out_dir: &Path,
+    source_maps: bool,
+) -> Result<PathBuf> {
+    if cfg.typecheck && cfg.compiler != TsCompiler::Tsc {
+        let mut cmd = Command::new(resolve_binary("tsc", None));
+        cmd.arg(entry).arg("--noEmit").args(TSC_OPTIONS);
+        run(cmd, "tsc", entry)?;
+    }
+
+    let stem = entry
+        .file_stem()
+        .context("Action entry has no file name")?
+        .to_string_lossy()
+        .to_string();
+    let out_file = out_dir.join(format!("{}.js", stem));
+
+    let compiler = cfg.compiler.as_str();
+    let mut cmd = Command::new(resolve_binary(compiler, cfg.path.as_deref()));
+    cmd.arg(entry);
+
+    match cfg.compiler {
+        TsCompiler::Tsc => {
+            let root = entry.parent().unwrap_or(Path::new("."));
+            cmd.arg("--outDir")
+                .arg(out_dir)
+                .arg("--rootDir")
+                .arg(root)
+                .arg("--noEmitOnError")
+                .args(TSC_OPTIONS);
+            if source_maps {
+                cmd.args(["--inlineSourceMap", "--inlineSources"]);
+            }
+        }
+        TsCompiler::Esbuild => {
+            cmd.args([
+                "--bundle",
+                "--platform=node",
+                "--format=cjs",
+                "--target=node20",
+                "--packages=external",
+                "--log-level=warning",
+            ])
+            .arg(format!("--outfile={}", out_file.display()));
+            if source_maps {
+                cmd.arg("--sourcemap=inline");
+            }
+        }
+        TsCompiler::Swc => {
+            cmd.arg("-o")
+                .arg(&out_file)
+                .args(["-C", "module.type=commonjs"])
+                .args(["-C", "jsc.parser.syntax=typescript"])
+                .args(["-C", "jsc.target=es2020"]);
+            if source_maps {
+                cmd.args(["--source-maps", "inline"]);
+            }
+        }
+    }
+
+    run(cmd, compiler, entry)?;
+
+    if !out_file.is_file() {
+        bail!(
+            "{} did not produce {} for {}",
+            compiler,
+            out_file.display(),
+            entry.display()
+        );
+    }
+    Ok(out_file)
+}
+
+/// Set up Node to run the compiled output of `entry`.
+///
+/// Must be called before the shim path is added, as `--enable-source-maps`
+/// is a runtime option. Returns the file the shim should load.
+pub fn prepare(compiled: &Compiled, entry: &Path, cmd: &mut TokioCommand) -> Result<PathBuf> {
+    bundle::link_runtime(entry, cmd)?;
+    Ok(compiled.file.clone())
+}
+
+/// Explicit path, else the project's `node_modules/.bin/<name>`, else PATH.
+fn resolve_binary(name: &str, explicit: Option<&str>) -> PathBuf {
+    if let Some(path) = explicit {
+        return PathBuf::from(path);
+    }
+    let local = Path::new("node_modules/.bin").join(name);
+    if local.is_file() {
+        return local;
+    }
+    PathBuf::from(name)
+}
+
+/// A path with a directory part must exist; a bare name must be on PATH.
+fn is_runnable(binary: &Path) -> bool {
+    if binary.components().count() > 1 {
+        return binary.is_file();
+    }
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| {
+            let candidate = dir.join(binary);
+            candidate.is_file() || (cfg!(windows) && candidate.with_extension("cmd").is_file())
+        })
+    })
+}
+
+fn install_hint(compiler: &str) -> &str {
+    match compiler {
+        "swc" => "@swc/cli @swc/core",
+        "tsc" => "typescript",
+        other => other,
+    }
+}
+
+fn run(mut cmd: Command, compiler: &str, entry: &Path) -> Result<()> {
+    let output = cmd.output().with_context(|| {
+        format!(
+            "Failed to run TypeScript compiler '{}'\n\n\
+            Install it in the project (npm install --save-dev {}) or set\n\
+            runtime.typescript.path in config.yaml",
+            compiler,
+            install_hint(compiler)
+        )
+    })?;
+
+    if !output.status.success() {
+        let mut diagnostics = String::from_utf8_lossy(&output.stdout).to_string();
+        diagnostics.push_str(&String::from_utf8_lossy(&output.stderr));
+        bail!(
+            "TypeScript errors in {} ({}):\n{}",
+            entry.display(),
+            compiler,
+            diagnostics.trim_end()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,290 @@
+// src/worker.rs
+
+//! Warm worker pool (`runtime.mode: warm`).
+//!
+//! Cold mode writes the shim to a temp dir and starts a new interpreter for
+//! every invocation, which dominates run time for short actions. Warm mode
+//! keeps long-lived runtime processes running the same shims as a request
+//! loop (`HSE_WORKER=1`):
+//! - each request is one JSON line on STDIN: `{ id, action, event }`
+//! - the shim answers on its protocol channel (see `protocol.rs`), tagging
+//!   every message with the request `id` and ending with `result`
+//!
+//! Workers keep the action module loaded and re-import it when the file
+//! changes on disk. They are keyed by everything fixed at spawn time
+//! (language, runtime binary, env, network allowlist) and are retired after
+//! [`MAX_RUNS_PER_WORKER`] invocations.
+//!
+//! Isolation is weaker than cold mode: module-level state, globals and
+//! timers left running by the action survive into the next invocation
+//! served by the same worker.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin, Command as TokioCommand};
+
+use crate::config::Config;
+use crate::limits::Language;
+use crate::logs::LogLine;
+use crate::metrics::{InvocationMetrics, MemoryTracker};
+use crate::network::NetworkSandbox;
+use crate::protocol::{self, Channel, Lines};
+use crate::shim::shim_for;
+
+/// Invocations served before a worker is replaced (bounds leaked state).
+const MAX_RUNS_PER_WORKER: u64 = 200;
+
+/// Idle workers kept across all keys.
+const MAX_IDLE_WORKERS: usize = 8;
+
+/// Result of one invocation served by a warm worker.
+pub struct WorkerInvocation {
+    pub output: Value,
+    pub metrics: InvocationMetrics,
+    pub logs: Vec<LogLine>,
+}
+
+/// Whether `cfg` can run on warm workers at all.
+///
+/// Enforced memory limits and cgroup accounting are set up per process, so
+/// they always start a fresh (cold) runtime.
+pub fn supported(cfg: &Config) -> bool {
+    !cfg.metrics.cgroup && !cfg.budgets.as_ref().is_some_and(|b| b.enforce_memory)
+}
+
+/// Run one invocation on a warm worker, starting one if none is idle.
+///
+/// With `echo`, log lines are forwarded to our own STDERR as they arrive.
+pub async fn invoke(
+    cfg: &Config,
+    language: Language,
+    runtime: &str,
+    action_file: &Path,
+    event: &Value,
+    echo: bool,
+) -> Result<WorkerInvocation> {
+    let key = WorkerKey {
+        language,
+        runtime: runtime.to_string(),
+        env: cfg.env.clone(),
+        network: cfg.network.as_ref().map(|n| n.allow.clone()),
+    };
+
+    let mut worker = match checkout(&key) {
+        Some(worker) => worker,
+        None => Worker::spawn(cfg, &key).await?,
+    };
+
+    // A worker that failed mid-request is dropped (and killed), not reused
+    let result = worker.invoke(action_file, event, echo).await;
+    if result.is_ok() {
+        checkin(key, worker);
+    }
+    result
+}
+
+/* ---------------- pool ---------------- */
+
+/// Spawn-time settings a worker cannot change afterwards.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct WorkerKey {
+    language: Language,
+    runtime: String,
+    env: BTreeMap<String, String>,
+    network: Option<Vec<String>>,
+}
+
+fn idle() -> &'static Mutex<HashMap<WorkerKey, Vec<Worker>>> {
+    static IDLE: OnceLock<Mutex<HashMap<WorkerKey, Vec<Worker>>>> = OnceLock::new();
+    IDLE.get_or_init(Default::default)
+}
+
+fn checkout(key: &WorkerKey) -> Option<Worker> {
+    let mut idle = idle().lock().ok()?;
+    let workers = idle.get_mut(key)?;
+
+    while let Some(mut worker) = workers.pop() {
+        // Skip workers that exited while idle (e.g. a stray `process.exit`)
+        if matches!(worker.child.try_wait(), Ok(None)) {
+            return Some(worker);
+        }
+    }
+    None
+}
+
+fn checkin(key: WorkerKey, worker: Worker) {
+    if worker.runs >= MAX_RUNS_PER_WORKER {
+        return;
+    }
+
+    let Ok(mut idle) = idle().lock() else {
+        return;
+    };
+    let total: usize = idle.values().map(Vec::len).sum();
+    if total < MAX_IDLE_WORKERS {
+        idle.entry(key).or_default().push(worker);
+    }
+}
+
+/* ---------------- worker ---------------- */
+
+struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Lines,
+    // Raw STDOUT / STDERR lines, drained in the background between requests
+    raw: Arc<Mutex<Vec<LogLine>>>,
+    pid: u32,
+    runs: u64,
+    sandbox: Option<NetworkSandbox>,
+
+    // Holds the shim file for the worker's lifetime
+    _dir: TempDir,
+}
+
+impl Worker {
+    async fn spawn(cfg: &Config, key: &WorkerKey) -> Result<Self> {
+        let dir = tempfile::tempdir().context("Failed to create temp dir")?;
+
+        let (shim_name, shim_code) = shim_for(key.language);
+        let shim_path = dir.path().join(shim_name);
+        std::fs::write(&shim_path, shim_code).context("Failed to write runner shim")?;
+
+        let mut cmd = TokioCommand::new(&key.runtime);
+        cmd.arg(&shim_path)
+            .env("HSE_WORKER", "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        for (k, v) in &key.env {
+            cmd.env(k, v);
+        }
+
+        let sandbox = match &cfg.network {
+            Some(network) => Some(NetworkSandbox::start(network).await?),
+            None => None,
+        };
+        if let Some(sandbox) = &sandbox {
+            sandbox.apply(&mut cmd);
+        }
+
+        let channel = Channel::attach(&mut cmd)?;
+        let mut child = cmd.spawn().context("Failed to spawn worker runtime")?;
+        let pid = child.id().context("Failed to get worker PID")?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .context("Failed to capture worker stdout")?;
+        let stderr = child
+            .stderr
+            .take()
+            .context("Failed to capture worker stderr")?;
+
+        let (messages, stdout) = channel.open(stdout);
+        let raw = Arc::new(Mutex::new(Vec::new()));
+        if let Some(stdout) = stdout {
+            drain(stdout, raw.clone());
+        }
+        drain(stderr, raw.clone());
+
+        Ok(Self {
+            stdin: child.stdin.take().context("Failed to open worker stdin")?,
+            messages,
+            raw,
+            child,
+            pid,
+            runs: 0,
+            sandbox,
+            _dir: dir,
+        })
+    }
+
+    async fn invoke(
+        &mut self,
+        action_file: &Path,
+        event: &Value,
+        echo: bool,
+    ) -> Result<WorkerInvocation> {
+        self.runs += 1;
+        let id = self.runs;
+
+        let mut request = serde_json::to_vec(&json!({
+            "id": id,
+            "action": action_file,
+            "event": event,
+        }))?;
+        request.push(b'\n');
+
+        // Peak RSS of the worker while serving this request
+        let memory = MemoryTracker::start(self.pid, Duration::from_millis(10));
+        let start = Instant::now();
+
+        self.stdin
+            .write_all(&request)
+            .await
+            .context("Failed to send request to warm worker")?;
+        self.stdin.flush().await?;
+
+        let transcript = protocol::collect_request(&mut self.messages, id, |log| {
+            if echo {
+                eprintln!("{}", log.message);
+            }
+        })
+        .await?;
+
+        let duration_ms = start.elapsed().as_millis();
+        let max_rss_kb = memory.stop_and_take();
+
+        let output = transcript.output().context("Warm worker sent no result")?;
+        let mut logs = transcript.logs;
+
+        // Output the action wrote straight to STDOUT / STDERR
+        let raw = std::mem::take(&mut *self.raw.lock().unwrap_or_else(|e| e.into_inner()));
+        for line in raw {
+            if echo {
+                eprintln!("{}", line.message);
+            }
+            logs.push(line);
+        }
+
+        let metrics = InvocationMetrics {
+            duration_ms,
+            max_rss_kb,
+            network_blocked: self
+                .sandbox
+                .as_ref()
+                .map(|s| s.take_blocked())
+                .unwrap_or_default(),
+            phases: transcript.phases,
+            ..Default::default()
+        };
+
+        Ok(WorkerInvocation {
+            output,
+            metrics,
+            logs,
+        })
+    }
+}
+
+/// Collect raw lines from a worker stream until it closes.
+fn drain(stream: impl AsyncRead + Unpin + Send + 'static, raw: Arc<Mutex<Vec<LogLine>>>) {
+    tokio::spawn(async move {
+        let mut lines = Lines::new(stream);
+        while let Some(line) = lines.next_line().await {
+            if let Ok(mut raw) = raw.lock() {
+                raw.push(LogLine::raw(line));
+            }
+        }
+    });
+}